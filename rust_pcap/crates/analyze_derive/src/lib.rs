@@ -1,19 +1,333 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
 use syn;
 
-#[proc_macro_derive(Layer)]
+/// `#[layer(abbrev = "eth")]` sets the protocol abbreviation, the lowercase
+/// type name otherwise. A `layers` field is exposed as the child layers.
+///
+/// Fields are described by `field_info()` and `fields()`, tuned with
+/// `#[layer(offset = 12, format = "hex")]` or hidden with `#[layer(skip)]`.
+/// `format = "debug"` renders a field through its `Debug` implementation.
+#[proc_macro_derive(Layer, attributes(layer))]
 pub fn layer_derive(input: TokenStream) -> TokenStream {
     let ast: syn::DeriveInput = syn::parse(input).unwrap();
     let name = &ast.ident;
+    let abbrev = layer_abbrev(&ast)
+        .unwrap_or_else(|| name.to_string().to_lowercase());
+    let fields = match &ast.data {
+        syn::Data::Struct(data) => data.fields.iter().collect::<Vec<_>>(),
+        _ => vec![],
+    };
+    let children = if fields.iter().any(|f| is_layers(f)) {
+        quote! {
+            fn children(&self) -> Option<&Layers> {
+                Some(&self.layers)
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let mut infos = vec![];
+    let mut values = vec![];
+    for field in fields.iter().filter(|f| !is_layers(f)) {
+        let attrs = FieldAttrs::parse(field);
+        if attrs.skip { continue; }
+        let ident = match &field.ident {
+            Some(ident) => ident,
+            None => continue,
+        };
+        let ty = &field.ty;
+        let ty_str = quote!(#ty).to_string().replace(' ', "");
+        let format = attrs.format.unwrap_or_else(|| default_format(&ty_str).to_string());
+        let format_variant = format_ident!("{}", format_variant(&format));
+        let offset = match attrs.offset {
+            Some(offset) => quote! { Some(#offset) },
+            None => quote! { None },
+        };
+        let field_name = ident.to_string();
+        let idx = infos.len();
+        infos.push(quote! {
+            FieldInfo {
+                name: #field_name,
+                ty: #ty_str,
+                format: FieldFormat::#format_variant,
+                offset: #offset,
+            }
+        });
+        let value = if format == "debug" {
+            quote! { FieldValue::Text(format!("{:?}", self.#ident)) }
+        } else {
+            quote! { ToFieldValue::to_field_value(&self.#ident) }
+        };
+        values.push(quote! {
+            Field { info: &info[#idx], value: #value }
+        });
+    }
+
     let gen = quote! {
         impl Layer for #name {
             fn name() -> &'static str where Self: Sized {
                 stringify!(#name)
             }
+            fn layer_name(&self) -> &'static str {
+                stringify!(#name)
+            }
+            fn abbrev(&self) -> &'static str {
+                #abbrev
+            }
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+            #children
+            fn field_info(&self) -> &'static [FieldInfo] {
+                const FIELDS: &[FieldInfo] = &[#(#infos),*];
+                FIELDS
+            }
+            fn fields(&self) -> Vec<Field> {
+                let info = self.field_info();
+                vec![#(#values),*]
+            }
+        }
+    };
+    gen.into()
+}
+
+fn is_layers(field: &syn::Field) -> bool {
+    field.ident.as_ref().is_some_and(|i| i == "layers")
+}
+
+fn layer_abbrev(ast: &syn::DeriveInput) -> Option<String> {
+    layer_metas(&ast.attrs).into_iter().find_map(|meta| match meta {
+        syn::Meta::NameValue(nv) if nv.path.is_ident("abbrev") => match nv.lit {
+            syn::Lit::Str(s) => Some(s.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn layer_metas(attrs: &[syn::Attribute]) -> Vec<syn::Meta> {
+    attr_metas(attrs, "layer")
+}
+
+/// Items of every `#[name(...)]` attribute
+fn attr_metas(attrs: &[syn::Attribute], name: &str) -> Vec<syn::Meta> {
+    attrs.iter()
+        .filter(|a| a.path.is_ident(name))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(syn::Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|nested| match nested {
+            syn::NestedMeta::Meta(meta) => Some(meta),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    skip: bool,
+    offset: Option<usize>,
+    format: Option<String>,
+}
+
+impl FieldAttrs {
+    fn parse(field: &syn::Field) -> Self {
+        let mut attrs = FieldAttrs::default();
+        for meta in layer_metas(&field.attrs) {
+            match meta {
+                syn::Meta::Path(path) if path.is_ident("skip") => attrs.skip = true,
+                syn::Meta::NameValue(nv) => match (nv.path.get_ident(), nv.lit) {
+                    (Some(i), syn::Lit::Int(offset)) if i == "offset" => {
+                        attrs.offset = Some(offset.base10_parse().unwrap());
+                    }
+                    (Some(i), syn::Lit::Str(format)) if i == "format" => {
+                        attrs.format = Some(format.value());
+                    }
+                    _ => panic!("unknown layer attribute"),
+                },
+                _ => panic!("unknown layer attribute"),
+            }
+        }
+        attrs
+    }
+}
+
+fn default_format(ty: &str) -> &'static str {
+    match ty {
+        "bool" => "bool",
+        "String" | "char" => "text",
+        "[u8;6]" => "mac",
+        "[u8;4]" => "ipv4",
+        "[u8;16]" => "ipv6",
+        _ if ty.starts_with('[') || ty.starts_with("Vec<") => "bytes",
+        _ => "dec",
+    }
+}
+
+fn format_variant(format: &str) -> &'static str {
+    match format {
+        "dec" => "Dec",
+        "hex" => "Hex",
+        "bool" => "Bool",
+        "text" => "Text",
+        "bytes" => "Bytes",
+        "mac" => "Mac",
+        "ipv4" => "IPv4",
+        "ipv6" => "IPv6",
+        "debug" => "Debug",
+        _ => panic!("unknown field format {}", format),
+    }
+}
+
+/// Fixed-layout header parser and serializer, fields are packed MSB first.
+///
+/// `#[header(bits = 4)]` overrides the width of an integer field (the type
+/// width, 1 for `bool`, `8 * N` for `[u8; N]`), `#[header(pad = 3)]` skips
+/// bits before a field, `#[header(little)]` reads a byte-aligned integer as
+/// little endian and `#[header(skip)]` leaves a field to `Default`. Fields
+/// of other types are nested headers.
+#[proc_macro_derive(Header, attributes(header))]
+pub fn header_derive(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let name = &ast.ident;
+    let fields = match &ast.data {
+        syn::Data::Struct(data) => data.fields.iter().collect::<Vec<_>>(),
+        _ => panic!("Header can only be derived for structs"),
+    };
+
+    let mut bits_total = quote! { 0 };
+    let mut reads = vec![];
+    let mut writes = vec![];
+    let mut inits = vec![];
+    for field in fields {
+        let ident = field.ident.as_ref().expect("Header needs named fields");
+        let attrs = HeaderAttrs::parse(field);
+        if attrs.skip {
+            inits.push(quote! { #ident: Default::default() });
+            continue;
+        }
+        let ty = &field.ty;
+        let ty_str = quote!(#ty).to_string().replace(' ', "");
+        let pad = attrs.pad;
+        let (bits, read, write) = match ty_str.as_str() {
+            "bool" => {
+                let bits = attrs.bits.unwrap_or(1);
+                (
+                    quote! { #bits },
+                    quote! { read_bits(data, at, #bits) != 0 },
+                    quote! { write_bits(out, at, #bits, self.#ident as u64) },
+                )
+            }
+            "u8" | "u16" | "u32" | "u64" => {
+                let bits = attrs.bits.unwrap_or(ty_str[1..].parse().unwrap());
+                if attrs.little {
+                    (
+                        quote! { #bits },
+                        quote! { (read_bits(data, at, #bits).swap_bytes() >> (64 - #bits)) as #ty },
+                        quote! { write_bits(out, at, #bits, (self.#ident as u64).swap_bytes() >> (64 - #bits)) },
+                    )
+                } else {
+                    (
+                        quote! { #bits },
+                        quote! { read_bits(data, at, #bits) as #ty },
+                        quote! { write_bits(out, at, #bits, self.#ident as u64) },
+                    )
+                }
+            }
+            _ if ty_str.starts_with("[u8;") => {
+                let len: usize = ty_str["[u8;".len()..ty_str.len() - 1].parse()
+                    .expect("array length must be a literal");
+                let bits = len * 8;
+                (
+                    quote! { #bits },
+                    quote! {{
+                        let mut bytes = [0u8; #len];
+                        for (i, b) in bytes.iter_mut().enumerate() {
+                            *b = read_bits(data, at + i * 8, 8) as u8;
+                        }
+                        bytes
+                    }},
+                    quote! {
+                        for (i, &b) in self.#ident.iter().enumerate() {
+                            write_bits(out, at + i * 8, 8, b as u64);
+                        }
+                    },
+                )
+            }
+            _ => (
+                quote! { <#ty as Header>::BITS },
+                quote! { <#ty as Header>::read(data, at) },
+                quote! { self.#ident.write_bits(out, at) },
+            ),
+        };
+        reads.push(quote! {
+            at += #pad;
+            let #ident = #read;
+            at += #bits;
+        });
+        writes.push(quote! {
+            at += #pad;
+            #write;
+            at += #bits;
+        });
+        inits.push(quote! { #ident });
+        bits_total = quote! { #bits_total + #pad + #bits };
+    }
+
+    let gen = quote! {
+        impl Header for #name {
+            const BITS: usize = #bits_total;
+
+            #[allow(unused_assignments)]
+            fn read(data: &[u8], offset: usize) -> Self {
+                let mut at = offset;
+                #(#reads)*
+                #name { #(#inits),* }
+            }
+
+            #[allow(unused_assignments)]
+            fn write_bits(&self, out: &mut [u8], offset: usize) {
+                let mut at = offset;
+                #(#writes)*
+            }
         }
     };
     gen.into()
 }
+
+#[derive(Default)]
+struct HeaderAttrs {
+    skip: bool,
+    little: bool,
+    bits: Option<usize>,
+    pad: usize,
+}
+
+impl HeaderAttrs {
+    fn parse(field: &syn::Field) -> Self {
+        let mut attrs = HeaderAttrs::default();
+        for meta in attr_metas(&field.attrs, "header") {
+            match meta {
+                syn::Meta::Path(path) if path.is_ident("skip") => attrs.skip = true,
+                syn::Meta::Path(path) if path.is_ident("little") => attrs.little = true,
+                syn::Meta::NameValue(nv) => match (nv.path.get_ident(), nv.lit) {
+                    (Some(i), syn::Lit::Int(bits)) if i == "bits" => {
+                        attrs.bits = Some(bits.base10_parse().unwrap());
+                    }
+                    (Some(i), syn::Lit::Int(pad)) if i == "pad" => {
+                        attrs.pad = pad.base10_parse().unwrap();
+                    }
+                    _ => panic!("unknown header attribute"),
+                },
+                _ => panic!("unknown header attribute"),
+            }
+        }
+        attrs
+    }
+}
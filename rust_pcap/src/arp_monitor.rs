@@ -0,0 +1,231 @@
+use std::collections::{HashSet, VecDeque};
+
+use serde::Serialize;
+
+use crate::*;
+
+#[derive(Debug, Clone)]
+pub struct ARPMonitorConfig {
+    /// Seconds a binding or a request stays fresh: a second MAC claiming an
+    /// IP within it is a duplicate IP rather than a binding change, and a
+    /// reply within it of the matching request is solicited
+    pub window: f64,
+    /// Unsolicited replies to one IP within `window` that raise a flood
+    /// alert
+    pub flood_threshold: usize,
+    /// Sender IPs claimed by one MAC that raise an alert
+    pub ip_threshold: usize,
+}
+
+impl Default for ARPMonitorConfig {
+    fn default() -> Self {
+        Self {
+            window: 10.0,
+            flood_threshold: 20,
+            ip_threshold: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ARPEventKind {
+    /// IP moved to another MAC
+    BindingChange,
+    /// Two MACs claiming the same IP at the same time
+    DuplicateIP,
+    /// Burst of replies to an IP that did not ask for them
+    ReplyFlood,
+    /// MAC claiming many IPs
+    ManyIPs,
+}
+
+/// Flat record so that events can be written as CSV rows
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ARPEvent {
+    pub ts: f64,
+    pub kind: ARPEventKind,
+    /// IP of the binding, or the flooded IP
+    pub ip: String,
+    /// MAC of the new binding, of the flooding replies, or claiming the IPs
+    pub mac: String,
+    /// Previous MAC of the IP
+    pub previous_mac: Option<String>,
+    /// Replies of a flood, or IPs claimed by the MAC
+    pub count: Option<usize>,
+}
+
+/// MAC an IP resolved to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ARPBinding {
+    pub mac: [u8; 6],
+    pub first_seen: f64,
+    pub last_seen: f64,
+}
+
+/// Builds the IP to MAC binding table from ARP senders and detects
+/// spoofing and address conflicts
+#[derive(Debug, Default)]
+pub struct ARPMonitor {
+    pub config: ARPMonitorConfig,
+    bindings: HashMap<[u8; 4], ARPBinding>,
+    /// IPs claimed by each MAC
+    claims: HashMap<[u8; 6], HashSet<[u8; 4]>>,
+    /// Timestamp of the latest request from an IP for a target IP
+    requests: HashMap<([u8; 4], [u8; 4]), f64>,
+    /// Timestamps of recent unsolicited replies to each IP
+    unsolicited: HashMap<[u8; 4], VecDeque<f64>>,
+    /// IPs with a flood alert raised until their window drains
+    flooded: HashSet<[u8; 4]>,
+    last_sweep: f64,
+}
+
+impl ARPMonitor {
+    const SWEEP_PERIOD: f64 = 1.0;
+
+    pub fn new(config: ARPMonitorConfig) -> Self {
+        Self { config, ..default() }
+    }
+
+    pub fn binding(&self, ip: [u8; 4]) -> Option<&ARPBinding> {
+        self.bindings.get(&ip)
+    }
+
+    pub fn bindings(&self) -> impl Iterator<Item=(&[u8; 4], &ARPBinding)> {
+        self.bindings.iter()
+    }
+
+    pub fn apply(&mut self, frame: &Frame) -> Vec<ARPEvent> {
+        let mut events = vec![];
+        let arp = match frame.get_layer::<ARP>() {
+            Some(arp) => arp,
+            None => return events,
+        };
+        let (sha, spa, tpa) = match (arp.sender_mac(), arp.sender_ip(), arp.target_ip()) {
+            (Some(sha), Some(spa), Some(tpa)) => (sha, spa, tpa),
+            _ => return events,
+        };
+        self.sweep(frame.ts);
+        match arp.oper {
+            ARP::REQUEST => {
+                self.requests.insert((spa, tpa), frame.ts);
+            }
+            ARP::REPLY if !arp.is_gratuitous() => {
+                let solicited = self.requests.remove(&(tpa, spa))
+                    .is_some_and(|ts| frame.ts - ts <= self.config.window);
+                if !solicited {
+                    events.extend(self.unsolicited_reply(frame.ts, sha, tpa));
+                }
+            }
+            _ => {}
+        }
+        // probes have no sender IP to bind
+        if spa != [0; 4] {
+            events.extend(self.bind(frame.ts, spa, sha));
+        }
+        events
+    }
+
+    fn bind(&mut self, ts: f64, ip: [u8; 4], mac: [u8; 6]) -> Vec<ARPEvent> {
+        let mut events = vec![];
+        let binding = self.bindings.entry(ip)
+            .or_insert(ARPBinding { mac, first_seen: ts, last_seen: ts });
+        if binding.mac != mac {
+            let kind = if ts - binding.last_seen <= self.config.window {
+                ARPEventKind::DuplicateIP
+            } else {
+                ARPEventKind::BindingChange
+            };
+            events.push(ARPEvent {
+                ts,
+                kind,
+                ip: fmt_iter!(ip, "."),
+                mac: fmt_iter!(mac, ":", "{:02x}"),
+                previous_mac: Some(fmt_iter!(binding.mac, ":", "{:02x}")),
+                count: None,
+            });
+            if let Some(claims) = self.claims.get_mut(&binding.mac) {
+                claims.remove(&ip);
+                if claims.is_empty() {
+                    self.claims.remove(&binding.mac);
+                }
+            }
+            *binding = ARPBinding { mac, first_seen: ts, last_seen: ts };
+        }
+        binding.last_seen = ts;
+        let claims = self.claims.entry(mac).or_default();
+        if claims.insert(ip) && claims.len() == self.config.ip_threshold {
+            events.push(ARPEvent {
+                ts,
+                kind: ARPEventKind::ManyIPs,
+                ip: fmt_iter!(ip, "."),
+                mac: fmt_iter!(mac, ":", "{:02x}"),
+                previous_mac: None,
+                count: Some(claims.len()),
+            });
+        }
+        events
+    }
+
+    fn unsolicited_reply(&mut self, ts: f64, mac: [u8; 6], target: [u8; 4]) -> Option<ARPEvent> {
+        let replies = self.unsolicited.entry(target).or_default();
+        replies.push_back(ts);
+        while replies.front().is_some_and(|&t| ts - t > self.config.window) {
+            replies.pop_front();
+        }
+        if replies.len() < self.config.flood_threshold {
+            self.flooded.remove(&target);
+            return None;
+        }
+        self.flooded.insert(target).then(|| ARPEvent {
+            ts,
+            kind: ARPEventKind::ReplyFlood,
+            ip: fmt_iter!(target, "."),
+            mac: fmt_iter!(mac, ":", "{:02x}"),
+            previous_mac: None,
+            count: Some(replies.len()),
+        })
+    }
+
+    /// Forgets stale requests and drained reply windows
+    fn sweep(&mut self, ts: f64) {
+        if ts - self.last_sweep < Self::SWEEP_PERIOD {
+            return;
+        }
+        self.last_sweep = ts;
+        let window = self.config.window;
+        self.requests.retain(|_, &mut t| ts - t <= window);
+        self.unsolicited.retain(|_, replies| replies.back().is_some_and(|&t| ts - t <= window));
+        let unsolicited = &self.unsolicited;
+        self.flooded.retain(|ip| unsolicited.contains_key(ip));
+    }
+}
+
+impl ARPEvent {
+    /// The event as a JSON object on a single line
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl std::fmt::Display for ARPEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ARPEventKind::BindingChange => write!(
+                f, "{} ARP binding change: {} moved from {} to {}",
+                self.ts, self.ip, self.previous_mac.as_deref().unwrap_or_default(), self.mac,
+            ),
+            ARPEventKind::DuplicateIP => write!(
+                f, "{} duplicate IP {} claimed by {} and {}",
+                self.ts, self.ip, self.previous_mac.as_deref().unwrap_or_default(), self.mac,
+            ),
+            ARPEventKind::ReplyFlood => write!(
+                f, "{} ARP reply flood: {} unsolicited replies to {}, latest from {}",
+                self.ts, self.count.unwrap_or_default(), self.ip, self.mac,
+            ),
+            ARPEventKind::ManyIPs => write!(
+                f, "{} {} claims {} IPs, latest {}",
+                self.ts, self.mac, self.count.unwrap_or_default(), self.ip,
+            ),
+        }
+    }
+}
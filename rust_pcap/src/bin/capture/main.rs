@@ -4,8 +4,8 @@ use rand::{Rng, thread_rng};
 use rand::rngs::ThreadRng;
 use tracing::info;
 
-use rust_pcap::{Codec, default, PcapIterator};
-use rust_pcap::counter::Count;
+use rust_pcap::{Codec, default, PcapIterator, Registry};
+use rust_pcap::counter::{Count, ECHO_HEADERS};
 
 const RNG: bool = true;
 
@@ -21,6 +21,19 @@ fn main() -> Result<(), Box<dyn ::std::error::Error>> {
     let device_name = args.get(1).unwrap();
     let capture_period = args.get(2).unwrap()
         .parse::<i64>().unwrap();
+    let registry = match args.iter().position(|a| a == "--dispatch") {
+        Some(idx) => Registry::load(args.get(idx + 1).ok_or("--dispatch needs a file")?)?,
+        None => Registry::default(),
+    };
+    // Layout 1 is what `csv` and the trained model read, layout 2 appends the
+    // echo pairing stats
+    let layout = match args.iter().position(|a| a == "--layout") {
+        Some(idx) => args.get(idx + 1).ok_or("--layout needs a version")?.parse::<u8>()?,
+        None => 1,
+    };
+    if !(1..=2).contains(&layout) {
+        return Err(format!("unknown layout {}", layout).into());
+    }
     let device = Device::list()
         .unwrap()
         .into_iter()
@@ -73,13 +86,18 @@ fn main() -> Result<(), Box<dyn ::std::error::Error>> {
         }
         info!("{}/{}; Pick {} packets", elapsed, capture_period, packets.len());
     }
-    let counts = Count::compute(PcapIterator::new(packets), Some(3.0));
+    let counts = Count::compute(PcapIterator::new(packets).with_registry(registry), Some(3.0));
     // dbg!(counts);
 
-    let path = if RNG { "data_set.csv" } else { "data_set_default.csv" };
+    let path = match (RNG, layout) {
+        (true, 1) => "data_set.csv".to_string(),
+        (false, 1) => "data_set_default.csv".to_string(),
+        (true, _) => format!("data_set_v{}.csv", layout),
+        (false, _) => format!("data_set_default_v{}.csv", layout),
+    };
     let mut wtr = csv::Writer::from_path(path)?;
     {
-        wtr.write_record([
+        let mut headers = vec![
             "total",
             "echo_req",
             "echo_res",
@@ -100,10 +118,14 @@ fn main() -> Result<(), Box<dyn ::std::error::Error>> {
             "avg_deltas_size",
             "avg_time",
             "avg_deltas_time",
-        ])?;
+        ];
+        if layout >= 2 {
+            headers.extend(ECHO_HEADERS);
+        }
+        wtr.write_record(headers)?;
     }
     for count in counts {
-        let row = [
+        let mut row = vec![
             count.total as f32,
             count.echo_req as f32,
             count.echo_res as f32,
@@ -125,7 +147,10 @@ fn main() -> Result<(), Box<dyn ::std::error::Error>> {
             count.avg_time,
             count.avg_deltas_time,
         ];
-        wtr.write_record(row.map(|e| e.to_string()))?;
+        if layout >= 2 {
+            row.extend(count.echo_row());
+        }
+        wtr.write_record(row.iter().map(|e| e.to_string()))?;
     }
     Ok(())
 }
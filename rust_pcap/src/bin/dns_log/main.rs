@@ -0,0 +1,49 @@
+use std::fs::File;
+
+use rust_pcap::{Frame, Pcap, PcapNG, Registry};
+use rust_pcap::dns_log::DNSTracker;
+
+/// `dns_log <capture> [--passive] [--dispatch <file>]`, writes DNS transactions, or the passive
+/// DNS table, as CSV to stdout
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let file_name = args.get(1).ok_or("usage: dns_log <capture> [--passive] [--dispatch <file>]")?;
+    let passive = args.iter().any(|a| a == "--passive");
+    let registry = match args.iter().position(|a| a == "--dispatch") {
+        Some(idx) => Registry::load(args.get(idx + 1).ok_or("--dispatch needs a file")?)?,
+        None => Registry::default(),
+    };
+    let file = File::open(file_name)?;
+    let frames: Box<dyn Iterator<Item=Frame>> = if file_name.ends_with(".pcapng") {
+        Box::new(PcapNG::new(file).with_registry(registry))
+    } else {
+        Box::new(Pcap::new(file).with_registry(registry))
+    };
+    let mut tracker = DNSTracker::default();
+    let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+    for frame in frames {
+        for transaction in tracker.apply(&frame) {
+            if !passive {
+                writer.serialize(transaction)?;
+            }
+        }
+    }
+    if passive {
+        writer.write_record(["name", "address", "first_seen", "last_seen", "count"])?;
+        for (name, address, entry) in tracker.passive.iter() {
+            writer.write_record([
+                name.to_string(),
+                address.to_string(),
+                entry.first_seen.to_string(),
+                entry.last_seen.to_string(),
+                entry.count.to_string(),
+            ])?;
+        }
+    } else {
+        for transaction in tracker.finish() {
+            writer.serialize(transaction)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
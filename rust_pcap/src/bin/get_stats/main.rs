@@ -1,29 +1,85 @@
 use std::fs::File;
 use std::time::Instant;
 
+use rust_pcap::{Frame, Pcap, PcapNG, Registry};
+use rust_pcap::arp_monitor::ARPMonitor;
+use rust_pcap::bpf::BpfFilter;
 use rust_pcap::counter::Count;
+use rust_pcap::dhcp_monitor::DHCPMonitor;
+use rust_pcap::filter::Filter;
+use rust_pcap::fingerprint::FingerprintAllowlist;
+use rust_pcap::icmp_tunnel::ICMPTunnelDetector;
 
-fn main() {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = std::env::args().collect();
     let file_name = args.get(1).unwrap();
+    let filter = match args.iter().position(|a| a == "--filter") {
+        Some(idx) => Some(args.get(idx + 1).ok_or("--filter needs an expression")?.parse::<Filter>()?),
+        None => None,
+    };
+    let bpf = match args.iter().position(|a| a == "--bpf") {
+        Some(idx) => Some(BpfFilter::new(args.get(idx + 1).ok_or("--bpf needs an expression")?, true)?),
+        None => None,
+    };
+    let allowlist = match args.iter().position(|a| a == "--tls-allowlist") {
+        Some(idx) => Some(FingerprintAllowlist::load(args.get(idx + 1).ok_or("--tls-allowlist needs a file")?)?),
+        None => None,
+    };
+    let registry = match args.iter().position(|a| a == "--dispatch") {
+        Some(idx) => Registry::load(args.get(idx + 1).ok_or("--dispatch needs a file")?)?,
+        None => Registry::default(),
+    };
+    let mut arp_monitor = args.iter().any(|a| a == "--arp-monitor").then(ARPMonitor::default);
+    let mut dhcp_monitor = args.iter().any(|a| a == "--dhcp-monitor").then(DHCPMonitor::default);
+    let mut icmp_tunnel = args.iter().any(|a| a == "--icmp-tunnel").then(ICMPTunnelDetector::default);
     let file = File::open(file_name).unwrap();
     let now = Instant::now();
-    let counts = if file_name.ends_with(".pcapng") {
-        Count::compute_ng(file, None)
-    } else {
-        Count::compute_legacy(file, None)
+    let frames: Box<dyn Iterator<Item=Frame>> = match (file_name.ends_with(".pcapng"), bpf) {
+        (true, Some(bpf)) => Box::new(PcapNG::new(file).with_registry(registry).with_bpf(bpf)),
+        (true, None) => Box::new(PcapNG::new(file).with_registry(registry)),
+        (false, Some(bpf)) => Box::new(Pcap::new(file).with_registry(registry).with_bpf(bpf)),
+        (false, None) => Box::new(Pcap::new(file).with_registry(registry)),
     };
+    let frames = frames.filter(|frame| filter.as_ref().is_none_or(|f| f.matches(frame)))
+        .inspect(|frame| {
+            for unknown in allowlist.iter().flat_map(|a| a.check(frame)) {
+                eprintln!("{}", unknown);
+            }
+            for event in arp_monitor.iter_mut().flat_map(|m| m.apply(frame)) {
+                eprintln!("{}", event);
+            }
+            for alert in dhcp_monitor.iter_mut().flat_map(|m| m.apply(frame)) {
+                eprintln!("{}", alert);
+            }
+            for alert in icmp_tunnel.iter_mut().flat_map(|d| d.apply(frame)) {
+                eprintln!("{}", alert);
+            }
+        });
+    let counts = Count::compute(frames, None);
     println!("Elapsed {}ms", now.elapsed().as_millis());
-    let count = counts.get(0).unwrap();
+    let count = counts.first().unwrap();
     dbg!(count.total);
     dbg!(count.ip);
     dbg!(count.tcp);
     dbg!(count.udp);
     dbg!(count.icmp);
     dbg!(count.arp);
+    dbg!(&count.arp_messages);
     dbg!(count.http);
     dbg!(count.dhcp);
+    dbg!(&count.dhcp_messages);
+    dbg!(count.dhcpv6);
+    dbg!(&count.dhcpv6_messages);
+    dbg!(count.dns_queries);
+    dbg!(count.dns_responses);
+    dbg!(count.dns_nxdomain);
     dbg!(count.opc_ua);
+    dbg!(count.tls);
+    dbg!(&count.tls_versions);
+    dbg!(count.sni.len());
+    dbg!(count.ja3.len());
+    dbg!(count.ja3s.len());
+    dbg!(count.ja4.len());
     dbg!(count.addresses.len());
     dbg!(count.ports.len());
     dbg!(count.bytes);
@@ -32,4 +88,18 @@ fn main() {
     dbg!(count.avg_deltas_size);
     dbg!(count.avg_time);
     dbg!(count.avg_deltas_time);
+    dbg!(&count.rtt.handshake);
+    dbg!(&count.rtt.data);
+    dbg!(&count.rtt.timestamp);
+    for (connection, rtt) in &count.rtt.connections {
+        println!("{}: {:?}", connection, rtt);
+    }
+    dbg!(&count.echo.all);
+    for (target, echo) in &count.echo.targets {
+        println!("{}: {:?}", std::net::Ipv4Addr::from(*target), echo);
+    }
+    for lease in &count.leases {
+        println!("{}: {:?}", std::net::Ipv4Addr::from(lease.ip), lease);
+    }
+    Ok(())
 }
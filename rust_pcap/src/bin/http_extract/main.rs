@@ -0,0 +1,28 @@
+use std::fs::File;
+
+use rust_pcap::{Frame, Pcap, PcapNG, Registry};
+use rust_pcap::extract::HTTPExtractor;
+
+/// `http_extract <capture> <directory> [--dispatch <file>]`, carves HTTP response bodies
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let usage = "usage: http_extract <capture> <directory> [--dispatch <file>]";
+    let file_name = args.get(1).ok_or(usage)?;
+    let dir = args.get(2).ok_or(usage)?;
+    let registry = match args.iter().position(|a| a == "--dispatch") {
+        Some(idx) => Registry::load(args.get(idx + 1).ok_or("--dispatch needs a file")?)?,
+        None => Registry::default(),
+    };
+    let file = File::open(file_name)?;
+    let frames: Box<dyn Iterator<Item=Frame>> = if file_name.ends_with(".pcapng") {
+        Box::new(PcapNG::new(file).with_registry(registry))
+    } else {
+        Box::new(Pcap::new(file).with_registry(registry))
+    };
+    let mut extractor = HTTPExtractor::new(dir)?;
+    for frame in frames {
+        extractor.apply(&frame)?;
+    }
+    println!("Extracted {} objects into {}", extractor.finish()?, dir);
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use std::fs::File;
+use std::io::Write;
+
+use rust_pcap::{Frame, Pcap, PcapNG, Registry};
+use rust_pcap::http_log::HTTPLogEntry;
+
+/// `http_log <capture> [--json] [--dispatch <file>]`, writes CSV (default) or JSON lines to stdout
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let file_name = args.get(1).ok_or("usage: http_log <capture> [--json] [--dispatch <file>]")?;
+    let json = args.iter().any(|a| a == "--json");
+    let registry = match args.iter().position(|a| a == "--dispatch") {
+        Some(idx) => Registry::load(args.get(idx + 1).ok_or("--dispatch needs a file")?)?,
+        None => Registry::default(),
+    };
+    let file = File::open(file_name)?;
+    let frames: Box<dyn Iterator<Item=Frame>> = if file_name.ends_with(".pcapng") {
+        Box::new(PcapNG::new(file).with_registry(registry))
+    } else {
+        Box::new(Pcap::new(file).with_registry(registry))
+    };
+    let entries = frames.flat_map(|frame| HTTPLogEntry::from_frame(&frame));
+    if json {
+        let mut out = std::io::stdout().lock();
+        for entry in entries {
+            writeln!(out, "{}", entry.to_json())?;
+        }
+    } else {
+        let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+        for entry in entries {
+            writer.serialize(entry)?;
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use pcap::*;
 use tracing::info;
 
-use rust_pcap::{Codec, PcapIterator};
+use rust_pcap::{Codec, PcapIterator, Registry};
 use rust_pcap::counter::Count;
 use rust_pcap::tf::TFBuilder;
 
@@ -15,6 +15,10 @@ fn main() -> Result<(), Box<dyn ::std::error::Error>> {
 
     let args: Vec<String> = std::env::args().collect();
     let device_name = args.get(1).unwrap();
+    let registry = match args.iter().position(|a| a == "--dispatch") {
+        Some(idx) => Registry::load(args.get(idx + 1).ok_or("--dispatch needs a file")?)?,
+        None => Registry::default(),
+    };
     let device = Device::list()
         .unwrap()
         .into_iter()
@@ -71,7 +75,7 @@ fn main() -> Result<(), Box<dyn ::std::error::Error>> {
                 break;
             }
         }
-        let mut stats = Count::compute(PcapIterator::new(packets), None);
+        let mut stats = Count::compute(PcapIterator::new(packets).with_registry(registry.clone()), None);
         if let Some(stat) = stats.pop() {
             let row = [
                 stat.total as f32,
@@ -79,7 +83,7 @@ fn main() -> Result<(), Box<dyn ::std::error::Error>> {
                 stat.addresses.len() as f32,
                 stat.echo_req as f32,
                 stat.echo_res as f32,
-                (stat.echo_req - stat.echo_res) as f32,
+                stat.echo_req.saturating_sub(stat.echo_res) as f32,
             ];
 
             let result = nn.restored_eval(&row)?;
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use pcap::{BpfProgram, Capture};
+use pcap_parser::Linktype;
+
+/// BPF capture filter evaluated on raw frame bytes, before dissection.
+///
+/// The expression is compiled by libpcap against a dead handle, once per
+/// link type seen, so offline captures filter like `Capture::filter`.
+pub struct BpfFilter {
+    expr: String,
+    optimize: bool,
+    /// `None` when the expression does not compile for that link type
+    programs: HashMap<i32, Option<BpfProgram>>,
+    /// Frames rejected so far
+    pub rejected: usize,
+}
+
+impl BpfFilter {
+    /// Check the expression against Ethernet, the usual link type
+    pub fn new(expr: &str, optimize: bool) -> Result<Self, pcap::Error> {
+        let program = compile(expr, optimize, Linktype::ETHERNET)?;
+        let mut programs = HashMap::new();
+        programs.insert(Linktype::ETHERNET.0, Some(program));
+        Ok(Self {
+            expr: expr.to_string(),
+            optimize,
+            programs,
+            rejected: 0,
+        })
+    }
+
+    pub fn expr(&self) -> &str {
+        &self.expr
+    }
+
+    pub fn matches(&mut self, data: &[u8], link_type: Linktype) -> bool {
+        let (expr, optimize) = (&self.expr, self.optimize);
+        let program = self.programs.entry(link_type.0).or_insert_with(|| {
+            compile(expr, optimize, link_type)
+                .map_err(|e| eprintln!("bpf `{}` on {:?}: {}", expr, link_type, e))
+                .ok()
+        });
+        let matches = program.as_ref().is_some_and(|p| p.filter(data));
+        if !matches {
+            self.rejected += 1;
+        }
+        matches
+    }
+}
+
+fn compile(expr: &str, optimize: bool, link_type: Linktype) -> Result<BpfProgram, pcap::Error> {
+    Capture::dead(pcap::Linktype(link_type.0))?.compile(expr, optimize)
+}
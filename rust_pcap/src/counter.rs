@@ -4,7 +4,13 @@ use std::fs::File;
 use derivative::Derivative;
 
 use crate::*;
+use crate::dhcpv6::DHCPv6;
+use crate::dns::DNS;
+use crate::echo::{EchoCount, EchoTracker};
+use crate::lease::{Lease, LeaseTable};
 use crate::opc_ua::OpcUa;
+use crate::tls::{TLS, TLSHandshake};
+use crate::rtt::{RTTCount, RTTTracker};
 
 #[derive(Default, Debug)]
 pub struct IPCount {
@@ -13,6 +19,42 @@ pub struct IPCount {
     pub mf: usize,
 }
 
+#[derive(Default, Debug)]
+pub struct DHCPCount {
+    pub discover: usize,
+    pub offer: usize,
+    pub request: usize,
+    pub decline: usize,
+    pub ack: usize,
+    pub nak: usize,
+    pub release: usize,
+    pub inform: usize,
+}
+
+#[derive(Default, Debug)]
+pub struct ARPCount {
+    pub request: usize,
+    pub reply: usize,
+    pub gratuitous: usize,
+    pub probe: usize,
+}
+
+#[derive(Default, Debug)]
+pub struct DHCPv6Count {
+    pub solicit: usize,
+    pub advertise: usize,
+    pub request: usize,
+    pub confirm: usize,
+    pub renew: usize,
+    pub rebind: usize,
+    pub reply: usize,
+    pub release: usize,
+    pub decline: usize,
+    pub information_request: usize,
+    pub relay_forw: usize,
+    pub relay_repl: usize,
+}
+
 #[derive(Default, Debug)]
 pub struct TCPCount {
     pub ns: usize,
@@ -48,10 +90,30 @@ pub struct Count {
     pub tcp_flags: TCPCount,
     pub udp: usize,
     pub arp: usize,
+    pub arp_messages: ARPCount,
     pub http: usize,
     pub smtp: usize,
     pub dhcp: usize,
+    pub dhcp_messages: DHCPCount,
+    pub dhcpv6: usize,
+    pub dhcpv6_messages: DHCPv6Count,
+    pub dns_queries: usize,
+    pub dns_responses: usize,
+    pub dns_nxdomain: usize,
     pub opc_ua: usize,
+    /// Frames carrying TLS records
+    pub tls: usize,
+    /// Versions negotiated by ServerHello
+    pub tls_versions: HashMap<u16, usize>,
+    #[derivative(Debug(format_with = "_count_fmt"))]
+    pub sni: HashSet<String>,
+    /// Distinct client and server fingerprints
+    #[derivative(Debug(format_with = "_count_fmt"))]
+    pub ja3: HashSet<String>,
+    #[derivative(Debug(format_with = "_count_fmt"))]
+    pub ja3s: HashSet<String>,
+    #[derivative(Debug(format_with = "_count_fmt"))]
+    pub ja4: HashSet<String>,
 
     #[derivative(Debug(format_with = "_count_fmt"))]
     pub addresses: HashSet<[u8; 4]>,
@@ -65,8 +127,16 @@ pub struct Count {
     pub avg_deltas_size: f32,
     pub avg_time: f32,
     pub avg_deltas_time: f32,
+
+    pub rtt: RTTCount,
+    pub echo: EchoCount,
+    /// Leases granted, renewed or released in the period, as left by the
+    /// message
+    pub leases: Vec<Lease>,
 }
 
+pub const ECHO_HEADERS: [&str; 4] = ["echo_loss", "echo_rtt", "echo_duplicates", "echo_late"];
+
 impl Count {
     pub fn flush(&mut self, sizes: &mut Vec<usize>, intervals: &mut Vec<f64>) -> Self {
         let pkt_count = sizes.len();
@@ -82,6 +152,8 @@ impl Count {
         self.avg_deltas_time = intervals.iter()
             .map(|&t| (self.avg_time - t as f32).abs())
             .sum::<f32>() / intervals.len().max(1) as f32;
+        self.rtt.flush();
+        self.echo.flush();
         sizes.clear();
         intervals.clear();
         std::mem::replace(self, Count::default())
@@ -125,16 +197,83 @@ impl Count {
             self.udp += 1;
             self.data_bytes += udp.payload.len();
         }
-        if let Some(_arp) = frame.get_layer::<ARP>() {
+        if let Some(arp) = frame.get_layer::<ARP>() {
             self.arp += 1;
+            let messages = &mut self.arp_messages;
+            match arp.oper {
+                ARP::REQUEST => messages.request += 1,
+                ARP::REPLY => messages.reply += 1,
+                _ => {}
+            }
+            if arp.is_gratuitous() { messages.gratuitous += 1 }
+            if arp.is_probe() { messages.probe += 1 }
         }
-        if let Some(http) = frame.get_layer::<HTTP>() {
-            if http.is_finalized {
-                self.http += 1;
+        if let Some(tcp) = frame.get_layer::<TCP>() {
+            self.http += tcp.layers().get_all::<HTTP>()
+                .filter(|http| http.is_finalized)
+                .count();
+        }
+        if let Some(tcp) = frame.get_layer::<TCP>() {
+            let mut records = tcp.layers().get_all::<TLS>().peekable();
+            if records.peek().is_some() {
+                self.tls += 1;
+            }
+            for handshake in records.flat_map(|r| r.handshakes()) {
+                match handshake {
+                    TLSHandshake::ClientHello(hello) => {
+                        self.sni.extend(hello.sni.clone());
+                        self.ja3.insert(hello.ja3());
+                        self.ja4.insert(hello.ja4());
+                    }
+                    TLSHandshake::ServerHello(hello) => {
+                        *self.tls_versions.entry(hello.negotiated_version()).or_default() += 1;
+                        self.ja3s.insert(hello.ja3s());
+                    }
+                    _ => {}
+                }
             }
         }
-        if let Some(_dhcp) = frame.get_layer::<DHCP>() {
+        if let Some(dhcp) = frame.get_layer::<DHCP>() {
             self.dhcp += 1;
+            let messages = &mut self.dhcp_messages;
+            match dhcp.message_type {
+                Some(DHCP::DISCOVER) => messages.discover += 1,
+                Some(DHCP::OFFER) => messages.offer += 1,
+                Some(DHCP::REQUEST) => messages.request += 1,
+                Some(DHCP::DECLINE) => messages.decline += 1,
+                Some(DHCP::ACK) => messages.ack += 1,
+                Some(DHCP::NAK) => messages.nak += 1,
+                Some(DHCP::RELEASE) => messages.release += 1,
+                Some(DHCP::INFORM) => messages.inform += 1,
+                _ => {}
+            }
+        }
+        if let Some(dhcpv6) = frame.get_layer::<DHCPv6>() {
+            self.dhcpv6 += 1;
+            let messages = &mut self.dhcpv6_messages;
+            match dhcpv6.msg_type {
+                DHCPv6::SOLICIT => messages.solicit += 1,
+                DHCPv6::ADVERTISE => messages.advertise += 1,
+                DHCPv6::REQUEST => messages.request += 1,
+                DHCPv6::CONFIRM => messages.confirm += 1,
+                DHCPv6::RENEW => messages.renew += 1,
+                DHCPv6::REBIND => messages.rebind += 1,
+                DHCPv6::REPLY => messages.reply += 1,
+                DHCPv6::RELEASE => messages.release += 1,
+                DHCPv6::DECLINE => messages.decline += 1,
+                DHCPv6::INFORMATION_REQUEST => messages.information_request += 1,
+                DHCPv6::RELAY_FORW => messages.relay_forw += 1,
+                DHCPv6::RELAY_REPL => messages.relay_repl += 1,
+                _ => {}
+            }
+        }
+        for dns in frame.layers_iter().filter_map(|l| l.as_any().downcast_ref::<DNS>()) {
+            if !dns.qr {
+                self.dns_queries += 1;
+            } else {
+                self.dns_responses += 1;
+                if dns.is_nxdomain() { self.dns_nxdomain += 1 }
+            }
         }
         if let Some(opc_ua) = frame.get_layer::<OpcUa>() {
             self.opc_ua += 1;
@@ -168,6 +307,10 @@ impl Count {
 
         let mut intervals = Vec::new();
 
+        let mut rtt = RTTTracker::default();
+        let mut echo = EchoTracker::default();
+        let mut leases = LeaseTable::default();
+
         for frame in pcap {
             _first.get_or_insert(frame.ts);
             _last = Some(frame.ts);
@@ -195,9 +338,13 @@ impl Count {
             last = Some(frame.ts);
             sizes.push(frame.data.len());
             count.apply(&frame);
+            count.rtt.samples.extend(rtt.apply(&frame));
+            count.echo.samples.extend(echo.apply(&frame));
+            count.leases.extend(leases.apply(&frame).cloned());
         }
 
         if !sizes.is_empty() {
+            count.echo.samples.extend(echo.finish());
             counts.push(count.flush(&mut sizes, &mut intervals));
         }
         // println!("COUNTS EXPECT TOTAL PERIOD {}", _last.unwrap() - _first.unwrap());
@@ -228,4 +375,15 @@ impl Count {
             self.avg_deltas_time,
         ]
     }
+
+    /// Columns appended by version 2 of the data set layout, named by
+    /// `ECHO_HEADERS`
+    pub fn echo_row(&self) -> Vec<f32> {
+        vec![
+            self.echo.all.loss,
+            self.echo.all.rtt.avg,
+            self.echo.all.duplicates as f32,
+            self.echo.all.late as f32,
+        ]
+    }
 }
\ No newline at end of file
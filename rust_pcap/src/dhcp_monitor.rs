@@ -0,0 +1,162 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::*;
+use crate::ethernet::Ethernet;
+
+#[derive(Debug, Clone)]
+pub struct DHCPMonitorConfig {
+    /// Seconds from the first DHCP message during which answering servers
+    /// are learned as authorized, `None` to rely on [`DHCPMonitor::authorize`].
+    /// Learning trusts first responders: a rogue server answering within it
+    /// is authorized too, so disable it when the servers are known.
+    pub learning: Option<f64>,
+    /// Sliding window of the starvation check, in seconds
+    pub window: f64,
+    /// DISCOVERs with distinct client addresses in `window` that raise a
+    /// starvation alert
+    pub threshold: usize,
+}
+
+impl Default for DHCPMonitorConfig {
+    fn default() -> Self {
+        Self {
+            learning: Some(60.0),
+            window: 10.0,
+            threshold: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DHCPAlertKind {
+    /// OFFER or ACK from a server identifier or MAC not authorized
+    RogueServer {
+        server_id: [u8; 4],
+        mac: [u8; 6],
+        message_type: u8,
+        /// Address offered or assigned
+        yiaddr: [u8; 4],
+    },
+    /// Burst of DISCOVERs with many client hardware addresses
+    Starvation {
+        discovers: usize,
+        clients: usize,
+        /// Ethernet sources that sent them, usually one for a spoofing host
+        sources: usize,
+        /// Seconds covered
+        window: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DHCPAlert {
+    pub ts: f64,
+    pub kind: DHCPAlertKind,
+}
+
+/// Detects rogue DHCP servers and DHCP starvation
+#[derive(Debug, Default)]
+pub struct DHCPMonitor {
+    pub config: DHCPMonitorConfig,
+    /// Server identifier and MAC pairs
+    authorized: HashSet<([u8; 4], [u8; 6])>,
+    /// Rogue server and MAC pairs already reported
+    reported: HashSet<([u8; 4], [u8; 6])>,
+    /// Learning ends at this timestamp
+    learning_until: Option<f64>,
+    /// Timestamp, client hardware address and Ethernet source of recent
+    /// DISCOVERs
+    discovers: VecDeque<(f64, [u8; 6], [u8; 6])>,
+    /// A starvation alert is raised until the window drains
+    starving: bool,
+}
+
+impl DHCPMonitor {
+    pub fn new(config: DHCPMonitorConfig) -> Self {
+        Self { config, ..default() }
+    }
+
+    /// Trust a server identifier and the MAC it answers from
+    pub fn authorize(&mut self, server_id: [u8; 4], mac: [u8; 6]) {
+        self.authorized.insert((server_id, mac));
+    }
+
+    pub fn is_authorized(&self, server_id: [u8; 4], mac: [u8; 6]) -> bool {
+        self.authorized.contains(&(server_id, mac))
+    }
+
+    pub fn apply(&mut self, frame: &Frame) -> Vec<DHCPAlert> {
+        let mut alerts = vec![];
+        let (dhcp, eth) = match (frame.get_layer::<DHCP>(), frame.get_layer::<Ethernet>()) {
+            (Some(dhcp), Some(eth)) => (dhcp, eth),
+            _ => return alerts,
+        };
+        let learning_until = *self.learning_until
+            .get_or_insert_with(|| frame.ts + self.config.learning.unwrap_or(0.0));
+        match dhcp.message_type {
+            Some(DHCP::OFFER | DHCP::ACK) => {
+                let server_id = dhcp.server_id()
+                    .or_else(|| frame.get_layer::<IPv4>().map(|ip| ip.src))
+                    .unwrap_or_default();
+                if self.is_authorized(server_id, eth.src) {
+                    return alerts;
+                }
+                if self.config.learning.is_some() && frame.ts < learning_until {
+                    self.authorize(server_id, eth.src);
+                } else if self.reported.insert((server_id, eth.src)) {
+                    alerts.push(DHCPAlert {
+                        ts: frame.ts,
+                        kind: DHCPAlertKind::RogueServer {
+                            server_id,
+                            mac: eth.src,
+                            message_type: dhcp.message_type.unwrap(),
+                            yiaddr: dhcp.yiaddr,
+                        },
+                    });
+                }
+            }
+            Some(DHCP::DISCOVER) => {
+                self.discovers.push_back((frame.ts, dhcp.chaddr[..6].try_into().unwrap(), eth.src));
+                while self.discovers.front().is_some_and(|d| frame.ts - d.0 > self.config.window) {
+                    self.discovers.pop_front();
+                }
+                let clients = self.discovers.iter().map(|d| d.1).collect::<HashSet<_>>().len();
+                if clients < self.config.threshold {
+                    self.starving = false;
+                } else if !self.starving {
+                    self.starving = true;
+                    alerts.push(DHCPAlert {
+                        ts: frame.ts,
+                        kind: DHCPAlertKind::Starvation {
+                            discovers: self.discovers.len(),
+                            clients,
+                            sources: self.discovers.iter().map(|d| d.2).collect::<HashSet<_>>().len(),
+                            window: self.config.window,
+                        },
+                    });
+                }
+            }
+            _ => {}
+        }
+        alerts
+    }
+}
+
+impl std::fmt::Display for DHCPAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            DHCPAlertKind::RogueServer { server_id, mac, message_type, yiaddr } => write!(
+                f, "{} rogue DHCP server {} ({}) sent {} for {}",
+                self.ts,
+                fmt_iter!(server_id, "."),
+                fmt_iter!(mac, ":", "{:02x}"),
+                if *message_type == DHCP::OFFER { "OFFER" } else { "ACK" },
+                fmt_iter!(yiaddr, "."),
+            ),
+            DHCPAlertKind::Starvation { discovers, clients, sources, window } => write!(
+                f, "{} DHCP starvation: {} DISCOVERs from {} client addresses and {} sources in {}s",
+                self.ts, discovers, clients, sources, window,
+            ),
+        }
+    }
+}
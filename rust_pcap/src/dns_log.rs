@@ -0,0 +1,295 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+
+use serde::Serialize;
+
+use crate::*;
+use crate::dns::{DNS, DNSData, DNSRecord};
+
+/// One line of a Zeek-like `dns.log`: a query and its response, if any
+#[derive(Debug, Clone, Serialize)]
+pub struct DNSTransaction {
+    /// Timestamp of the query
+    pub ts: f64,
+    pub client: String,
+    pub client_port: u16,
+    pub server: String,
+    pub server_port: u16,
+    /// `udp` or `tcp`
+    pub transport: &'static str,
+    pub id: u16,
+    pub query: String,
+    pub qtype: u16,
+    /// `None` for a query without response
+    pub rcode: Option<u8>,
+    /// Answer section as `type:value`, space separated
+    pub answers: String,
+    /// Seconds between the query and the response
+    pub latency: Option<f64>,
+}
+
+/// Addresses a name resolved to
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassiveDNSEntry {
+    pub first_seen: f64,
+    pub last_seen: f64,
+    /// Responses that carried the mapping
+    pub count: usize,
+}
+
+/// Name to address mappings of every answer seen, names include the query
+/// name of a CNAME chain
+#[derive(Debug, Default)]
+pub struct PassiveDNS {
+    entries: HashMap<String, HashMap<IpAddr, PassiveDNSEntry>>,
+    /// Reverse index to label addresses
+    names: HashMap<IpAddr, HashSet<String>>,
+    mappings: usize,
+    last_sweep: f64,
+}
+
+impl PassiveDNS {
+    /// Seconds after which a mapping no answer carried again is forgotten
+    pub const MAX_AGE: f64 = 86400.0;
+    /// Upper bound of name to address mappings, new ones are dropped past it
+    const MAX_MAPPINGS: usize = 65536;
+    const SWEEP_PERIOD: f64 = 60.0;
+
+    pub fn insert(&mut self, name: &str, address: IpAddr, ts: f64) {
+        self.expire(ts);
+        let name = name.trim_end_matches('.').to_lowercase();
+        let known = self.entries.get(&name).is_some_and(|addresses| addresses.contains_key(&address));
+        if !known {
+            if self.mappings >= Self::MAX_MAPPINGS {
+                return;
+            }
+            self.mappings += 1;
+        }
+        let entry = self.entries.entry(name.clone()).or_default()
+            .entry(address)
+            .or_insert(PassiveDNSEntry { first_seen: ts, last_seen: ts, count: 0 });
+        entry.first_seen = entry.first_seen.min(ts);
+        entry.last_seen = entry.last_seen.max(ts);
+        entry.count += 1;
+        self.names.entry(address).or_default().insert(name);
+    }
+
+    /// Addresses of `name`, with first and last seen
+    pub fn addresses(&self, name: &str) -> Option<&HashMap<IpAddr, PassiveDNSEntry>> {
+        self.entries.get(&name.trim_end_matches('.').to_lowercase())
+    }
+
+    /// Names that resolved to `address`, sorted
+    pub fn names(&self, address: IpAddr) -> Vec<&str> {
+        let mut names = self.names.get(&address)
+            .map(|names| names.iter().map(|n| n.as_str()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(&str, &IpAddr, &PassiveDNSEntry)> {
+        self.entries.iter()
+            .flat_map(|(name, addresses)| addresses.iter().map(move |(a, e)| (name.as_str(), a, e)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn expire(&mut self, ts: f64) {
+        if ts - self.last_sweep < Self::SWEEP_PERIOD {
+            return;
+        }
+        self.last_sweep = ts;
+        for addresses in self.entries.values_mut() {
+            addresses.retain(|_, entry| ts - entry.last_seen <= Self::MAX_AGE);
+        }
+        self.entries.retain(|_, addresses| !addresses.is_empty());
+        let entries = &self.entries;
+        self.names.retain(|address, names| {
+            names.retain(|name| entries.get(name).is_some_and(|addresses| addresses.contains_key(address)));
+            !names.is_empty()
+        });
+        self.mappings = self.entries.values().map(|addresses| addresses.len()).sum();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    client: SocketAddr,
+    server: SocketAddr,
+    tcp: bool,
+    id: u16,
+}
+
+#[derive(Debug)]
+struct Query {
+    ts: f64,
+    name: String,
+    qtype: u16,
+}
+
+/// Pairs queries with responses by transaction id and 5-tuple and feeds
+/// the passive DNS table
+#[derive(Debug, Default)]
+pub struct DNSTracker {
+    pending: HashMap<Key, Query>,
+    pub passive: PassiveDNS,
+    last_sweep: f64,
+}
+
+impl DNSTracker {
+    /// Seconds after which a query is logged without response
+    pub const TIMEOUT: f64 = 10.0;
+    /// Upper bound of queries waiting for a response
+    const MAX_PENDING: usize = 65536;
+    const SWEEP_PERIOD: f64 = 1.0;
+
+    /// Transactions completed by the frame, and queries timed out before it
+    pub fn apply(&mut self, frame: &Frame) -> Vec<DNSTransaction> {
+        let mut transactions = self.expire(frame.ts);
+        let (src, dst) = match (frame.get_layer::<IPv4>(), frame.get_layer::<IPv6>()) {
+            (Some(ip), _) => (IpAddr::from(ip.src), IpAddr::from(ip.dst)),
+            (_, Some(ip)) => (IpAddr::from(ip.src), IpAddr::from(ip.dst)),
+            _ => return transactions,
+        };
+        let (tcp, src, dst) = match (frame.get_layer::<TCP>(), frame.get_layer::<UDP>()) {
+            (Some(tcp), _) => (true, SocketAddr::new(src, tcp.src), SocketAddr::new(dst, tcp.dst)),
+            (_, Some(udp)) => (false, SocketAddr::new(src, udp.src), SocketAddr::new(dst, udp.dst)),
+            _ => return transactions,
+        };
+        let messages = frame.layers_iter().filter_map(|l| l.as_any().downcast_ref::<DNS>());
+        for dns in messages {
+            if !dns.qr {
+                let question = match dns.questions.first() {
+                    Some(question) => question,
+                    None => continue,
+                };
+                if self.pending.len() < Self::MAX_PENDING {
+                    self.pending.insert(Key { client: src, server: dst, tcp, id: dns.id }, Query {
+                        ts: frame.ts,
+                        name: question.name.clone(),
+                        qtype: question.kind,
+                    });
+                }
+                continue;
+            }
+            self.learn(dns, frame.ts);
+            let key = Key { client: dst, server: src, tcp, id: dns.id };
+            if let Some(query) = self.pending.remove(&key) {
+                let mut transaction = DNSTransaction::new(&key, query);
+                transaction.rcode = Some(dns.rcode);
+                transaction.answers = fmt_iter!(dns.answers.iter().map(fmt_record).collect::<Vec<_>>(), " ");
+                transaction.latency = Some(frame.ts - transaction.ts);
+                transactions.push(transaction);
+            }
+        }
+        transactions
+    }
+
+    /// Queries still without response, oldest first
+    pub fn finish(&mut self) -> Vec<DNSTransaction> {
+        self.expire(f64::INFINITY)
+    }
+
+    fn expire(&mut self, ts: f64) -> Vec<DNSTransaction> {
+        if ts - self.last_sweep < Self::SWEEP_PERIOD {
+            return vec![];
+        }
+        self.last_sweep = ts;
+        let expired = self.pending.iter()
+            .filter(|(_, query)| ts - query.ts > Self::TIMEOUT)
+            .map(|(&key, _)| key)
+            .collect::<Vec<_>>();
+        let mut transactions = expired.into_iter()
+            .map(|key| {
+                let query = self.pending.remove(&key).unwrap();
+                DNSTransaction::new(&key, query)
+            })
+            .collect::<Vec<_>>();
+        transactions.sort_by(|a, b| a.ts.total_cmp(&b.ts));
+        transactions
+    }
+
+    fn learn(&mut self, dns: &DNS, ts: f64) {
+        let query = dns.questions.first().map(|q| q.name.as_str());
+        for record in &dns.answers {
+            let address = match record.data {
+                DNSData::A(a) => IpAddr::from(a),
+                DNSData::AAAA(a) => IpAddr::from(a),
+                _ => continue,
+            };
+            self.passive.insert(&record.name, address, ts);
+            if let Some(query) = query.filter(|&q| !q.eq_ignore_ascii_case(&record.name)) {
+                self.passive.insert(query, address, ts);
+            }
+        }
+    }
+}
+
+impl DNSTransaction {
+    fn new(key: &Key, query: Query) -> Self {
+        DNSTransaction {
+            ts: query.ts,
+            client: key.client.ip().to_string(),
+            client_port: key.client.port(),
+            server: key.server.ip().to_string(),
+            server_port: key.server.port(),
+            transport: if key.tcp { "tcp" } else { "udp" },
+            id: key.id,
+            query: query.name,
+            qtype: query.qtype,
+            rcode: None,
+            answers: String::new(),
+            latency: None,
+        }
+    }
+}
+
+fn fmt_record(record: &DNSRecord) -> String {
+    match &record.data {
+        DNSData::A(a) => format!("A:{}", IpAddr::from(*a)),
+        DNSData::AAAA(a) => format!("AAAA:{}", IpAddr::from(*a)),
+        DNSData::CNAME(name) => format!("CNAME:{}", name),
+        DNSData::NS(name) => format!("NS:{}", name),
+        DNSData::PTR(name) => format!("PTR:{}", name),
+        DNSData::MX { exchange, .. } => format!("MX:{}", exchange),
+        DNSData::TXT(strings) => format!("TXT:{}", strings.concat()),
+        DNSData::SRV { port, target, .. } => format!("SRV:{}:{}", target, port),
+        DNSData::SOA { mname, .. } => format!("SOA:{}", mname),
+        DNSData::Raw(data) => format!("{}:{}", record.kind, fmt_iter!(data, "", "{:02x}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passive_aging() {
+        let mut passive = PassiveDNS::default();
+        let address = IpAddr::from([10, 0, 0, 1]);
+        passive.insert("old.example.", address, 1000.0);
+        passive.insert("new.example", address, 1000.0 + PassiveDNS::MAX_AGE);
+        passive.insert("new.example", address, 1000.0 + 2.0 * PassiveDNS::MAX_AGE);
+        assert!(passive.addresses("old.example").is_none());
+        assert_eq!(passive.names(address), vec!["new.example"]);
+        assert_eq!(passive.addresses("new.example").unwrap()[&address].count, 2);
+    }
+
+    #[test]
+    fn passive_cap() {
+        let mut passive = PassiveDNS::default();
+        for i in 0..=PassiveDNS::MAX_MAPPINGS as u32 {
+            passive.insert("example", IpAddr::from(i.to_be_bytes()), 1.0);
+        }
+        assert_eq!(passive.addresses("example").unwrap().len(), PassiveDNS::MAX_MAPPINGS);
+        passive.insert("example", IpAddr::from([0, 0, 0, 0]), 2.0);
+        assert_eq!(passive.addresses("example").unwrap()[&IpAddr::from([0, 0, 0, 0])].count, 2);
+    }
+}
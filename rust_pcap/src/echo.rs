@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use crate::*;
+use crate::rtt::RTTStats;
+
+/// Echo request as sent from `src` to `dst`, replies flow the other way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EchoKey {
+    pub src: [u8; 4],
+    pub dst: [u8; 4],
+    pub id: u16,
+    pub seq: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EchoOutcome {
+    /// First reply within [`EchoTracker::TIMEOUT`]
+    Reply,
+    /// Reply to a request already counted as lost
+    Late,
+    /// Reply to a request already answered
+    Duplicate,
+    /// No reply within [`EchoTracker::TIMEOUT`]
+    Lost,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EchoSample {
+    pub key: EchoKey,
+    pub outcome: EchoOutcome,
+    /// Seconds since the request, for replies and late replies
+    pub rtt: Option<f64>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct EchoStats {
+    /// Requests answered or lost
+    pub requests: usize,
+    pub replies: usize,
+    pub lost: usize,
+    pub late: usize,
+    pub duplicates: usize,
+    /// Percentage of `requests` lost
+    pub loss: f32,
+    /// Of the replies within the timeout
+    pub rtt: RTTStats,
+}
+
+impl EchoStats {
+    pub fn compute<'a>(samples: impl IntoIterator<Item=&'a EchoSample>) -> Self {
+        let mut stats = EchoStats::default();
+        let mut rtts = vec![];
+        for sample in samples {
+            match sample.outcome {
+                EchoOutcome::Reply => {
+                    stats.replies += 1;
+                    rtts.extend(sample.rtt);
+                }
+                EchoOutcome::Late => stats.late += 1,
+                EchoOutcome::Duplicate => stats.duplicates += 1,
+                EchoOutcome::Lost => stats.lost += 1,
+            }
+        }
+        stats.requests = stats.replies + stats.lost;
+        if stats.requests > 0 {
+            stats.loss = (stats.lost * 100) as f32 / stats.requests as f32;
+        }
+        stats.rtt = RTTStats::compute(rtts);
+        stats
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct EchoCount {
+    pub samples: Vec<EchoSample>,
+
+    pub all: EchoStats,
+    /// Per pinged address
+    pub targets: HashMap<[u8; 4], EchoStats>,
+}
+
+impl EchoCount {
+    pub fn flush(&mut self) {
+        self.all = EchoStats::compute(&self.samples);
+        let mut targets = HashMap::<[u8; 4], Vec<&EchoSample>>::new();
+        for sample in &self.samples {
+            targets.entry(sample.key.dst).or_default().push(sample);
+        }
+        self.targets = targets.into_iter()
+            .map(|(target, samples)| (target, EchoStats::compute(samples)))
+            .collect();
+    }
+}
+
+/// Pairs ICMP echo requests with their replies by addresses, identifier
+/// and sequence number
+#[derive(Default, Debug)]
+pub struct EchoTracker {
+    /// Request time of unanswered requests
+    pending: HashMap<EchoKey, f64>,
+    /// Request time of answered requests, to spot duplicates
+    answered: HashMap<EchoKey, f64>,
+    /// Request time of lost requests, to spot late replies
+    lost: HashMap<EchoKey, f64>,
+    last_sweep: f64,
+}
+
+impl EchoTracker {
+    /// Seconds after which a request without reply is lost
+    pub const TIMEOUT: f64 = 5.0;
+    /// Seconds answered and lost requests are remembered
+    const HISTORY: f64 = 60.0;
+    /// Upper bound of requests waiting for a reply
+    const MAX_PENDING: usize = 65536;
+    const SWEEP_PERIOD: f64 = 1.0;
+
+    /// Replies of the frame, and requests lost before it
+    pub fn apply(&mut self, frame: &Frame) -> Vec<EchoSample> {
+        let mut samples = self.expire(frame.ts);
+        let (ip, icmp) = match (frame.get_layer::<IPv4>(), frame.get_layer::<ICMP>()) {
+            (Some(ip), Some(icmp)) => (ip, icmp),
+            _ => return samples,
+        };
+        let (id, seq, kind) = match &icmp.data {
+            ICMPData::Echo { id, num, kind, .. } => (*id, *num, kind),
+            _ => return samples,
+        };
+        match kind {
+            Echo::Request => {
+                let key = EchoKey { src: ip.src, dst: ip.dst, id, seq };
+                if self.pending.len() < Self::MAX_PENDING {
+                    self.pending.entry(key).or_insert(frame.ts);
+                }
+            }
+            Echo::Response => {
+                let key = EchoKey { src: ip.dst, dst: ip.src, id, seq };
+                let (outcome, sent) = if let Some(sent) = self.pending.remove(&key) {
+                    self.answered.insert(key, sent);
+                    (EchoOutcome::Reply, Some(sent))
+                } else if let Some(sent) = self.lost.remove(&key) {
+                    self.answered.insert(key, sent);
+                    (EchoOutcome::Late, Some(sent))
+                } else if self.answered.contains_key(&key) {
+                    (EchoOutcome::Duplicate, None)
+                } else {
+                    return samples;
+                };
+                samples.push(EchoSample { key, outcome, rtt: sent.map(|sent| frame.ts - sent) });
+            }
+        }
+        samples
+    }
+
+    /// Requests still without reply, counted as lost
+    pub fn finish(&mut self) -> Vec<EchoSample> {
+        self.pending.drain()
+            .map(|(key, _)| EchoSample { key, outcome: EchoOutcome::Lost, rtt: None })
+            .collect()
+    }
+
+    fn expire(&mut self, ts: f64) -> Vec<EchoSample> {
+        if ts - self.last_sweep < Self::SWEEP_PERIOD {
+            return vec![];
+        }
+        self.last_sweep = ts;
+        self.answered.retain(|_, &mut sent| ts - sent <= Self::HISTORY);
+        self.lost.retain(|_, &mut sent| ts - sent <= Self::HISTORY);
+        let expired = self.pending.iter()
+            .filter(|(_, &sent)| ts - sent > Self::TIMEOUT)
+            .map(|(&key, _)| key)
+            .collect::<Vec<_>>();
+        expired.into_iter()
+            .map(|key| {
+                self.lost.insert(key, self.pending.remove(&key).unwrap());
+                EchoSample { key, outcome: EchoOutcome::Lost, rtt: None }
+            })
+            .collect()
+    }
+}
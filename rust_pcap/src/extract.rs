@@ -0,0 +1,145 @@
+use std::error::Error;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::*;
+use crate::hash::{sha256, to_hex};
+
+/// Manifest line of an extracted HTTP response body
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractedObject {
+    /// File name in the output directory, the body SHA-256
+    pub file: String,
+    /// Timestamp of the response
+    pub ts: f64,
+    pub client: String,
+    pub client_port: u16,
+    pub server: String,
+    pub server_port: u16,
+    pub host: Option<String>,
+    pub uri: Option<String>,
+    pub content_type: Option<String>,
+    /// Decoded body size
+    pub size: usize,
+    pub sha256: String,
+    /// `false` if the stream ended before the end of the body
+    pub complete: bool,
+}
+
+/// Writes response bodies into a directory, named by their SHA-256 so
+/// identical objects are stored once, and lists them in `manifest.csv`
+pub struct HTTPExtractor {
+    dir: PathBuf,
+    manifest: csv::Writer<File>,
+    pub objects: usize,
+}
+
+impl HTTPExtractor {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self, Box<dyn Error>> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let manifest = csv::Writer::from_path(dir.join("manifest.csv"))?;
+        Ok(Self { dir, manifest, objects: 0 })
+    }
+
+    pub fn apply(&mut self, frame: &Frame) -> Result<(), Box<dyn Error>> {
+        let (ip, tcp) = match (frame.get_layer::<IPv4>(), frame.get_layer::<TCP>()) {
+            (Some(ip), Some(tcp)) => (ip, tcp),
+            _ => return Ok(()),
+        };
+        let responses = tcp.layers().get_all::<HTTP>()
+            .filter(|http| matches!(http.kind, HTTPKind::Res { .. }) && !http.body.is_empty());
+        for http in responses {
+            let digest = to_hex(&sha256(&http.body));
+            let path = self.dir.join(&digest);
+            if !path.exists() {
+                std::fs::write(&path, &http.body)?;
+            }
+            let request = http.request.as_ref();
+            self.manifest.serialize(ExtractedObject {
+                file: digest.clone(),
+                ts: frame.ts,
+                client: fmt_iter!(ip.dst, "."),
+                client_port: tcp.dst,
+                server: fmt_iter!(ip.src, "."),
+                server_port: tcp.src,
+                host: request.and_then(|r| r.header("Host")).map(to_string),
+                uri: request.map(|r| r.uri.clone()),
+                content_type: http.header("Content-Type").map(to_string),
+                size: http.body.len(),
+                sha256: digest,
+                complete: http.is_finalized && !http.truncated,
+            })?;
+            self.objects += 1;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<usize, Box<dyn Error>> {
+        self.manifest.flush()?;
+        Ok(self.objects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pcap_parser::Linktype;
+
+    use super::*;
+
+    fn segment(src: u16, dst: u16, sn: u32, payload: &[u8]) -> Vec<u8> {
+        let (client, server) = ([10, 0, 0, 1], [10, 0, 0, 2]);
+        let (src_ip, dst_ip) = if src > dst { (client, server) } else { (server, client) };
+        let len = (40 + payload.len()) as u16;
+        [
+            &[0x45, 0][..], &len.to_be_bytes(), &[0, 1, 0x40, 0, 64, 6, 0, 0], &src_ip, &dst_ip,
+            &src.to_be_bytes(), &dst.to_be_bytes(), &sn.to_be_bytes(), &[0, 0, 0, 0],
+            &[0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0],
+            payload,
+        ].concat()
+    }
+
+    fn extract(name: &str, response: &[u8]) -> (PathBuf, Vec<csv::StringRecord>) {
+        let dir = std::env::temp_dir().join(format!("rust_pcap_extract_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut extractor = HTTPExtractor::new(&dir).unwrap();
+        let mut ctx = DissectionContext::default();
+        let segments = [
+            segment(50000, 80, 1, b"GET /a.txt HTTP/1.1\r\nHost: example\r\n\r\n"),
+            segment(80, 50000, 1, response),
+        ];
+        for (i, data) in segments.iter().enumerate() {
+            let len = data.len() as u32;
+            let frame = Frame::new(data, i as f64, len, len, Linktype::IPV4, &mut ctx);
+            extractor.apply(&frame).unwrap();
+        }
+        extractor.finish().unwrap();
+        let records = csv::Reader::from_path(dir.join("manifest.csv")).unwrap()
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        (dir, records)
+    }
+
+    #[test]
+    fn manifest() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello";
+        let (dir, records) = extract("manifest", response);
+        let digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(&record[0], digest);
+        assert_eq!(&record[2], "10.0.0.1");
+        assert_eq!(&record[5], "80");
+        assert_eq!(&record[6], "example");
+        assert_eq!(&record[7], "/a.txt");
+        assert_eq!(&record[8], "text/plain");
+        assert_eq!(&record[9], "5");
+        assert_eq!(&record[10], digest);
+        assert_eq!(&record[11], "true");
+        assert_eq!(std::fs::read(dir.join(digest)).unwrap(), b"hello");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
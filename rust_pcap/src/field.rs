@@ -0,0 +1,138 @@
+use std::fmt::Formatter;
+
+use crate::fmt_iter;
+
+/// How a field value is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldFormat {
+    Dec,
+    Hex,
+    Bool,
+    Text,
+    Bytes,
+    Mac,
+    IPv4,
+    IPv6,
+    /// `Debug` of a structured value, already rendered into `FieldValue::Text`
+    Debug,
+}
+
+/// Static description of a layer field, generated by `#[derive(Layer)]`
+#[derive(Debug, Clone, Copy)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    /// Rust type as written in the layer struct
+    pub ty: &'static str,
+    pub format: FieldFormat,
+    /// Byte offset from the start of the layer header, if fixed
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    None,
+    Bool(bool),
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Bytes(Vec<u8>),
+    Text(String),
+}
+
+/// Value of a field together with its description
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub info: &'static FieldInfo,
+    pub value: FieldValue,
+}
+
+impl Field {
+    pub fn name(&self) -> &'static str {
+        self.info.name
+    }
+}
+
+impl std::fmt::Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.value, self.info.format) {
+            (FieldValue::None, _) => write!(f, "None"),
+            (FieldValue::Bool(v), _) => write!(f, "{}", v),
+            (FieldValue::UInt(v), FieldFormat::Hex) => write!(f, "{:#x}", v),
+            (FieldValue::UInt(v), FieldFormat::IPv4) => {
+                write!(f, "{}", fmt_iter!((*v as u32).to_be_bytes(), "."))
+            }
+            (FieldValue::UInt(v), FieldFormat::Mac) => {
+                let bytes = v.to_be_bytes();
+                write!(f, "{}", fmt_iter!(bytes[2..], ":", "{:02x}"))
+            }
+            (FieldValue::UInt(v), _) => write!(f, "{}", v),
+            (FieldValue::Int(v), _) => write!(f, "{}", v),
+            (FieldValue::Float(v), _) => write!(f, "{}", v),
+            (FieldValue::Bytes(v), FieldFormat::IPv4) => write!(f, "{}", fmt_iter!(v, ".")),
+            (FieldValue::Bytes(v), FieldFormat::Mac) => write!(f, "{}", fmt_iter!(v, ":", "{:02x}")),
+            (FieldValue::Bytes(v), FieldFormat::IPv6) if v.len() == 16 => {
+                let v: [u8; 16] = v.as_slice().try_into().unwrap();
+                write!(f, "{}", std::net::Ipv6Addr::from(v))
+            }
+            (FieldValue::Bytes(v), _) => write!(f, "{}", fmt_iter!(v, "", "{:02x}")),
+            (FieldValue::Text(v), _) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// Conversion of layer field types into [`FieldValue`]
+pub trait ToFieldValue {
+    fn to_field_value(&self) -> FieldValue;
+}
+
+macro_rules! impl_to_field_value {
+    ($variant:ident, $as:ty, $($type:ty),+) => {
+        $(
+        impl ToFieldValue for $type {
+            fn to_field_value(&self) -> FieldValue {
+                FieldValue::$variant(*self as $as)
+            }
+        }
+        )+
+    }
+}
+
+impl_to_field_value!(UInt, u64, u8, u16, u32, u64, usize);
+impl_to_field_value!(Int, i64, i8, i16, i32, i64, isize);
+impl_to_field_value!(Float, f64, f32, f64);
+
+impl ToFieldValue for bool {
+    fn to_field_value(&self) -> FieldValue {
+        FieldValue::Bool(*self)
+    }
+}
+
+impl ToFieldValue for char {
+    fn to_field_value(&self) -> FieldValue {
+        FieldValue::Text(self.to_string())
+    }
+}
+
+impl ToFieldValue for String {
+    fn to_field_value(&self) -> FieldValue {
+        FieldValue::Text(self.clone())
+    }
+}
+
+impl ToFieldValue for Vec<u8> {
+    fn to_field_value(&self) -> FieldValue {
+        FieldValue::Bytes(self.clone())
+    }
+}
+
+impl<const N: usize> ToFieldValue for [u8; N] {
+    fn to_field_value(&self) -> FieldValue {
+        FieldValue::Bytes(self.to_vec())
+    }
+}
+
+impl<T: ToFieldValue> ToFieldValue for Option<T> {
+    fn to_field_value(&self) -> FieldValue {
+        self.as_ref().map_or(FieldValue::None, |v| v.to_field_value())
+    }
+}
@@ -0,0 +1,446 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use memmem::{Searcher, TwoWaySearcher};
+use nom::branch::alt;
+use nom::bytes::complete::{is_not, tag, take_while1, take_while_m_n};
+use nom::character::complete::{char, digit1, hex_digit1, multispace0, multispace1};
+use nom::combinator::{all_consuming, map, map_res, not, opt, peek, recognize, value};
+use nom::error::Error;
+use nom::multi::{many0, separated_list0, separated_list1};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+use nom::{Finish, IResult};
+
+use crate::*;
+
+/// Wireshark-like display filter, e.g.
+/// `tcp.port == 4840 && ip.src in 10.0.0.0/8` or `goose.app_id > 0x3000`.
+///
+/// Fields are `<layer abbrev>.<field name>` as produced by
+/// [`Layer::fields`], plus a few Wireshark aliases (`tcp.port`, `ip.addr`,
+/// `icmp.type`, ...). A bare abbreviation tests for the layer presence.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+}
+
+#[derive(Debug)]
+pub struct FilterError(pub String);
+
+impl std::fmt::Display for FilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid filter: {}", self.0)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Exists(String),
+    Compare(String, Op, Vec<Literal>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    In,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Number(u64),
+    IPv4 { addr: u32, prefix: u8 },
+    Mac([u8; 6]),
+    Str(String),
+}
+
+impl Filter {
+    pub fn parse(s: &str) -> Result<Filter, FilterError> {
+        all_consuming(delimited(multispace0, expr, multispace0))(s)
+            .finish()
+            .map(|(_, expr)| Filter { expr })
+            .map_err(|e: Error<&str>| FilterError(format!("unexpected `{}`", e.input)))
+    }
+
+    pub fn matches(&self, frame: &Frame) -> bool {
+        self.expr.eval(frame)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Filter::parse(s)
+    }
+}
+
+impl Expr {
+    fn eval(&self, frame: &Frame) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(frame) && b.eval(frame),
+            Expr::Or(a, b) => a.eval(frame) || b.eval(frame),
+            Expr::Not(e) => !e.eval(frame),
+            Expr::Exists(name) => match name.split_once('.') {
+                Some(_) => !field_values(frame, name).is_empty(),
+                None => frame_layers(frame).any(|l| l.abbrev() == name),
+            },
+            Expr::Compare(name, Op::Ne, literals) => {
+                !field_values(frame, name).iter()
+                    .any(|f| literals.iter().any(|l| compare(f, Op::Eq, l)))
+            }
+            Expr::Compare(name, op, literals) => {
+                field_values(frame, name).iter()
+                    .any(|f| literals.iter().any(|l| compare(f, *op, l)))
+            }
+        }
+    }
+}
+
+/// The frame itself followed by every nested layer
+fn frame_layers(frame: &Frame) -> impl Iterator<Item=&dyn Layer> {
+    std::iter::once(frame as &dyn Layer).chain(frame.layers_iter())
+}
+
+/// Wireshark names that map to differently named or several fields
+fn aliases<'a>(layer: &str, field: &'a str) -> Vec<&'a str> {
+    match (layer, field) {
+        ("tcp" | "udp", "port") => vec!["src", "dst"],
+        ("tcp" | "udp", "srcport") => vec!["src"],
+        ("tcp" | "udp", "dstport") => vec!["dst"],
+        ("ip" | "ipv6" | "eth", "addr") => vec!["src", "dst"],
+        ("ip", "len") => vec!["size"],
+        ("eth", "type") => vec!["eth_type"],
+        ("icmp", "type") => vec!["kind"],
+        ("vlan", "id") => vec!["vid"],
+        _ => vec![field],
+    }
+}
+
+fn field_values(frame: &Frame, name: &str) -> Vec<Field> {
+    let (layer, field) = match name.split_once('.') {
+        Some(parts) => parts,
+        None => return vec![],
+    };
+    let names = aliases(layer, field);
+    frame_layers(frame)
+        .filter(|l| l.abbrev() == layer)
+        .flat_map(|l| l.fields())
+        .filter(|f| names.contains(&f.name()))
+        .collect()
+}
+
+fn ordering(op: Op, ord: Ordering) -> bool {
+    match op {
+        Op::Eq | Op::In => ord == Ordering::Equal,
+        Op::Ne => ord != Ordering::Equal,
+        Op::Gt => ord == Ordering::Greater,
+        Op::Ge => ord != Ordering::Less,
+        Op::Lt => ord == Ordering::Less,
+        Op::Le => ord != Ordering::Greater,
+        Op::Contains => false,
+    }
+}
+
+fn compare_ip(addr: u32, op: Op, network: u32, prefix: u8) -> bool {
+    let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+    match op {
+        Op::Eq | Op::In => addr & mask == network & mask,
+        Op::Ne => addr & mask != network & mask,
+        _ => ordering(op, addr.cmp(&network)),
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    TwoWaySearcher::new(needle).search_in(haystack).is_some()
+}
+
+fn compare(field: &Field, op: Op, literal: &Literal) -> bool {
+    match (&field.value, literal) {
+        (FieldValue::UInt(v), Literal::Number(n)) => ordering(op, v.cmp(n)),
+        (FieldValue::Int(v), Literal::Number(n)) => ordering(op, (*v as i128).cmp(&(*n as i128))),
+        (FieldValue::Float(v), Literal::Number(n)) => {
+            v.partial_cmp(&(*n as f64)).is_some_and(|ord| ordering(op, ord))
+        }
+        (FieldValue::Bool(b), Literal::Number(n)) => ordering(op, (*b as u64).cmp(n)),
+        (FieldValue::Bool(b), Literal::Str(s)) => ordering(op, b.to_string().as_str().cmp(s)),
+        (FieldValue::UInt(v), Literal::IPv4 { addr, prefix }) => {
+            compare_ip(*v as u32, op, *addr, *prefix)
+        }
+        (FieldValue::Bytes(b), Literal::IPv4 { addr, prefix }) if b.len() == 4 => {
+            compare_ip(u32::from_be_bytes(b.as_slice().try_into().unwrap()), op, *addr, *prefix)
+        }
+        (FieldValue::UInt(v), Literal::Mac(m)) => {
+            let mut mac = [0; 8];
+            mac[2..].copy_from_slice(m);
+            ordering(op, v.cmp(&u64::from_be_bytes(mac)))
+        }
+        (FieldValue::Bytes(b), Literal::Mac(m)) => ordering(op, b.as_slice().cmp(m)),
+        (FieldValue::Bytes(b), Literal::Number(n)) if b.len() <= 8 => {
+            let v = b.iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+            ordering(op, v.cmp(n))
+        }
+        (FieldValue::Bytes(b), Literal::Str(s)) => match op {
+            Op::Contains => contains(b, s.as_bytes()),
+            _ => ordering(op, b.as_slice().cmp(s.as_bytes())),
+        },
+        (FieldValue::Text(t), Literal::Str(s)) => match op {
+            Op::Contains => t.contains(s.as_str()),
+            _ => ordering(op, t.as_str().cmp(s)),
+        },
+        _ => false,
+    }
+}
+
+type Res<'a, O> = IResult<&'a str, O>;
+
+fn ws<'a, O>(inner: impl FnMut(&'a str) -> Res<'a, O>) -> impl FnMut(&'a str) -> Res<'a, O> {
+    delimited(multispace0, inner, multispace0)
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// `word` not followed by another word character
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> Res<'a, &'a str> {
+    terminated(tag(word), not(peek(take_while_m_n(1, 1, is_word))))
+}
+
+fn expr(s: &str) -> Res<'_, Expr> {
+    let (s, first) = and_expr(s)?;
+    let (s, rest) = many0(preceded(ws(alt((tag("||"), keyword("or")))), and_expr))(s)?;
+    Ok((s, rest.into_iter().fold(first, |a, b| Expr::Or(Box::new(a), Box::new(b)))))
+}
+
+fn and_expr(s: &str) -> Res<'_, Expr> {
+    let (s, first) = not_expr(s)?;
+    let (s, rest) = many0(preceded(ws(alt((tag("&&"), keyword("and")))), not_expr))(s)?;
+    Ok((s, rest.into_iter().fold(first, |a, b| Expr::And(Box::new(a), Box::new(b)))))
+}
+
+fn not_expr(s: &str) -> Res<'_, Expr> {
+    alt((
+        map(
+            preceded(ws(alt((tag("!"), keyword("not")))), not_expr),
+            |e| Expr::Not(Box::new(e)),
+        ),
+        primary,
+    ))(s)
+}
+
+fn primary(s: &str) -> Res<'_, Expr> {
+    alt((
+        delimited(ws(char('(')), expr, ws(char(')'))),
+        comparison,
+    ))(s)
+}
+
+fn field_name(s: &str) -> Res<'_, &str> {
+    recognize(separated_list1(char('.'), take_while1(is_word)))(s)
+}
+
+fn comparison(s: &str) -> Res<'_, Expr> {
+    let (s, name) = ws(field_name)(s)?;
+    let (s, rhs) = opt(pair(ws(operator), ws(literals)))(s)?;
+    let expr = match rhs {
+        Some((op, literals)) => Expr::Compare(name.to_string(), op, literals),
+        None => Expr::Exists(name.to_string()),
+    };
+    Ok((s, expr))
+}
+
+fn operator(s: &str) -> Res<'_, Op> {
+    alt((
+        value(Op::Eq, alt((tag("=="), keyword("eq")))),
+        value(Op::Ne, alt((tag("!="), keyword("ne")))),
+        value(Op::Ge, alt((tag(">="), keyword("ge")))),
+        value(Op::Le, alt((tag("<="), keyword("le")))),
+        value(Op::Gt, alt((tag(">"), keyword("gt")))),
+        value(Op::Lt, alt((tag("<"), keyword("lt")))),
+        value(Op::In, keyword("in")),
+        value(Op::Contains, keyword("contains")),
+    ))(s)
+}
+
+/// A single literal or a `{a b c}` set
+fn literals(s: &str) -> Res<'_, Vec<Literal>> {
+    alt((
+        delimited(
+            pair(char('{'), multispace0),
+            separated_list0(alt((ws(tag(",")), multispace1)), literal),
+            pair(multispace0, char('}')),
+        ),
+        map(literal, |l| vec![l]),
+    ))(s)
+}
+
+fn literal(s: &str) -> Res<'_, Literal> {
+    alt((string, mac, ipv4, number, map(take_while1(is_word), |w: &str| Literal::Str(w.to_string()))))(s)
+}
+
+fn string(s: &str) -> Res<'_, Literal> {
+    map(
+        delimited(char('"'), opt(is_not("\"")), char('"')),
+        |v: Option<&str>| Literal::Str(v.unwrap_or_default().to_string()),
+    )(s)
+}
+
+fn hex_byte(s: &str) -> Res<'_, u8> {
+    map_res(take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit()), |h| u8::from_str_radix(h, 16))(s)
+}
+
+fn mac(s: &str) -> Res<'_, Literal> {
+    let (s, first) = hex_byte(s)?;
+    let (s, rest) = tuple((
+        preceded(char(':'), hex_byte),
+        preceded(char(':'), hex_byte),
+        preceded(char(':'), hex_byte),
+        preceded(char(':'), hex_byte),
+        preceded(char(':'), hex_byte),
+    ))(s)?;
+    Ok((s, Literal::Mac([first, rest.0, rest.1, rest.2, rest.3, rest.4])))
+}
+
+fn octet(s: &str) -> Res<'_, u8> {
+    map_res(digit1, u8::from_str)(s)
+}
+
+fn ipv4(s: &str) -> Res<'_, Literal> {
+    let (s, (a, _, b, _, c, _, d)) = tuple((
+        octet, char('.'), octet, char('.'), octet, char('.'), octet,
+    ))(s)?;
+    let (s, prefix) = opt(preceded(char('/'), map_res(digit1, u8::from_str)))(s)?;
+    let prefix = prefix.unwrap_or(32).min(32);
+    Ok((s, Literal::IPv4 { addr: u32::from_be_bytes([a, b, c, d]), prefix }))
+}
+
+fn number(s: &str) -> Res<'_, Literal> {
+    let hex = map_res(
+        preceded(alt((tag("0x"), tag("0X"))), hex_digit1),
+        |h| u64::from_str_radix(h, 16),
+    );
+    let dec = map_res(digit1, u64::from_str);
+    map(
+        terminated(alt((hex, dec)), not(peek(take_while_m_n(1, 1, is_word)))),
+        Literal::Number,
+    )(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Expr {
+        Filter::parse(s).unwrap().expr
+    }
+
+    fn compare_expr(name: &str, op: Op, literals: Vec<Literal>) -> Expr {
+        Expr::Compare(name.to_string(), op, literals)
+    }
+
+    #[test]
+    fn precedence() {
+        let a = Expr::Exists("tcp".to_string());
+        let b = Expr::Exists("udp".to_string());
+        let c = Expr::Exists("arp".to_string());
+        let and = Expr::And(Box::new(b.clone()), Box::new(c.clone()));
+        assert_eq!(parse("tcp || udp && arp"), Expr::Or(Box::new(a.clone()), Box::new(and.clone())));
+        assert_eq!(parse("tcp or udp and arp"), parse("tcp || udp && arp"));
+        let or = Expr::Or(Box::new(a.clone()), Box::new(b));
+        assert_eq!(parse("(tcp || udp) && arp"), Expr::And(Box::new(or), Box::new(c)));
+        assert_eq!(parse("!tcp"), Expr::Not(Box::new(a.clone())));
+        assert_eq!(parse("not not tcp"), Expr::Not(Box::new(Expr::Not(Box::new(a)))));
+    }
+
+    #[test]
+    fn operators() {
+        let port = |op| compare_expr("tcp.port", op, vec![Literal::Number(80)]);
+        assert_eq!(parse("tcp.port == 80"), port(Op::Eq));
+        assert_eq!(parse("tcp.port eq 80"), port(Op::Eq));
+        assert_eq!(parse("tcp.port!=80"), port(Op::Ne));
+        assert_eq!(parse("tcp.port >= 80"), port(Op::Ge));
+        assert_eq!(parse("tcp.port <= 80"), port(Op::Le));
+        assert_eq!(parse("tcp.port > 80"), port(Op::Gt));
+        assert_eq!(parse("tcp.port lt 80"), port(Op::Lt));
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(
+            parse("goose.app_id > 0x3000"),
+            compare_expr("goose.app_id", Op::Gt, vec![Literal::Number(0x3000)]),
+        );
+        assert_eq!(
+            parse("ip.src in 10.0.0.0/8"),
+            compare_expr("ip.src", Op::In, vec![Literal::IPv4 { addr: 0x0A00_0000, prefix: 8 }]),
+        );
+        assert_eq!(
+            parse("ip.dst == 10.0.0.1"),
+            compare_expr("ip.dst", Op::Eq, vec![Literal::IPv4 { addr: 0x0A00_0001, prefix: 32 }]),
+        );
+        assert_eq!(
+            parse("eth.src == 00:1a:2B:3c:4d:5e"),
+            compare_expr("eth.src", Op::Eq, vec![Literal::Mac([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e])]),
+        );
+        assert_eq!(
+            parse(r#"http.uri contains "/a b""#),
+            compare_expr("http.uri", Op::Contains, vec![Literal::Str("/a b".to_string())]),
+        );
+        assert_eq!(
+            parse(r#"http.uri == """#),
+            compare_expr("http.uri", Op::Eq, vec![Literal::Str(String::new())]),
+        );
+        assert_eq!(
+            parse("tcp.port in {80, 443 8080}"),
+            compare_expr("tcp.port", Op::In, vec![80, 443, 8080].into_iter().map(Literal::Number).collect()),
+        );
+        assert_eq!(parse("tcp.port in {}"), compare_expr("tcp.port", Op::In, vec![]));
+    }
+
+    #[test]
+    fn keywords_need_a_boundary() {
+        // `order` and `notice` are fields, not `or` and `not` followed by a word
+        assert_eq!(parse("order"), Expr::Exists("order".to_string()));
+        assert_eq!(parse("notice"), Expr::Exists("notice".to_string()));
+        // neither is a number not followed by a word, so a bare word
+        assert_eq!(
+            parse("tcp.port == 80abc"),
+            compare_expr("tcp.port", Op::Eq, vec![Literal::Str("80abc".to_string())]),
+        );
+        assert_eq!(parse("tcp.port == 0x"), compare_expr("tcp.port", Op::Eq, vec![Literal::Str("0x".to_string())]));
+    }
+
+    #[test]
+    fn malformed() {
+        for s in [
+            "", "   ", "(", "(tcp", "tcp)", "tcp &&", "|| tcp", "!", "tcp.port ==", "== 80",
+            "tcp.port == 80 80", "tcp.port in {80", r#"http.uri == "open"#, "tcp..port",
+            "ip.src == 10.0.0", "ip.src == 256.0.0.1",
+        ] {
+            assert!(Filter::parse(s).is_err(), "{:?}", s);
+        }
+        let error = Filter::parse("tcp &&").unwrap_err();
+        assert_eq!(error.to_string(), "invalid filter: unexpected `&&`");
+    }
+
+    #[test]
+    fn ip_prefix() {
+        let network = u32::from_be_bytes([10, 1, 0, 0]);
+        assert!(compare_ip(u32::from_be_bytes([10, 1, 2, 3]), Op::In, network, 16));
+        assert!(!compare_ip(u32::from_be_bytes([10, 2, 2, 3]), Op::In, network, 16));
+        assert!(compare_ip(u32::from_be_bytes([10, 2, 2, 3]), Op::Ne, network, 16));
+        assert!(compare_ip(0xFFFF_FFFF, Op::Eq, network, 0));
+        assert!(compare_ip(u32::from_be_bytes([10, 1, 0, 1]), Op::Gt, network, 32));
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+
+use crate::*;
+use crate::tls::{TLS, TLSHandshake};
+
+/// TLS fingerprint of a hello missing from the allowlist
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownFingerprint {
+    pub ts: f64,
+    pub src: [u8; 4],
+    pub src_port: u16,
+    pub dst: [u8; 4],
+    pub dst_port: u16,
+    /// `false` for the JA3S of a ServerHello
+    pub client: bool,
+    pub ja3: String,
+    /// Empty for servers
+    pub ja4: String,
+    pub sni: Option<String>,
+}
+
+/// Known JA3, JA3S and JA4 fingerprints, a hello is known if any of its
+/// fingerprints is listed
+#[derive(Debug, Clone, Default)]
+pub struct FingerprintAllowlist {
+    known: HashSet<String>,
+}
+
+impl FingerprintAllowlist {
+    /// One fingerprint per line, `#` starts a comment
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(std::fs::read_to_string(path)?.lines().collect())
+    }
+
+    pub fn insert(&mut self, fingerprint: &str) {
+        self.known.insert(fingerprint.to_lowercase());
+    }
+
+    pub fn contains(&self, fingerprint: &str) -> bool {
+        self.known.contains(&fingerprint.to_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.known.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty()
+    }
+
+    /// Hellos of the frame whose fingerprints are all unknown
+    pub fn check(&self, frame: &Frame) -> Vec<UnknownFingerprint> {
+        let (ip, tcp) = match (frame.get_layer::<IPv4>(), frame.get_layer::<TCP>()) {
+            (Some(ip), Some(tcp)) => (ip, tcp),
+            _ => return vec![],
+        };
+        let unknown = |client: bool, ja3: String, ja4: String, sni: Option<String>| UnknownFingerprint {
+            ts: frame.ts,
+            src: ip.src,
+            src_port: tcp.src,
+            dst: ip.dst,
+            dst_port: tcp.dst,
+            client,
+            ja3,
+            ja4,
+            sni,
+        };
+        tcp.layers().get_all::<TLS>()
+            .flat_map(|record| record.handshakes())
+            .filter_map(|handshake| match handshake {
+                TLSHandshake::ClientHello(hello) => {
+                    let (ja3, ja4) = (hello.ja3(), hello.ja4());
+                    let known = self.contains(&ja3) || self.contains(&ja4);
+                    (!known).then(|| unknown(true, ja3, ja4, hello.sni.clone()))
+                }
+                TLSHandshake::ServerHello(hello) => {
+                    let ja3s = hello.ja3s();
+                    (!self.contains(&ja3s)).then(|| unknown(false, ja3s, String::new(), None))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl<'a> FromIterator<&'a str> for FingerprintAllowlist {
+    fn from_iter<I: IntoIterator<Item=&'a str>>(lines: I) -> Self {
+        let mut allowlist = Self::default();
+        for line in lines {
+            let fingerprint = line.split('#').next().unwrap().trim();
+            if !fingerprint.is_empty() {
+                allowlist.insert(fingerprint);
+            }
+        }
+        allowlist
+    }
+}
+
+impl std::fmt::Display for UnknownFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}:{} -> {}:{} unknown {} {}",
+               self.ts,
+               fmt_iter!(self.src, "."), self.src_port,
+               fmt_iter!(self.dst, "."), self.dst_port,
+               if self.client { "JA3" } else { "JA3S" },
+               self.ja3)?;
+        if !self.ja4.is_empty() {
+            write!(f, " JA4 {}", self.ja4)?;
+        }
+        if let Some(sni) = &self.sni {
+            write!(f, " SNI {}", sni)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::{ClientHello, ServerHello, TLSExtension};
+
+    const GREASE: u16 = 0x1a1a;
+
+    fn extensions(kinds: &[u16]) -> Vec<TLSExtension> {
+        kinds.iter().map(|&kind| TLSExtension { kind, data: vec![] }).collect()
+    }
+
+    fn client_hello(version: u16, cipher_suites: &[u16], extension_kinds: &[u16]) -> ClientHello {
+        ClientHello {
+            version,
+            random: [0; 32],
+            session_id: vec![],
+            cipher_suites: cipher_suites.to_vec(),
+            compression_methods: vec![0],
+            extensions: extensions(extension_kinds),
+            sni: None,
+            alpn: vec![],
+            supported_versions: vec![],
+            supported_groups: vec![],
+            ec_point_formats: vec![],
+            signature_algorithms: vec![],
+        }
+    }
+
+    #[test]
+    fn ja3() {
+        // example of the JA3 reference implementation, with GREASE added
+        let mut hello = client_hello(
+            769,
+            &[GREASE, 47, 53, 5, 10, 49161, 49162, 49171, 49172, 50, 56, 19, 4],
+            &[GREASE, 0, 10, 11],
+        );
+        hello.supported_groups = vec![GREASE, 23, 24, 25];
+        hello.ec_point_formats = vec![0];
+        assert_eq!(hello.ja3_string(), "769,47-53-5-10-49161-49162-49171-49172-50-56-19-4,0-10-11,23-24-25,0");
+        assert_eq!(hello.ja3(), "ada70206e40642a3e4461f35503241d5");
+    }
+
+    #[test]
+    fn ja3s() {
+        let hello = ServerHello {
+            version: 769,
+            random: [0; 32],
+            session_id: vec![],
+            cipher_suite: 47,
+            compression_method: 0,
+            extensions: extensions(&[65281, 0, 11, 35, 5, 16]),
+            alpn: None,
+            supported_version: None,
+        };
+        assert_eq!(hello.ja3s_string(), "769,47,65281-0-11-35-5-16");
+        assert_eq!(hello.ja3s(), "836ce314215654b5b1f85f97c73e506f");
+    }
+
+    #[test]
+    fn ja4() {
+        // Chrome example of the JA4 specification, with GREASE added
+        let mut hello = client_hello(
+            0x0303,
+            &[
+                GREASE, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8,
+                0xc013, 0xc014, 0x009c, 0x009d, 0x002f, 0x0035,
+            ],
+            &[
+                GREASE, 0x0000, 0x0017, 0xff01, 0x000a, 0x000b, 0x0023, 0x0010, 0x0005, 0x000d,
+                0x0012, 0x0033, 0x002d, 0x002b, 0x001b, 0x0015, 0x4469,
+            ],
+        );
+        hello.sni = Some("example.com".to_string());
+        hello.alpn = vec!["h2".to_string(), "http/1.1".to_string()];
+        hello.supported_versions = vec![GREASE, 0x0304, 0x0303];
+        hello.signature_algorithms = vec![0x0403, 0x0804, 0x0401, 0x0503, 0x0805, 0x0501, 0x0806, 0x0601];
+        assert_eq!(hello.ja4(), "t13d1516h2_8daaf6152771_e5627efa2ab1");
+        // no SNI nor ALPN, TLS 1.2 only
+        hello.sni = None;
+        hello.alpn = vec![];
+        hello.supported_versions = vec![];
+        assert!(hello.ja4().starts_with("t12i151600_8daaf6152771_"), "{}", hello.ja4());
+    }
+
+    #[test]
+    fn ja4_empty_lists() {
+        let hello = client_hello(0x0301, &[], &[]);
+        assert_eq!(hello.ja4(), "t10i000000_000000000000_000000000000");
+    }
+
+    #[test]
+    fn allowlist() {
+        let allowlist = "# known\nada70206e40642a3e4461f35503241d5  # curl\n\n  t13d1516h2_8daaf6152771_e5627efa2ab1\n"
+            .lines()
+            .collect::<FingerprintAllowlist>();
+        assert_eq!(allowlist.len(), 2);
+        assert!(allowlist.contains("ada70206e40642a3e4461f35503241d5"));
+        assert!(allowlist.contains("t13d1516h2_8daaf6152771_e5627efa2ab1"));
+        assert!(!allowlist.contains("# known"));
+    }
+}
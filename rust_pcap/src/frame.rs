@@ -6,22 +6,84 @@ use ethernet::Ethernet;
 use crate::*;
 
 pub mod arp;
+pub mod dispatch;
 pub mod dhcp;
+pub mod dhcpv6;
+pub mod dns;
 pub mod ethernet;
+pub mod hpack;
 pub mod http;
+pub mod http2;
 pub mod icmp;
 pub mod ip;
 pub mod tcp;
+pub mod tls;
 pub mod udp;
 pub mod goose;
 pub mod opc_ua;
 pub mod sv;
 pub mod tpkt;
+pub mod x509;
 
 
+/// Bounds on the state kept between frames, timeouts use packet timestamps
+#[derive(Debug, Clone)]
+pub struct DissectionLimits {
+    /// Bytes buffered per TCP stream buffer, the excess is dropped
+    pub stream_bytes: Option<usize>,
+    /// Seconds without segments after which a TCP stream is evicted
+    pub idle_timeout: Option<f64>,
+    /// Bytes buffered over all TCP streams, least recently used are evicted
+    pub memory_budget: Option<usize>,
+}
+
+impl Default for DissectionLimits {
+    fn default() -> Self {
+        Self {
+            stream_bytes: Some(1 << 20),
+            idle_timeout: Some(300.0),
+            memory_budget: Some(256 << 20),
+        }
+    }
+}
+
+impl DissectionLimits {
+    pub fn unlimited() -> Self {
+        Self {
+            stream_bytes: None,
+            idle_timeout: None,
+            memory_budget: None,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct DissectionStats {
+    /// Streams dropped after `idle_timeout`
+    pub evicted_idle: usize,
+    /// Streams dropped to fit `memory_budget`
+    pub evicted_budget: usize,
+    /// Streams that hit `stream_bytes`
+    pub truncated: usize,
+}
+
 #[derive(Default, Debug)]
 pub struct DissectionContext {
     pub tcp: TCPContext,
+    pub registry: Registry,
+    pub apps: FlowApps,
+    pub http: HTTPPending,
+    pub http2: HTTP2Connections,
+    pub limits: DissectionLimits,
+    pub stats: DissectionStats,
+    /// Timestamp of the frame being dissected
+    pub ts: f64,
+}
+
+impl DissectionContext {
+    pub fn new(limits: DissectionLimits, registry: Registry) -> Self {
+        Self { limits, registry, ..default() }
+    }
 }
 
 #[derive(Layer)]
@@ -29,6 +91,7 @@ pub struct Frame {
     pub ts: f64,
     pub caplen: u32,
     pub origlen: u32,
+    #[layer(skip)]
     pub data: Vec<u8>,
 
     layers: Layers,
@@ -43,11 +106,24 @@ impl Frame {
         ctx: &mut DissectionContext,
     ) -> Frame
     {
+        ctx.ts = ts;
+        ctx.tcp.expire(ts, ctx.limits.idle_timeout, &mut ctx.stats);
+        ctx.apps.expire(ts, ctx.limits.idle_timeout);
+        ctx.http.expire(ts, ctx.limits.idle_timeout);
+        ctx.http2.expire(ts, ctx.limits.idle_timeout);
         let mut layers = Layers::default();
         match link_type {
             Linktype::ETHERNET => layers.insert(Ethernet::new(data.into(), ctx)),
-            Linktype::IPV4 => layers.insert(IPv4::new(data, ctx)),
-            Linktype::IPV6 => layers.insert(IPv6::new(data, ctx)),
+            Linktype::IPV4 => {
+                if let Some(ipv4) = IPv4::try_make(data, ctx) {
+                    layers.insert(ipv4);
+                }
+            }
+            Linktype::IPV6 => {
+                if let Some(ipv6) = IPv6::try_make(data, ctx) {
+                    layers.insert(ipv6);
+                }
+            }
             _ => {}
         }
         Frame { ts, caplen, origlen, data: data.to_vec(), layers }
@@ -91,7 +167,4 @@ impl HasLayers for Frame {
     fn layers(&self) -> &Layers {
         &self.layers
     }
-    fn get_layer_descendants<T>(&self) -> Option<&T> where T: Layer {
-        get_layer_descendants!(self, T, Ethernet, IPv4)
-    }
 }
@@ -1,33 +1,86 @@
-use byteorder::{ByteOrder, NetworkEndian};
-
 use crate::*;
 
-#[derive(Debug, Layer)]
+/// ARP packet (RFC 826), addresses are `hlen` and `plen` bytes long, offsets
+/// are those of Ethernet and IPv4
+#[derive(Debug, Layer, Header)]
 pub struct ARP {
-    htype: u16,
-    ptype: u16,
-    hlen: u8,
-    plen: u8,
-    oper: u16,
-    sha: u64,
-    spa: u32,
-    tha: u64,
-    tpa: u32,
+    #[layer(offset = 0)]
+    pub htype: u16,
+    #[layer(offset = 2, format = "hex")]
+    pub ptype: u16,
+    #[layer(offset = 4)]
+    pub hlen: u8,
+    #[layer(offset = 5)]
+    pub plen: u8,
+    #[layer(offset = 6)]
+    pub oper: u16,
+    #[layer(offset = 8, format = "mac")]
+    #[header(skip)]
+    pub sha: Vec<u8>,
+    #[layer(offset = 14, format = "ipv4")]
+    #[header(skip)]
+    pub spa: Vec<u8>,
+    #[layer(offset = 18, format = "mac")]
+    #[header(skip)]
+    pub tha: Vec<u8>,
+    #[layer(offset = 24, format = "ipv4")]
+    #[header(skip)]
+    pub tpa: Vec<u8>,
 }
 
 impl ARP {
-    pub fn new(data: &[u8]) -> ARP {
-        const MASK48: u64 = 0xFF_FF_FF_FF_FF_FF_00_00;
-        ARP {
-            htype: NetworkEndian::read_u16(data.get(0..2).unwrap()),
-            ptype: NetworkEndian::read_u16(data.get(2..4).unwrap()),
-            hlen: data.get(4).unwrap().clone(),
-            plen: data.get(5).unwrap().clone(),
-            oper: NetworkEndian::read_u16(data.get(6..8).unwrap()),
-            sha: (NetworkEndian::read_u64(data.get(8..16).unwrap()) & MASK48) >> 16,
-            spa: NetworkEndian::read_u32(data.get(14..18).unwrap()),
-            tha: (NetworkEndian::read_u64(data.get(18..26).unwrap()) & MASK48) >> 16,
-            tpa: NetworkEndian::read_u32(data.get(24..28).unwrap()),
-        }
+    /// Operations
+    pub const REQUEST: u16 = 1;
+    pub const REPLY: u16 = 2;
+
+    /// Hardware and protocol types
+    pub const ETHERNET: u16 = 1;
+    pub const IPV4: u16 = 0x0800;
+
+    pub fn try_make(data: &[u8]) -> Option<ARP> {
+        let mut arp = ARP::parse(data)?;
+        let (hlen, plen) = (arp.hlen as usize, arp.plen as usize);
+        let addresses = data.get(ARP::SIZE..ARP::SIZE + 2 * (hlen + plen))?;
+        let (sender, target) = addresses.split_at(hlen + plen);
+        arp.sha = sender[..hlen].to_vec();
+        arp.spa = sender[hlen..].to_vec();
+        arp.tha = target[..hlen].to_vec();
+        arp.tpa = target[hlen..].to_vec();
+        Some(arp)
+    }
+
+    /// Ethernet and IPv4 addresses
+    fn is_ethernet_ipv4(&self) -> bool {
+        self.htype == Self::ETHERNET && self.ptype == Self::IPV4 && self.hlen == 6 && self.plen == 4
+    }
+
+    pub fn sender_mac(&self) -> Option<[u8; 6]> {
+        self.is_ethernet_ipv4().then(|| self.sha.as_slice().try_into().unwrap())
+    }
+
+    pub fn sender_ip(&self) -> Option<[u8; 4]> {
+        self.is_ethernet_ipv4().then(|| self.spa.as_slice().try_into().unwrap())
+    }
+
+    pub fn target_mac(&self) -> Option<[u8; 6]> {
+        self.is_ethernet_ipv4().then(|| self.tha.as_slice().try_into().unwrap())
+    }
+
+    pub fn target_ip(&self) -> Option<[u8; 4]> {
+        self.is_ethernet_ipv4().then(|| self.tpa.as_slice().try_into().unwrap())
+    }
+
+    /// Announcement of the sender's own address: a request or reply whose
+    /// sender and target protocol addresses are equal
+    pub fn is_gratuitous(&self) -> bool {
+        [Self::REQUEST, Self::REPLY].contains(&self.oper)
+            && self.spa == self.tpa
+            && self.spa.iter().any(|&b| b != 0)
+    }
+
+    /// Address conflict detection probe (RFC 5227 2.1.1): a request with
+    /// an all-zero sender protocol address
+    pub fn is_probe(&self) -> bool {
+        self.oper == Self::REQUEST && self.spa.iter().all(|&b| b == 0)
     }
 }
@@ -2,66 +2,203 @@ use byteorder::{ByteOrder, NetworkEndian};
 
 use crate::*;
 
-#[derive(Debug, Layer)]
+/// Decoded option (RFC 2132), unknown or malformed ones are kept raw
+#[derive(Debug, Clone, PartialEq)]
+pub enum DHCPOption {
+    MessageType(u8),
+    SubnetMask([u8; 4]),
+    Router(Vec<[u8; 4]>),
+    DNSServers(Vec<[u8; 4]>),
+    Hostname(String),
+    DomainName(String),
+    RequestedIP([u8; 4]),
+    /// Seconds
+    LeaseTime(u32),
+    ServerId([u8; 4]),
+    ParameterRequestList(Vec<u8>),
+    VendorClass(String),
+    /// Usually the hardware type followed by the MAC
+    ClientId(Vec<u8>),
+    Other {
+        code: u8,
+        data: Vec<u8>,
+    },
+}
+
+/// Option codes
+impl DHCPOption {
+    pub const PAD: u8 = 0;
+    pub const SUBNET_MASK: u8 = 1;
+    pub const ROUTER: u8 = 3;
+    pub const DNS_SERVERS: u8 = 6;
+    pub const HOSTNAME: u8 = 12;
+    pub const DOMAIN_NAME: u8 = 15;
+    pub const REQUESTED_IP: u8 = 50;
+    pub const LEASE_TIME: u8 = 51;
+    pub const OVERLOAD: u8 = 52;
+    pub const MESSAGE_TYPE: u8 = 53;
+    pub const SERVER_ID: u8 = 54;
+    pub const PARAMETER_REQUEST_LIST: u8 = 55;
+    pub const VENDOR_CLASS: u8 = 60;
+    pub const CLIENT_ID: u8 = 61;
+    pub const END: u8 = 255;
+}
+
+#[derive(Debug, Layer, Header)]
 pub struct DHCP {
-    op: u8,
-    htype: u8,
-    hlen: u8,
-    hops: u8,
-    xid: u32,
-    secs: u16,
-    flags: u16,
-    ciaddr: u32,
-    yiaddr: u32,
-    siaddr: u32,
-    giaddr: u32,
-    chaddr: [u8; 16],
-    sname: [u8; 64],
-    file: [u8; 128],
-    options: Option<Vec<u8>>,
+    #[layer(offset = 0)]
+    pub op: u8,
+    #[layer(offset = 1)]
+    pub htype: u8,
+    #[layer(offset = 2)]
+    pub hlen: u8,
+    #[layer(offset = 3)]
+    pub hops: u8,
+    #[layer(offset = 4, format = "hex")]
+    pub xid: u32,
+    #[layer(offset = 8)]
+    pub secs: u16,
+    #[layer(offset = 10, format = "hex")]
+    pub flags: u16,
+    #[layer(offset = 12)]
+    pub ciaddr: [u8; 4],
+    #[layer(offset = 16)]
+    pub yiaddr: [u8; 4],
+    #[layer(offset = 20)]
+    pub siaddr: [u8; 4],
+    #[layer(offset = 24)]
+    pub giaddr: [u8; 4],
+    #[layer(offset = 28, format = "bytes")]
+    pub chaddr: [u8; 16],
+    #[layer(offset = 44)]
+    pub sname: [u8; 64],
+    #[layer(offset = 108)]
+    pub file: [u8; 128],
+    /// `None` for plain BOOTP
+    #[header(skip)]
+    pub message_type: Option<u8>,
+    #[layer(offset = 240, format = "debug")]
+    #[header(skip)]
+    pub options: Vec<DHCPOption>,
 }
 
 impl DHCP {
     const START_OPTIONS: [u8; 4] = [99, 130, 83, 99];
 
+    /// Message types of option 53
+    pub const DISCOVER: u8 = 1;
+    pub const OFFER: u8 = 2;
+    pub const REQUEST: u8 = 3;
+    pub const DECLINE: u8 = 4;
+    pub const ACK: u8 = 5;
+    pub const NAK: u8 = 6;
+    pub const RELEASE: u8 = 7;
+    pub const INFORM: u8 = 8;
+
     pub fn try_make(data: &[u8]) -> Option<DHCP> {
-        let op = data.get(0)?.clone();
-        if ![0x01, 0x02].contains(&op) { return None; }
-        let htype = data.get(1)?.clone();
-        let hlen = data.get(2)?.clone();
-        let hops = data.get(3)?.clone();
-        let xid = NetworkEndian::read_u32(data.get(4..8)?);
-        let secs = NetworkEndian::read_u16(data.get(8..10)?);
-        let flags = NetworkEndian::read_u16(data.get(10..12)?);
-        let ciaddr = NetworkEndian::read_u32(data.get(12..16)?);
-        let yiaddr = NetworkEndian::read_u32(data.get(16..20)?);
-        let siaddr = NetworkEndian::read_u32(data.get(20..24)?);
-        let giaddr = NetworkEndian::read_u32(data.get(24..28)?);
-        let chaddr = data.get(28..44)?;
-        let sname = data.get(44..108)?;
-        let file = data.get(108..236)?;
-        let data = data.get(236..);
-        let options = if let Some(data) = data {
-            if data.starts_with(&Self::START_OPTIONS) {
-                Some(data.get(4..).expect("Has magic but no options").to_vec())
-            } else { None }
-        } else { None };
-        Some(DHCP {
-            op,
-            htype,
-            hlen,
-            hops,
-            xid,
-            secs,
-            flags,
-            ciaddr,
-            yiaddr,
-            siaddr,
-            giaddr,
-            chaddr: chaddr.try_into().unwrap(),
-            sname: sname.try_into().unwrap(),
-            file: file.try_into().unwrap(),
-            options,
+        let mut dhcp = DHCP::parse(data)?;
+        if ![0x01, 0x02].contains(&dhcp.op) { return None; }
+        let options = data.get(DHCP::SIZE..);
+        if let Some(options) = options.and_then(|o| o.strip_prefix(&Self::START_OPTIONS)) {
+            dhcp.options = Self::decode_options(options);
+            // options continued in the file and sname fields (RFC 2132 9.3)
+            let overload = dhcp.options.iter().find_map(|o| match o {
+                DHCPOption::Other { code: DHCPOption::OVERLOAD, data } => data.first().copied(),
+                _ => None,
+            });
+            if let Some(overload) = overload {
+                if overload & 1 != 0 {
+                    dhcp.options.extend(Self::decode_options(&dhcp.file));
+                }
+                if overload & 2 != 0 {
+                    dhcp.options.extend(Self::decode_options(&dhcp.sname));
+                }
+            }
+            dhcp.message_type = dhcp.options.iter().find_map(|o| match o {
+                DHCPOption::MessageType(kind) => Some(*kind),
+                _ => None,
+            });
+        }
+        Some(dhcp)
+    }
+
+    fn decode_options(mut data: &[u8]) -> Vec<DHCPOption> {
+        let mut options = vec![];
+        while let Some(&code) = data.first() {
+            match code {
+                DHCPOption::PAD => {
+                    data = &data[1..];
+                    continue;
+                }
+                DHCPOption::END => break,
+                _ => {}
+            }
+            let value = match data.get(1).and_then(|&len| data.get(2..2 + len as usize)) {
+                Some(value) => value,
+                None => break,
+            };
+            data = &data[2 + value.len()..];
+            options.push(Self::decode_option(code, value)
+                .unwrap_or_else(|| DHCPOption::Other { code, data: value.to_vec() }));
+        }
+        options
+    }
+
+    fn decode_option(code: u8, value: &[u8]) -> Option<DHCPOption> {
+        let address = || value.try_into().ok();
+        let addresses = || {
+            (!value.is_empty() && value.len().is_multiple_of(4))
+                .then(|| value.chunks(4).map(|a| a.try_into().unwrap()).collect())
+        };
+        let text = || String::from_utf8_lossy(value).trim_end_matches('\0').to_string();
+        Some(match code {
+            DHCPOption::MESSAGE_TYPE => DHCPOption::MessageType(*value.first()?),
+            DHCPOption::SUBNET_MASK => DHCPOption::SubnetMask(address()?),
+            DHCPOption::ROUTER => DHCPOption::Router(addresses()?),
+            DHCPOption::DNS_SERVERS => DHCPOption::DNSServers(addresses()?),
+            DHCPOption::HOSTNAME => DHCPOption::Hostname(text()),
+            DHCPOption::DOMAIN_NAME => DHCPOption::DomainName(text()),
+            DHCPOption::REQUESTED_IP => DHCPOption::RequestedIP(address()?),
+            DHCPOption::LEASE_TIME => DHCPOption::LeaseTime(NetworkEndian::read_u32(value.get(..4)?)),
+            DHCPOption::SERVER_ID => DHCPOption::ServerId(address()?),
+            DHCPOption::PARAMETER_REQUEST_LIST => DHCPOption::ParameterRequestList(value.to_vec()),
+            DHCPOption::VENDOR_CLASS => DHCPOption::VendorClass(text()),
+            DHCPOption::CLIENT_ID => DHCPOption::ClientId(value.to_vec()),
+            _ => return None,
+        })
+    }
+
+    /// Client hardware address of an Ethernet client
+    pub fn mac(&self) -> Option<[u8; 6]> {
+        (self.htype == 1 && self.hlen == 6).then(|| self.chaddr[..6].try_into().unwrap())
+    }
+
+    pub fn requested_ip(&self) -> Option<[u8; 4]> {
+        self.options.iter().find_map(|o| match o {
+            DHCPOption::RequestedIP(ip) => Some(*ip),
+            _ => None,
+        })
+    }
+
+    pub fn server_id(&self) -> Option<[u8; 4]> {
+        self.options.iter().find_map(|o| match o {
+            DHCPOption::ServerId(ip) => Some(*ip),
+            _ => None,
+        })
+    }
+
+    /// Seconds
+    pub fn lease_time(&self) -> Option<u32> {
+        self.options.iter().find_map(|o| match o {
+            DHCPOption::LeaseTime(time) => Some(*time),
+            _ => None,
+        })
+    }
+
+    pub fn hostname(&self) -> Option<&str> {
+        self.options.iter().find_map(|o| match o {
+            DHCPOption::Hostname(name) => Some(name.as_str()),
+            _ => None,
         })
     }
 }
@@ -0,0 +1,482 @@
+use byteorder::{ByteOrder, NetworkEndian};
+
+use crate::*;
+
+/// DHCP unique identifier (RFC 8415 11)
+#[derive(Debug, Clone, PartialEq)]
+pub enum DUID {
+    /// Link-layer address plus time
+    LLT {
+        hw_type: u16,
+        time: u32,
+        link_layer: Vec<u8>,
+    },
+    /// Enterprise number and identifier
+    EN {
+        enterprise: u32,
+        id: Vec<u8>,
+    },
+    /// Link-layer address
+    LL {
+        hw_type: u16,
+        link_layer: Vec<u8>,
+    },
+    UUID([u8; 16]),
+    Other(Vec<u8>),
+}
+
+impl DUID {
+    fn parse(data: &[u8]) -> DUID {
+        let duid = match NetworkEndian::read_u16(data.get(..2).unwrap_or(&[0, 0])) {
+            1 if data.len() >= 8 => Some(DUID::LLT {
+                hw_type: NetworkEndian::read_u16(&data[2..4]),
+                time: NetworkEndian::read_u32(&data[4..8]),
+                link_layer: data[8..].to_vec(),
+            }),
+            2 if data.len() >= 6 => Some(DUID::EN {
+                enterprise: NetworkEndian::read_u32(&data[2..6]),
+                id: data[6..].to_vec(),
+            }),
+            3 if data.len() >= 4 => Some(DUID::LL {
+                hw_type: NetworkEndian::read_u16(&data[2..4]),
+                link_layer: data[4..].to_vec(),
+            }),
+            4 => data.get(2..).and_then(|uuid| uuid.try_into().ok()).map(DUID::UUID),
+            _ => None,
+        };
+        duid.unwrap_or_else(|| DUID::Other(data.to_vec()))
+    }
+
+    /// Link-layer address, the client MAC for Ethernet
+    pub fn link_layer(&self) -> Option<&[u8]> {
+        match self {
+            DUID::LLT { link_layer, .. } | DUID::LL { link_layer, .. } => Some(link_layer),
+            _ => None,
+        }
+    }
+}
+
+/// Decoded option (RFC 8415 21), unknown or malformed ones are kept raw
+#[derive(Debug, Clone, PartialEq)]
+pub enum DHCPv6Option {
+    ClientId(DUID),
+    ServerId(DUID),
+    /// Identity association for non-temporary addresses
+    IANA {
+        iaid: u32,
+        t1: u32,
+        t2: u32,
+        options: Vec<DHCPv6Option>,
+    },
+    IAAddress {
+        address: [u8; 16],
+        preferred: u32,
+        valid: u32,
+        options: Vec<DHCPv6Option>,
+    },
+    /// Identity association for prefix delegation
+    IAPD {
+        iaid: u32,
+        t1: u32,
+        t2: u32,
+        options: Vec<DHCPv6Option>,
+    },
+    IAPrefix {
+        preferred: u32,
+        valid: u32,
+        prefix_len: u8,
+        prefix: [u8; 16],
+        options: Vec<DHCPv6Option>,
+    },
+    OptionRequest(Vec<u16>),
+    /// Hundredths of a second
+    ElapsedTime(u16),
+    /// Message carried by a relay
+    RelayMessage(Box<DHCPv6>),
+    StatusCode {
+        code: u16,
+        message: String,
+    },
+    RapidCommit,
+    InterfaceId(Vec<u8>),
+    Other {
+        code: u16,
+        data: Vec<u8>,
+    },
+}
+
+/// Option codes
+impl DHCPv6Option {
+    pub const CLIENT_ID: u16 = 1;
+    pub const SERVER_ID: u16 = 2;
+    pub const IA_NA: u16 = 3;
+    pub const IA_ADDR: u16 = 5;
+    pub const ORO: u16 = 6;
+    pub const ELAPSED_TIME: u16 = 8;
+    pub const RELAY_MSG: u16 = 9;
+    pub const STATUS_CODE: u16 = 13;
+    pub const RAPID_COMMIT: u16 = 14;
+    pub const INTERFACE_ID: u16 = 18;
+    pub const IA_PD: u16 = 25;
+    pub const IA_PREFIX: u16 = 26;
+}
+
+/// DHCPv6 message (RFC 8415 8), or relay message (RFC 8415 9) with the
+/// relayed message in its options
+#[derive(Debug, Clone, PartialEq, Layer)]
+#[layer(abbrev = "dhcpv6")]
+pub struct DHCPv6 {
+    #[layer(offset = 0)]
+    pub msg_type: u8,
+    /// 0 for relay messages
+    #[layer(offset = 1, format = "hex")]
+    pub transaction_id: u32,
+    /// Relay messages only
+    #[layer(offset = 1)]
+    pub hop_count: Option<u8>,
+    #[layer(offset = 2, format = "ipv6")]
+    pub link_address: Option<[u8; 16]>,
+    #[layer(offset = 18, format = "ipv6")]
+    pub peer_address: Option<[u8; 16]>,
+    #[layer(format = "debug")]
+    pub options: Vec<DHCPv6Option>,
+}
+
+impl DHCPv6 {
+    /// Message types
+    pub const SOLICIT: u8 = 1;
+    pub const ADVERTISE: u8 = 2;
+    pub const REQUEST: u8 = 3;
+    pub const CONFIRM: u8 = 4;
+    pub const RENEW: u8 = 5;
+    pub const REBIND: u8 = 6;
+    pub const REPLY: u8 = 7;
+    pub const RELEASE: u8 = 8;
+    pub const DECLINE: u8 = 9;
+    pub const RECONFIGURE: u8 = 10;
+    pub const INFORMATION_REQUEST: u8 = 11;
+    pub const RELAY_FORW: u8 = 12;
+    pub const RELAY_REPL: u8 = 13;
+
+    /// Relay agents on the path, bounds the nesting (RFC 8415 7.6)
+    const HOP_COUNT_LIMIT: usize = 8;
+    /// Levels of nested IA, address and prefix options, valid messages use
+    /// two
+    const NESTING_LIMIT: usize = 4;
+
+    pub fn try_make(data: &[u8]) -> Option<DHCPv6> {
+        Self::parse_nested(data, 0)
+    }
+
+    fn parse_nested(data: &[u8], depth: usize) -> Option<DHCPv6> {
+        let msg_type = *data.first()?;
+        match msg_type {
+            Self::RELAY_FORW | Self::RELAY_REPL if depth <= Self::HOP_COUNT_LIMIT => Some(DHCPv6 {
+                msg_type,
+                transaction_id: 0,
+                hop_count: Some(*data.get(1)?),
+                link_address: Some(data.get(2..18)?.try_into().unwrap()),
+                peer_address: Some(data.get(18..34)?.try_into().unwrap()),
+                options: Self::decode_options(&data[34..], depth, 0)?,
+            }),
+            Self::SOLICIT..=Self::INFORMATION_REQUEST => Some(DHCPv6 {
+                msg_type,
+                transaction_id: NetworkEndian::read_u24(data.get(1..4)?),
+                hop_count: None,
+                link_address: None,
+                peer_address: None,
+                options: Self::decode_options(&data[4..], depth, 0)?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn decode_options(mut data: &[u8], depth: usize, nesting: usize) -> Option<Vec<DHCPv6Option>> {
+        let mut options = vec![];
+        while !data.is_empty() {
+            let code = NetworkEndian::read_u16(data.get(..2)?);
+            let len = NetworkEndian::read_u16(data.get(2..4)?) as usize;
+            let value = data.get(4..4 + len)?;
+            data = &data[4 + len..];
+            options.push(Self::decode_option(code, value, depth, nesting)
+                .unwrap_or_else(|| DHCPv6Option::Other { code, data: value.to_vec() }));
+        }
+        Some(options)
+    }
+
+    /// `depth` counts relay messages and `nesting` the options around this
+    /// one, options past the nesting limit are kept raw
+    fn decode_option(code: u16, value: &[u8], depth: usize, nesting: usize) -> Option<DHCPv6Option> {
+        let u32_at = |at: usize| value.get(at..at + 4).map(NetworkEndian::read_u32);
+        let nested = |at: usize| {
+            if nesting >= Self::NESTING_LIMIT {
+                return None;
+            }
+            Self::decode_options(value.get(at..)?, depth, nesting + 1)
+        };
+        Some(match code {
+            DHCPv6Option::CLIENT_ID => DHCPv6Option::ClientId(DUID::parse(value)),
+            DHCPv6Option::SERVER_ID => DHCPv6Option::ServerId(DUID::parse(value)),
+            DHCPv6Option::IA_NA => DHCPv6Option::IANA {
+                iaid: u32_at(0)?,
+                t1: u32_at(4)?,
+                t2: u32_at(8)?,
+                options: nested(12)?,
+            },
+            DHCPv6Option::IA_PD => DHCPv6Option::IAPD {
+                iaid: u32_at(0)?,
+                t1: u32_at(4)?,
+                t2: u32_at(8)?,
+                options: nested(12)?,
+            },
+            DHCPv6Option::IA_ADDR => DHCPv6Option::IAAddress {
+                address: value.get(..16)?.try_into().unwrap(),
+                preferred: u32_at(16)?,
+                valid: u32_at(20)?,
+                options: nested(24)?,
+            },
+            DHCPv6Option::IA_PREFIX => DHCPv6Option::IAPrefix {
+                preferred: u32_at(0)?,
+                valid: u32_at(4)?,
+                prefix_len: *value.get(8)?,
+                prefix: value.get(9..25)?.try_into().unwrap(),
+                options: nested(25)?,
+            },
+            DHCPv6Option::ORO => DHCPv6Option::OptionRequest(
+                value.chunks_exact(2).map(NetworkEndian::read_u16).collect()
+            ),
+            DHCPv6Option::ELAPSED_TIME => DHCPv6Option::ElapsedTime(
+                NetworkEndian::read_u16(value.get(..2)?)
+            ),
+            DHCPv6Option::RELAY_MSG => DHCPv6Option::RelayMessage(
+                Box::new(Self::parse_nested(value, depth + 1)?)
+            ),
+            DHCPv6Option::STATUS_CODE => DHCPv6Option::StatusCode {
+                code: NetworkEndian::read_u16(value.get(..2)?),
+                message: String::from_utf8_lossy(&value[2..]).into_owned(),
+            },
+            DHCPv6Option::RAPID_COMMIT => DHCPv6Option::RapidCommit,
+            DHCPv6Option::INTERFACE_ID => DHCPv6Option::InterfaceId(value.to_vec()),
+            _ => return None,
+        })
+    }
+
+    pub fn is_relay(&self) -> bool {
+        matches!(self.msg_type, Self::RELAY_FORW | Self::RELAY_REPL)
+    }
+
+    /// Message relayed by this relay message, `None` for client and server
+    /// messages
+    pub fn relayed(&self) -> Option<&DHCPv6> {
+        self.options.iter().find_map(|o| match o {
+            DHCPv6Option::RelayMessage(message) => Some(message.as_ref()),
+            _ => None,
+        })
+    }
+
+    /// Client or server message at the bottom of the relay nesting
+    pub fn inner(&self) -> &DHCPv6 {
+        let mut message = self;
+        while let Some(relayed) = message.relayed() {
+            message = relayed;
+        }
+        message
+    }
+
+    pub fn client_id(&self) -> Option<&DUID> {
+        self.options.iter().find_map(|o| match o {
+            DHCPv6Option::ClientId(duid) => Some(duid),
+            _ => None,
+        })
+    }
+
+    pub fn server_id(&self) -> Option<&DUID> {
+        self.options.iter().find_map(|o| match o {
+            DHCPv6Option::ServerId(duid) => Some(duid),
+            _ => None,
+        })
+    }
+
+    /// Addresses of the IA_NA options
+    pub fn addresses(&self) -> Vec<[u8; 16]> {
+        self.options.iter()
+            .filter_map(|o| match o {
+                DHCPv6Option::IANA { options, .. } => Some(options),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|o| match o {
+                DHCPv6Option::IAAddress { address, .. } => Some(*address),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Prefixes and lengths of the IA_PD options
+    pub fn prefixes(&self) -> Vec<([u8; 16], u8)> {
+        self.options.iter()
+            .filter_map(|o| match o {
+                DHCPv6Option::IAPD { options, .. } => Some(options),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|o| match o {
+                DHCPv6Option::IAPrefix { prefix, prefix_len, .. } => Some((*prefix, *prefix_len)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(code: u16, value: &[u8]) -> Vec<u8> {
+        [&code.to_be_bytes()[..], &(value.len() as u16).to_be_bytes(), value].concat()
+    }
+
+    fn address(last: u8) -> [u8; 16] {
+        let mut address = [0; 16];
+        address[..2].copy_from_slice(&[0x20, 0x01]);
+        address[15] = last;
+        address
+    }
+
+    /// REPLY with DUIDs, an address, a delegated prefix and a status code
+    fn reply() -> Vec<u8> {
+        let ia_addr = option(5, &[&address(0x50)[..], &[0, 0, 0, 100, 0, 0, 0, 200]].concat());
+        let ia_na = option(3, &[&[0, 0, 0, 1, 0, 0, 0, 50, 0, 0, 0, 80][..], &ia_addr].concat());
+        let ia_prefix = option(26, &[&[0, 0, 0, 1, 0, 0, 0, 2, 56][..], &address(0)].concat());
+        let ia_pd = option(25, &[&[0, 0, 0, 2, 0, 0, 0, 50, 0, 0, 0, 80][..], &ia_prefix].concat());
+        [
+            &[DHCPv6::REPLY, 0x12, 0x34, 0x56][..],
+            &option(1, &[0, 3, 0, 1, 2, 0, 0, 0, 0, 7]),
+            &option(2, &[0, 1, 0, 1, 0, 0, 0, 9, 2, 0, 0, 0, 0, 1]),
+            &ia_na,
+            &ia_pd,
+            &option(13, b"\0\0ok"),
+        ].concat()
+    }
+
+    #[test]
+    fn message() {
+        let dhcpv6 = DHCPv6::try_make(&reply()).unwrap();
+        assert_eq!(dhcpv6.msg_type, DHCPv6::REPLY);
+        assert_eq!(dhcpv6.transaction_id, 0x123456);
+        assert!(!dhcpv6.is_relay());
+        assert_eq!(dhcpv6.client_id(), Some(&DUID::LL { hw_type: 1, link_layer: vec![2, 0, 0, 0, 0, 7] }));
+        assert_eq!(dhcpv6.client_id().unwrap().link_layer(), Some(&[2, 0, 0, 0, 0, 7][..]));
+        assert_eq!(
+            dhcpv6.server_id(),
+            Some(&DUID::LLT { hw_type: 1, time: 9, link_layer: vec![2, 0, 0, 0, 0, 1] }),
+        );
+        assert_eq!(dhcpv6.addresses(), vec![address(0x50)]);
+        assert_eq!(dhcpv6.prefixes(), vec![(address(0), 56)]);
+        assert_eq!(dhcpv6.options[4], DHCPv6Option::StatusCode { code: 0, message: "ok".to_string() });
+    }
+
+    #[test]
+    fn relay() {
+        let solicit = [
+            &[DHCPv6::SOLICIT, 0, 0, 1][..],
+            &option(6, &[0, 23, 0, 24]),
+            &option(8, &[0, 0]),
+            &option(14, &[]),
+        ].concat();
+        let relay = |hops: u8, message: &[u8]| [
+            &[DHCPv6::RELAY_FORW, hops][..],
+            &address(1),
+            &address(2),
+            &option(18, b"eth0"),
+            &option(9, message),
+        ].concat();
+        let dhcpv6 = DHCPv6::try_make(&relay(1, &relay(0, &solicit))).unwrap();
+        assert!(dhcpv6.is_relay());
+        assert_eq!(dhcpv6.hop_count, Some(1));
+        assert_eq!(dhcpv6.link_address, Some(address(1)));
+        assert_eq!(dhcpv6.options[0], DHCPv6Option::InterfaceId(b"eth0".to_vec()));
+        assert!(dhcpv6.relayed().unwrap().is_relay());
+        let inner = dhcpv6.inner();
+        assert_eq!(inner.msg_type, DHCPv6::SOLICIT);
+        assert_eq!(inner.options, vec![
+            DHCPv6Option::OptionRequest(vec![23, 24]),
+            DHCPv6Option::ElapsedTime(0),
+            DHCPv6Option::RapidCommit,
+        ]);
+        // relays nested deeper than the hop count limit are kept raw
+        let mut message = solicit;
+        for hops in 0..=DHCPv6::HOP_COUNT_LIMIT as u8 {
+            message = relay(hops, &message);
+        }
+        assert_eq!(DHCPv6::try_make(&message).unwrap().inner().msg_type, DHCPv6::SOLICIT);
+        let dhcpv6 = DHCPv6::try_make(&relay(9, &message)).unwrap();
+        let innermost = dhcpv6.inner();
+        assert_eq!(innermost.msg_type, DHCPv6::RELAY_FORW);
+        assert!(matches!(innermost.options[1], DHCPv6Option::Other { code: 9, .. }));
+    }
+
+    #[test]
+    fn nesting() {
+        let ia_na = |inner: &[u8]| option(3, &[&[0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0][..], inner].concat());
+        let nested = |levels: usize| {
+            let mut options = vec![];
+            for _ in 0..levels {
+                options = ia_na(&options);
+            }
+            [&[DHCPv6::REQUEST, 0, 0, 1][..], &options].concat()
+        };
+        let depth = |dhcpv6: &DHCPv6| {
+            let mut depth = 0;
+            let mut options = &dhcpv6.options;
+            while let Some(DHCPv6Option::IANA { options: inner, .. }) = options.first() {
+                depth += 1;
+                options = inner;
+            }
+            (depth, options.first().cloned())
+        };
+        let limit = DHCPv6::NESTING_LIMIT;
+        assert_eq!(depth(&DHCPv6::try_make(&nested(limit)).unwrap()), (limit, None));
+        let (levels, raw) = depth(&DHCPv6::try_make(&nested(limit + 1)).unwrap());
+        assert_eq!(levels, limit);
+        assert!(matches!(raw, Some(DHCPv6Option::Other { code: 3, .. })));
+        // as deep as a datagram allows
+        assert!(DHCPv6::try_make(&nested(4000)).is_some());
+    }
+
+    #[test]
+    fn truncated() {
+        let data = reply();
+        // cut inside the header or an option
+        let boundaries = [4, 18, 36, 80, 125, data.len()];
+        for end in 0..data.len() {
+            let dhcpv6 = DHCPv6::try_make(&data[..end]);
+            assert_eq!(dhcpv6.is_some(), boundaries.contains(&end), "{}", end);
+        }
+        assert!(DHCPv6::try_make(&[DHCPv6::RELAY_REPL, 0, 1]).is_none());
+    }
+
+    #[test]
+    fn malformed_options() {
+        // IA_NA shorter than its fixed fields, status code without its code,
+        // unknown option and DUID type, both kept raw
+        let data = [
+            &[DHCPv6::ADVERTISE, 0, 0, 1][..],
+            &option(3, &[0, 0, 0, 1]),
+            &option(13, &[0]),
+            &option(99, &[1, 2]),
+            &option(1, &[0, 9, 1]),
+        ].concat();
+        let dhcpv6 = DHCPv6::try_make(&data).unwrap();
+        assert_eq!(dhcpv6.options, vec![
+            DHCPv6Option::Other { code: 3, data: vec![0, 0, 0, 1] },
+            DHCPv6Option::Other { code: 13, data: vec![0] },
+            DHCPv6Option::Other { code: 99, data: vec![1, 2] },
+            DHCPv6Option::ClientId(DUID::Other(vec![0, 9, 1])),
+        ]);
+        assert_eq!(dhcpv6.client_id().unwrap().link_layer(), None);
+        assert!(DHCPv6::try_make(&[0, 0, 0, 1]).is_none());
+        assert!(DHCPv6::try_make(&[14, 0, 0, 1]).is_none());
+        assert!(DHCPv6::try_make(&[]).is_none());
+    }
+}
@@ -0,0 +1,223 @@
+use std::error::Error;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Transport {
+    TCP,
+    UDP,
+}
+
+/// Application protocols `TCP` and `UDP` can dispatch to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum App {
+    HTTP,
+    HTTP2,
+    TLS,
+    OpcUa,
+    TPKT,
+    DHCP,
+    DHCPv6,
+    DNS,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dissector {
+    pub app: App,
+    pub transport: Transport,
+    /// Well-known ports, tried before any heuristic
+    pub ports: Vec<u16>,
+    /// Probe the payload on any other port
+    pub heuristic: bool,
+    /// Higher is tried first
+    pub priority: i32,
+}
+
+/// Extra port mappings, e.g. `(tcp: {4841: OpcUa})` in RON
+#[derive(Debug, Default, Deserialize)]
+pub struct DispatchConfig {
+    #[serde(default)]
+    pub tcp: HashMap<u16, App>,
+    #[serde(default)]
+    pub udp: HashMap<u16, App>,
+}
+
+impl DispatchConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let config = std::fs::read_to_string(path)?;
+        Ok(ron::from_str(&config)?)
+    }
+}
+
+/// Decides which application dissectors to try on a segment or datagram
+#[derive(Debug, Clone)]
+pub struct Registry {
+    dissectors: Vec<Dissector>,
+    ports: HashMap<(Transport, u16), App>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Dissector {
+            app: App::HTTP,
+            transport: Transport::TCP,
+            ports: vec![80, 8000, 8008, 8080],
+            heuristic: true,
+            priority: 30,
+        });
+        registry.register(Dissector {
+            app: App::HTTP2,
+            transport: Transport::TCP,
+            ports: vec![],
+            heuristic: true,
+            priority: 15,
+        });
+        registry.register(Dissector {
+            app: App::TLS,
+            transport: Transport::TCP,
+            ports: vec![443, 465, 636, 853, 993, 995, 8443],
+            heuristic: true,
+            priority: 25,
+        });
+        registry.register(Dissector {
+            app: App::OpcUa,
+            transport: Transport::TCP,
+            ports: vec![4840],
+            heuristic: true,
+            priority: 20,
+        });
+        registry.register(Dissector {
+            app: App::TPKT,
+            transport: Transport::TCP,
+            ports: vec![102, 3389],
+            heuristic: true,
+            priority: 10,
+        });
+        registry.register(Dissector {
+            app: App::DHCP,
+            transport: Transport::UDP,
+            ports: vec![67, 68],
+            heuristic: false,
+            priority: 10,
+        });
+        registry.register(Dissector {
+            app: App::DHCPv6,
+            transport: Transport::UDP,
+            ports: vec![546, 547],
+            heuristic: false,
+            priority: 10,
+        });
+        registry.register(Dissector {
+            app: App::DNS,
+            transport: Transport::UDP,
+            ports: vec![53, 5353],
+            heuristic: false,
+            priority: 20,
+        });
+        registry.register(Dissector {
+            app: App::DNS,
+            transport: Transport::TCP,
+            ports: vec![53],
+            heuristic: false,
+            priority: 20,
+        });
+        registry
+    }
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Self { dissectors: vec![], ports: default() }
+    }
+
+    pub fn register(&mut self, dissector: Dissector) {
+        self.dissectors.push(dissector);
+        self.dissectors.sort_by_key(|d| -d.priority);
+    }
+
+    /// Map an additional port, it takes precedence over well-known ports
+    pub fn map_port(&mut self, transport: Transport, port: u16, app: App) {
+        self.ports.insert((transport, port), app);
+    }
+
+    /// The default registry with the port mappings of a `DispatchConfig` file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let mut registry = Self::default();
+        registry.configure(&DispatchConfig::load(path)?);
+        Ok(registry)
+    }
+
+    pub fn configure(&mut self, config: &DispatchConfig) {
+        for (&port, &app) in &config.tcp {
+            self.map_port(Transport::TCP, port, app);
+        }
+        for (&port, &app) in &config.udp {
+            self.map_port(Transport::UDP, port, app);
+        }
+    }
+
+    /// Applications to try in order: mapped ports, well-known ports, heuristics
+    pub fn candidates(&self, transport: Transport, src: u16, dst: u16) -> Vec<App> {
+        let mut apps = Vec::new();
+        let mut push = |app: App| if !apps.contains(&app) { apps.push(app) };
+        for port in [dst, src] {
+            if let Some(&app) = self.ports.get(&(transport, port)) {
+                push(app);
+            }
+        }
+        let dissectors = self.dissectors.iter()
+            .filter(|d| d.transport == transport);
+        for dissector in dissectors.clone() {
+            if dissector.ports.contains(&dst) || dissector.ports.contains(&src) {
+                push(dissector.app);
+            }
+        }
+        for dissector in dissectors.filter(|d| d.heuristic) {
+            push(dissector.app);
+        }
+        apps
+    }
+}
+
+/// Application chosen per flow, so later packets skip probing
+#[derive(Debug, Default)]
+pub struct FlowApps {
+    flows: HashMap<String, (App, f64)>,
+    last_sweep: f64,
+}
+
+impl FlowApps {
+    const SWEEP_PERIOD: f64 = 1.0;
+
+    pub fn get(&mut self, key: &str, ts: f64) -> Option<App> {
+        let (app, last_seen) = self.flows.get_mut(key)?;
+        *last_seen = ts;
+        Some(*app)
+    }
+
+    pub fn insert(&mut self, key: &str, app: App, ts: f64) {
+        self.flows.insert(key.to_string(), (app, ts));
+    }
+
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    /// Forget flows idle for `timeout` seconds before `ts`
+    pub fn expire(&mut self, ts: f64, timeout: Option<f64>) {
+        let timeout = match timeout {
+            Some(timeout) if ts - self.last_sweep >= Self::SWEEP_PERIOD => timeout,
+            _ => return,
+        };
+        self.last_sweep = ts;
+        self.flows.retain(|_, (_, last_seen)| ts - *last_seen <= timeout);
+    }
+}
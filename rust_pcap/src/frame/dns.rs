@@ -0,0 +1,399 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use derivative::Derivative;
+
+use crate::*;
+
+pub const A: u16 = 1;
+pub const NS: u16 = 2;
+pub const CNAME: u16 = 5;
+pub const SOA: u16 = 6;
+pub const PTR: u16 = 12;
+pub const MX: u16 = 15;
+pub const TXT: u16 = 16;
+pub const AAAA: u16 = 28;
+pub const SRV: u16 = 33;
+
+pub const NOERROR: u8 = 0;
+pub const SERVFAIL: u8 = 2;
+pub const NXDOMAIN: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DNSQuestion {
+    pub name: String,
+    pub kind: u16,
+    pub class: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DNSData {
+    A([u8; 4]),
+    AAAA([u8; 16]),
+    CNAME(String),
+    NS(String),
+    PTR(String),
+    MX {
+        preference: u16,
+        exchange: String,
+    },
+    TXT(Vec<String>),
+    SRV {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    SOA {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    /// Other types, or a known type with a malformed payload
+    Raw(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DNSRecord {
+    pub name: String,
+    pub kind: u16,
+    pub class: u16,
+    pub ttl: u32,
+    pub data: DNSData,
+}
+
+/// One DNS message (RFC 1035 4.1), flags as in RFC 4035 3.1
+#[derive(Derivative, Layer, Header)]
+#[derivative(Debug)]
+#[layer(abbrev = "dns")]
+pub struct DNS {
+    #[layer(offset = 0, format = "hex")]
+    pub id: u16,
+    /// Response
+    #[layer(offset = 2)]
+    pub qr: bool,
+    #[layer(offset = 2)]
+    #[header(bits = 4)]
+    pub opcode: u8,
+    #[layer(offset = 2)]
+    pub aa: bool,
+    #[layer(offset = 2)]
+    pub tc: bool,
+    #[layer(offset = 2)]
+    pub rd: bool,
+    #[layer(offset = 3)]
+    pub ra: bool,
+    #[layer(offset = 3)]
+    #[header(pad = 1)]
+    pub ad: bool,
+    #[layer(offset = 3)]
+    pub cd: bool,
+    #[layer(offset = 3)]
+    #[header(bits = 4)]
+    pub rcode: u8,
+    #[layer(offset = 4)]
+    pub qdcount: u16,
+    #[layer(offset = 6)]
+    pub ancount: u16,
+    #[layer(offset = 8)]
+    pub nscount: u16,
+    #[layer(offset = 10)]
+    pub arcount: u16,
+    #[layer(format = "debug")]
+    #[header(skip)]
+    pub questions: Vec<DNSQuestion>,
+    #[layer(format = "debug")]
+    #[header(skip)]
+    pub answers: Vec<DNSRecord>,
+    #[layer(format = "debug")]
+    #[header(skip)]
+    pub authorities: Vec<DNSRecord>,
+    #[layer(format = "debug")]
+    #[header(skip)]
+    pub additionals: Vec<DNSRecord>,
+}
+
+/// Length prefixed messages of one direction of a TCP connection
+#[derive(Default, Debug)]
+pub struct DNSContext {
+    /// Stream bytes not consumed by a complete message yet
+    pub buffer: Vec<u8>,
+    started: bool,
+}
+
+impl DNS {
+    /// Longest name in presentation format (RFC 1035 2.3.4)
+    const MAX_NAME: usize = 255;
+
+    /// Message of a datagram, `None` if malformed
+    pub fn try_make(data: &[u8]) -> Option<DNS> {
+        let mut dns = DNS::parse(data)?;
+        if dns.opcode > 6 {
+            return None;
+        }
+        let mut at = DNS::SIZE;
+        for _ in 0..dns.qdcount {
+            let (name, next) = name(data, at)?;
+            let fixed = data.get(next..next + 4)?;
+            dns.questions.push(DNSQuestion {
+                name,
+                kind: NetworkEndian::read_u16(&fixed[..2]),
+                class: NetworkEndian::read_u16(&fixed[2..]),
+            });
+            at = next + 4;
+        }
+        for (count, section) in [
+            (dns.ancount, &mut dns.answers),
+            (dns.nscount, &mut dns.authorities),
+            (dns.arcount, &mut dns.additionals),
+        ] {
+            for _ in 0..count {
+                let (record, next) = record(data, at)?;
+                section.push(record);
+                at = next;
+            }
+        }
+        Some(dns)
+    }
+
+    /// Messages completed by the segment (RFC 1035 4.2.2), `None` if the
+    /// stream is not DNS
+    pub fn try_make_tcp(sequence: &mut TCPSequence, tcp: &TCP) -> Option<Vec<DNS>> {
+        let cap = sequence.cap;
+        let ctx = sequence.dns.get_or_insert_with(default);
+        sequence.truncated |= extend_capped(&mut ctx.buffer, &tcp.stream, cap);
+        let mut messages = vec![];
+        while let Some(len) = ctx.buffer.get(..2).map(NetworkEndian::read_u16) {
+            let end = 2 + len as usize;
+            if ctx.buffer.len() < end {
+                break;
+            }
+            match DNS::try_make(&ctx.buffer[2..end]) {
+                Some(dns) => messages.push(dns),
+                None if !ctx.started => {
+                    sequence.dns = None;
+                    return None;
+                }
+                // keep the framing, only this message is lost
+                None => {}
+            }
+            ctx.started = true;
+            ctx.buffer.drain(..end);
+        }
+        let header = ctx.buffer.get(2..).and_then(DNS::parse);
+        if !ctx.started && header.is_some_and(|dns| dns.opcode > 6) {
+            sequence.dns = None;
+            return None;
+        }
+        Some(messages)
+    }
+
+    /// Records of every section
+    pub fn records(&self) -> impl Iterator<Item=&DNSRecord> {
+        self.answers.iter().chain(&self.authorities).chain(&self.additionals)
+    }
+
+    pub fn is_nxdomain(&self) -> bool {
+        self.qr && self.rcode == NXDOMAIN
+    }
+}
+
+/// Name at `at` with compression pointers followed, and the offset after it
+/// in the message
+fn name(message: &[u8], mut at: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::<String>::new();
+    let mut len = 0;
+    let mut end = None;
+    // each pointer must go backwards, so the loop ends
+    let mut limit = at;
+    loop {
+        let first = *message.get(at)?;
+        match first & 0xc0 {
+            0x00 if first == 0 => break,
+            0x00 => {
+                let label = message.get(at + 1..at + 1 + first as usize)?;
+                len += label.len() + 1;
+                if len > DNS::MAX_NAME {
+                    return None;
+                }
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                at += 1 + first as usize;
+            }
+            0xc0 => {
+                let pointer = (NetworkEndian::read_u16(message.get(at..at + 2)?) & 0x3fff) as usize;
+                if pointer >= limit {
+                    return None;
+                }
+                end.get_or_insert(at + 2);
+                limit = pointer;
+                at = pointer;
+            }
+            // extended label types (RFC 6891) are obsolete
+            _ => return None,
+        }
+    }
+    let name = if labels.is_empty() { ".".to_string() } else { labels.join(".") };
+    Some((name, end.unwrap_or(at + 1)))
+}
+
+fn record(message: &[u8], at: usize) -> Option<(DNSRecord, usize)> {
+    let (name, at) = name(message, at)?;
+    let fixed = message.get(at..at + 10)?;
+    let kind = NetworkEndian::read_u16(&fixed[..2]);
+    let rdlength = NetworkEndian::read_u16(&fixed[8..]) as usize;
+    let start = at + 10;
+    let rdata = message.get(start..start + rdlength)?;
+    let data = rdata_of(message, kind, start, rdata)
+        .unwrap_or_else(|| DNSData::Raw(rdata.to_vec()));
+    Some((DNSRecord {
+        name,
+        kind,
+        class: NetworkEndian::read_u16(&fixed[2..4]),
+        ttl: NetworkEndian::read_u32(&fixed[4..8]),
+        data,
+    }, start + rdlength))
+}
+
+/// Names inside the data may point anywhere in the message
+fn rdata_of(message: &[u8], kind: u16, start: usize, rdata: &[u8]) -> Option<DNSData> {
+    let name_at = |offset: usize| name(message, start + offset).map(|(name, _)| name);
+    Some(match kind {
+        A => DNSData::A(rdata.try_into().ok()?),
+        AAAA => DNSData::AAAA(rdata.try_into().ok()?),
+        CNAME => DNSData::CNAME(name_at(0)?),
+        NS => DNSData::NS(name_at(0)?),
+        PTR => DNSData::PTR(name_at(0)?),
+        MX => DNSData::MX {
+            preference: NetworkEndian::read_u16(rdata.get(..2)?),
+            exchange: name_at(2)?,
+        },
+        TXT => {
+            let mut strings = vec![];
+            let mut rest = rdata;
+            while let Some(&len) = rest.first() {
+                strings.push(String::from_utf8_lossy(rest.get(1..1 + len as usize)?).into_owned());
+                rest = &rest[1 + len as usize..];
+            }
+            DNSData::TXT(strings)
+        }
+        SRV => DNSData::SRV {
+            priority: NetworkEndian::read_u16(rdata.get(..2)?),
+            weight: NetworkEndian::read_u16(rdata.get(2..4)?),
+            port: NetworkEndian::read_u16(rdata.get(4..6)?),
+            target: name_at(6)?,
+        },
+        SOA => {
+            let (mname, at) = name(message, start)?;
+            let (rname, at) = name(message, at)?;
+            let values = message.get(at..at + 20)?;
+            let value = |i: usize| NetworkEndian::read_u32(&values[i * 4..]);
+            DNSData::SOA {
+                mname,
+                rname,
+                serial: value(0),
+                refresh: value(1),
+                retry: value(2),
+                expire: value(3),
+                minimum: value(4),
+            }
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Response to `www.example.com A`: a CNAME to `example.com` and its
+    /// address, names compressed, then an MX and a TXT record
+    fn response() -> Vec<u8> {
+        let mut message = vec![
+            0x12, 0x34, 0x81, 0x80, 0, 1, 0, 4, 0, 0, 0, 0,
+            3, b'w', b'w', b'w', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0,
+            0, 1, 0, 1,
+        ];
+        // www.example.com CNAME example.com
+        message.extend([0xc0, 12, 0, 5, 0, 1, 0, 0, 0x0e, 0x10, 0, 2, 0xc0, 16]);
+        // example.com A 93.184.216.34
+        message.extend([0xc0, 16, 0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4, 93, 184, 216, 34]);
+        // example.com MX 10 mail.example.com
+        message.extend([0xc0, 16, 0, 15, 0, 1, 0, 0, 0, 60, 0, 9, 0, 10, 4, b'm', b'a', b'i', b'l', 0xc0, 16]);
+        // example.com TXT "v=1" ""
+        message.extend([0xc0, 16, 0, 16, 0, 1, 0, 0, 0, 60, 0, 5, 3, b'v', b'=', b'1', 0]);
+        message
+    }
+
+    #[test]
+    fn message() {
+        let data = response();
+        let dns = DNS::try_make(&data).unwrap();
+        assert_eq!(dns.id, 0x1234);
+        assert!(dns.qr && dns.rd && dns.ra && !dns.aa && !dns.tc);
+        assert_eq!(dns.rcode, NOERROR);
+        assert_eq!(dns.serialize(), data[..DNS::SIZE]);
+        assert_eq!(dns.questions, vec![DNSQuestion { name: "www.example.com".to_string(), kind: A, class: 1 }]);
+        let data = dns.answers.iter().map(|r| r.data.clone()).collect::<Vec<_>>();
+        assert_eq!(data, vec![
+            DNSData::CNAME("example.com".to_string()),
+            DNSData::A([93, 184, 216, 34]),
+            DNSData::MX { preference: 10, exchange: "mail.example.com".to_string() },
+            DNSData::TXT(vec!["v=1".to_string(), String::new()]),
+        ]);
+        assert_eq!(dns.answers[0].name, "www.example.com");
+        assert_eq!(dns.answers[0].ttl, 3600);
+        assert_eq!(dns.records().count(), 4);
+    }
+
+    #[test]
+    fn truncated() {
+        let data = response();
+        for end in 0..data.len() {
+            assert!(DNS::try_make(&data[..end]).is_none(), "{}", end);
+        }
+    }
+
+    #[test]
+    fn malformed_rdata() {
+        // an A record of 3 bytes is kept raw
+        let mut data = vec![0, 1, 0x80, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        data.extend([0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 3, 1, 2, 3]);
+        let dns = DNS::try_make(&data).unwrap();
+        assert_eq!(dns.answers[0].name, ".");
+        assert_eq!(dns.answers[0].data, DNSData::Raw(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn bad_names() {
+        let header = [0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        let question = |name: &[u8]| [&header[..], name, &[0, 1, 0, 1]].concat();
+        // pointer to itself, forward and to a later pointer
+        assert!(DNS::try_make(&question(&[0xc0, 12])).is_none());
+        assert!(DNS::try_make(&question(&[0xc0, 14, 0])).is_none());
+        // extended label type
+        assert!(DNS::try_make(&question(&[0x41, 0])).is_none());
+        // longer than 255 bytes
+        let long = (0..5).flat_map(|_| [&[63][..], &[b'a'; 63]].concat()).chain([0]).collect::<Vec<_>>();
+        assert!(DNS::try_make(&question(&long)).is_none());
+        let short = (0..3).flat_map(|_| [&[63][..], &[b'a'; 63]].concat()).chain([0]).collect::<Vec<_>>();
+        assert!(DNS::try_make(&question(&short)).is_some());
+    }
+
+    #[test]
+    fn pointer_loop() {
+        // the answer name points to the question name, which points back
+        let mut data = vec![0, 1, 0x80, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, b'a', 0xc0, 12, 0, 1, 0, 1];
+        assert!(DNS::try_make(&data).is_none());
+        data[15] = 18;
+        assert!(DNS::try_make(&data).is_none());
+    }
+
+    #[test]
+    fn opcode() {
+        let mut data = response();
+        data[2] = 0x80 | 7 << 3;
+        assert!(DNS::try_make(&data).is_none());
+    }
+}
@@ -7,10 +7,15 @@ use crate::goose::GOOSE;
 use crate::sv::SampledValue;
 
 #[derive(Layer)]
+#[layer(abbrev = "eth")]
 pub struct Ethernet {
-    src: [u8; 6],
-    dst: [u8; 6],
+    #[layer(offset = 0)]
+    pub dst: [u8; 6],
+    #[layer(offset = 6)]
+    pub src: [u8; 6],
+    #[layer(offset = 12, format = "hex")]
     eth_type: u16,
+    #[layer(format = "hex")]
     crc: [u8; 4],
     layers: Layers,
 }
@@ -23,6 +28,32 @@ impl std::fmt::Debug for Ethernet {
     }
 }
 
+/// IEEE 802.1Q tag, stacked tags are consecutive layers of an [`Ethernet`]
+#[derive(Debug, Layer)]
+pub struct VLAN {
+    /// 0x8100 for 802.1Q or 0x88A8 for an 802.1ad service tag
+    #[layer(offset = 0, format = "hex")]
+    pub tpid: u16,
+    #[layer(offset = 2)]
+    pub pcp: u8,
+    #[layer(offset = 2)]
+    pub dei: bool,
+    #[layer(offset = 2)]
+    pub vid: u16,
+}
+
+impl VLAN {
+    pub fn new(tpid: u16, tci: &[u8]) -> VLAN {
+        let tci = NetworkEndian::read_u16(tci);
+        VLAN {
+            tpid,
+            pcp: (tci >> 13) as u8,
+            dei: tci & 0x1000 > 0,
+            vid: tci & 0x0FFF,
+        }
+    }
+}
+
 static WARN_ETHER_TYPE: bool = false;
 
 impl Ethernet {
@@ -54,15 +85,37 @@ impl Ethernet {
     const GOOSE: u16 = 0x88B8;
     const SV: u16 = 0x88BA;
 
+    const IEEE_802_1AD: u16 = 0x88A8;
+
     pub fn new(data: MultipartSlice, ctx: &mut DissectionContext) -> Ethernet {
         let mut layers = Layers::default();
-        let eth_type = NetworkEndian::read_u16(data.get(12..14).unwrap());
+        let mut data = data;
+        let mut eth_type = NetworkEndian::read_u16(data.get(12..14).unwrap());
+        while [Self::IEEE_802_1Q, Self::IEEE_802_1AD].contains(&eth_type) {
+            layers.insert(VLAN::new(eth_type, data.get(14..16).unwrap()));
+            data = MultipartSlice {
+                slices: vec![
+                    data.get(..12).unwrap(),
+                    data.get(16..).unwrap(),
+                ]
+            };
+            eth_type = NetworkEndian::read_u16(data.get(12..14).unwrap());
+        }
         match eth_type {
             Self::IP4 => {
-                layers.insert(IPv4::new(data.get(14..).unwrap(), ctx));
+                if let Some(ipv4) = IPv4::try_make(data.get(14..).unwrap(), ctx) {
+                    layers.insert(ipv4);
+                }
             }
             Self::ARP => {
-                layers.insert(ARP::new(data.get(14..).unwrap()));
+                if let Some(arp) = ARP::try_make(data.get(14..).unwrap()) {
+                    layers.insert(arp);
+                }
+            }
+            Self::IP6 => {
+                if let Some(ipv6) = IPv6::try_make(data.get(14..).unwrap(), ctx) {
+                    layers.insert(ipv6);
+                }
             }
             Self::WAKE_ON_LAN => { if WARN_ETHER_TYPE { println!("WAKE_ON_LAN not implemented") } }
             Self::AVTP => { if WARN_ETHER_TYPE { println!("AVTP not implemented") } }
@@ -74,23 +127,10 @@ impl Ethernet {
             Self::RARP => { if WARN_ETHER_TYPE { println!("RARP not implemented") } }
             Self::ETHERTALK => { if WARN_ETHER_TYPE { println!("ETHERTALK not implemented") } }
             Self::AARP => { if WARN_ETHER_TYPE { println!("AARP not implemented") } }
-            Self::IEEE_802_1Q => {
-                // TODO: maybe need tag layer?
-                // let data = [&data[..12], &data[16..]].concat();
-                // return Self::new(data.as_slice(), ctx);
-                let slice = MultipartSlice {
-                    slices: vec![
-                        data.get(..12).unwrap(),
-                        data.get(16..).unwrap(),
-                    ]
-                };
-                return Self::new(slice, ctx);
-            }
             Self::SLPP => { if WARN_ETHER_TYPE { println!("SLPP not implemented") } }
             Self::VLACP => { if WARN_ETHER_TYPE { println!("VLACP not implemented") } }
             Self::IPX => { if WARN_ETHER_TYPE { println!("IPX not implemented") } }
             Self::QNET => { if WARN_ETHER_TYPE { println!("QNET not implemented") } }
-            Self::IP6 => { if WARN_ETHER_TYPE { println!("IP6 not implemented") } }
             Self::EFC => { if WARN_ETHER_TYPE { println!("EFC not implemented") } }
             Self::LACP => { if WARN_ETHER_TYPE { println!("LACP not implemented") } }
             Self::COBRA_NET => { if WARN_ETHER_TYPE { println!("COBRA_NET not implemented") } }
@@ -105,8 +145,8 @@ impl Ethernet {
             _ => { if WARN_ETHER_TYPE { println!("unknown eth_type: {:#04x}", eth_type) } }
         }
         Ethernet {
-            src: get_array!(data, 0..6),
-            dst: get_array!(data, 6..12),
+            dst: get_array!(data, 0..6),
+            src: get_array!(data, 6..12),
             eth_type,
             crc: get_array!(data, (data.len() - 4)..),
             layers,
@@ -118,7 +158,4 @@ impl HasLayers for Ethernet {
     fn layers(&self) -> &Layers {
         &self.layers
     }
-    fn get_layer_descendants<T>(&self) -> Option<&T> where T: Layer {
-        get_layer_descendants!(self, T, IPv4)
-    }
 }
\ No newline at end of file
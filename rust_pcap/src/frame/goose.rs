@@ -4,10 +4,15 @@ use byteorder::{ByteOrder, NetworkEndian};
 
 #[derive(Layer)]
 pub struct GOOSE {
+    #[layer(offset = 0, format = "hex")]
     pub app_id: u16,
+    #[layer(offset = 2)]
     pub len: u16,
+    #[layer(offset = 4)]
     pub reserved1: u16,
+    #[layer(offset = 6)]
     pub reserved2: u16,
+    #[layer(offset = 8)]
     pub apdu: Vec<u8>,
     pub ext: Vec<u8>,
 }
@@ -0,0 +1,413 @@
+use std::collections::VecDeque;
+
+use lazy_static::lazy_static;
+
+use crate::default;
+
+/// RFC 7541 appendix A
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// Huffman code lengths of the 256 octets and EOS (RFC 7541 appendix B),
+/// the code is canonical so the codes follow from the lengths
+const HUFFMAN_LENGTHS: [u8; 257] = [
+    13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28,
+    28, 28, 28, 28, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    6, 10, 10, 12, 13, 6, 8, 11, 10, 10, 8, 11, 8, 6, 6, 6,
+    5, 5, 5, 6, 6, 6, 6, 6, 6, 6, 7, 8, 15, 6, 12, 10,
+    13, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 8, 7, 8, 13, 19, 13, 14, 6,
+    15, 5, 6, 5, 6, 5, 6, 6, 6, 5, 7, 7, 6, 6, 6, 5,
+    6, 7, 6, 5, 5, 6, 7, 7, 7, 7, 7, 15, 11, 14, 13, 28,
+    20, 22, 20, 20, 22, 22, 22, 23, 22, 23, 23, 23, 23, 23, 24, 23,
+    24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24,
+    22, 21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23,
+    21, 21, 22, 21, 23, 22, 23, 23, 20, 22, 22, 22, 23, 22, 22, 23,
+    26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25,
+    19, 21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27,
+    20, 24, 20, 21, 22, 21, 21, 23, 22, 22, 25, 25, 24, 24, 26, 23,
+    26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26,
+    30,
+];
+
+const EOS: u16 = 256;
+const MAX_CODE_LEN: usize = 30;
+
+/// Canonical decoding tables: first code and symbols of each length
+struct Huffman {
+    first: [u32; MAX_CODE_LEN + 1],
+    count: [u32; MAX_CODE_LEN + 1],
+    offset: [usize; MAX_CODE_LEN + 1],
+    symbols: Vec<u16>,
+}
+
+lazy_static! {
+    static ref HUFFMAN: Huffman = Huffman::new();
+}
+
+impl Huffman {
+    fn new() -> Self {
+        let mut symbols = (0..=EOS).collect::<Vec<_>>();
+        symbols.sort_by_key(|&s| (HUFFMAN_LENGTHS[s as usize], s));
+        let mut huffman = Huffman {
+            first: [0; MAX_CODE_LEN + 1],
+            count: [0; MAX_CODE_LEN + 1],
+            offset: [0; MAX_CODE_LEN + 1],
+            symbols,
+        };
+        for &len in HUFFMAN_LENGTHS.iter() {
+            huffman.count[len as usize] += 1;
+        }
+        let mut code = 0;
+        let mut offset = 0;
+        for len in 1..=MAX_CODE_LEN {
+            huffman.first[len] = code;
+            huffman.offset[len] = offset;
+            code = (code + huffman.count[len]) << 1;
+            offset += huffman.count[len] as usize;
+        }
+        huffman
+    }
+
+    fn decode(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(data.len() * 8 / 5);
+        let (mut code, mut len) = (0u32, 0usize);
+        for i in 0..data.len() * 8 {
+            let bit = (data[i / 8] >> (7 - i % 8)) & 1;
+            code = code << 1 | bit as u32;
+            len += 1;
+            if len > MAX_CODE_LEN {
+                return None;
+            }
+            let index = code.wrapping_sub(self.first[len]);
+            if index < self.count[len] {
+                match self.symbols[self.offset[len] + index as usize] {
+                    EOS => return None,
+                    symbol => out.push(symbol as u8),
+                }
+                code = 0;
+                len = 0;
+            }
+        }
+        // padding is the most significant bits of EOS, all ones
+        if len >= 8 || code != (1 << len) - 1 {
+            return None;
+        }
+        Some(out)
+    }
+}
+
+/// Header block decoder of one direction of a connection
+#[derive(Debug)]
+pub struct HPACKDecoder {
+    /// Newest entry first
+    table: VecDeque<(String, String)>,
+    size: usize,
+    pub max_size: usize,
+    /// `SETTINGS_HEADER_TABLE_SIZE` of the receiver, size updates above it
+    /// are errors (RFC 7541 6.3)
+    pub limit: usize,
+}
+
+impl Default for HPACKDecoder {
+    fn default() -> Self {
+        Self { table: default(), size: 0, max_size: Self::DEFAULT_SIZE, limit: Self::DEFAULT_SIZE }
+    }
+}
+
+impl HPACKDecoder {
+    pub const DEFAULT_SIZE: usize = 4096;
+    /// Size overhead of a table entry (RFC 7541 4.1)
+    const ENTRY_OVERHEAD: usize = 32;
+
+    /// Header list of a complete header block, `None` if malformed
+    pub fn decode(&mut self, mut block: &[u8]) -> Option<Vec<(String, String)>> {
+        let mut headers = vec![];
+        while let Some(&first) = block.first() {
+            if first & 0x80 != 0 {
+                // indexed header field
+                let (index, rest) = integer(block, 7)?;
+                headers.push(self.get(index)?);
+                block = rest;
+            } else if first & 0x40 != 0 {
+                // literal with incremental indexing
+                let (header, rest) = self.literal(block, 6)?;
+                self.insert(header.clone());
+                headers.push(header);
+                block = rest;
+            } else if first & 0x20 != 0 {
+                // dynamic table size update
+                let (size, rest) = integer(block, 5)?;
+                if size > self.limit {
+                    return None;
+                }
+                self.max_size = size;
+                self.evict(0);
+                block = rest;
+            } else {
+                // literal without indexing or never indexed
+                let (header, rest) = self.literal(block, 4)?;
+                headers.push(header);
+                block = rest;
+            }
+        }
+        Some(headers)
+    }
+
+    fn get(&self, index: usize) -> Option<(String, String)> {
+        match index {
+            0 => None,
+            1..=61 => {
+                let (name, value) = STATIC_TABLE[index - 1];
+                Some((name.to_string(), value.to_string()))
+            }
+            _ => self.table.get(index - 62).cloned(),
+        }
+    }
+
+    fn literal<'a>(&self, block: &'a [u8], prefix: u8) -> Option<((String, String), &'a [u8])> {
+        let (index, rest) = integer(block, prefix)?;
+        let (name, rest) = match index {
+            0 => string(rest)?,
+            _ => (self.get(index)?.0, rest),
+        };
+        let (value, rest) = string(rest)?;
+        Some(((name, value), rest))
+    }
+
+    fn insert(&mut self, header: (String, String)) {
+        let size = header.0.len() + header.1.len() + Self::ENTRY_OVERHEAD;
+        self.evict(size);
+        // an entry larger than the table empties it and is not added
+        if size <= self.max_size {
+            self.size += size;
+            self.table.push_front(header);
+        }
+    }
+
+    /// Drop oldest entries until `room` more bytes fit
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.max_size {
+            match self.table.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + Self::ENTRY_OVERHEAD,
+                None => break,
+            }
+        }
+    }
+}
+
+/// Integer with an N-bit prefix (RFC 7541 5.1)
+fn integer(data: &[u8], prefix: u8) -> Option<(usize, &[u8])> {
+    let max = (1usize << prefix) - 1;
+    let value = (*data.first()? as usize) & max;
+    if value < max {
+        return Some((value, &data[1..]));
+    }
+    let mut value = max;
+    for (i, &b) in data[1..].iter().enumerate() {
+        let shift = 7 * i as u32;
+        if shift > 28 {
+            return None;
+        }
+        value += ((b & 0x7f) as usize) << shift;
+        if b & 0x80 == 0 {
+            return Some((value, &data[i + 2..]));
+        }
+    }
+    None
+}
+
+/// String literal, optionally Huffman coded (RFC 7541 5.2)
+fn string(data: &[u8]) -> Option<(String, &[u8])> {
+    let huffman = *data.first()? & 0x80 != 0;
+    let (len, rest) = integer(data, 7)?;
+    let raw = rest.get(..len)?;
+    let bytes = if huffman { HUFFMAN.decode(raw)? } else { raw.to_vec() };
+    Some((String::from_utf8_lossy(&bytes).into_owned(), &rest[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(s: &str) -> Vec<u8> {
+        let s = s.replace(' ', "");
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn headers(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|&(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn integers() {
+        // RFC 7541 C.1
+        assert_eq!(integer(&[0b0000_1010], 5), Some((10, &[][..])));
+        assert_eq!(integer(&[0b0001_1111, 0b1001_1010, 0b0000_1010, 0xff], 5), Some((1337, &[0xff][..])));
+        assert_eq!(integer(&[42], 8), Some((42, &[][..])));
+        // continuation bit set on the last byte
+        assert_eq!(integer(&[0b0001_1111, 0b1001_1010], 5), None);
+        assert_eq!(integer(&[], 5), None);
+        // longer than any header length
+        assert_eq!(integer(&[0x1f, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01], 5), None);
+    }
+
+    /// Requests of RFC 7541 C.3 and C.4, as raw and Huffman coded literals
+    fn requests(blocks: [&str; 3]) {
+        let mut decoder = HPACKDecoder::default();
+        let first = headers(&[(":method", "GET"), (":scheme", "http"), (":path", "/"), (":authority", "www.example.com")]);
+        assert_eq!(decoder.decode(&unhex(blocks[0])), Some(first));
+        assert_eq!(decoder.size, 57);
+        let second = headers(&[
+            (":method", "GET"), (":scheme", "http"), (":path", "/"), (":authority", "www.example.com"),
+            ("cache-control", "no-cache"),
+        ]);
+        assert_eq!(decoder.decode(&unhex(blocks[1])), Some(second));
+        assert_eq!(decoder.size, 110);
+        let third = headers(&[
+            (":method", "GET"), (":scheme", "https"), (":path", "/index.html"), (":authority", "www.example.com"),
+            ("custom-key", "custom-value"),
+        ]);
+        assert_eq!(decoder.decode(&unhex(blocks[2])), Some(third));
+        assert_eq!(decoder.size, 164);
+        assert_eq!(decoder.table[0], ("custom-key".to_string(), "custom-value".to_string()));
+    }
+
+    #[test]
+    fn raw_requests() {
+        requests([
+            "8286 8441 0f77 7777 2e65 7861 6d70 6c65 2e63 6f6d",
+            "8286 84be 5808 6e6f 2d63 6163 6865",
+            "8287 85bf 400a 6375 7374 6f6d 2d6b 6579 0c63 7573 746f 6d2d 7661 6c75 65",
+        ]);
+    }
+
+    #[test]
+    fn huffman_requests() {
+        requests([
+            "8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff",
+            "8286 84be 5886 a8eb 1064 9cbf",
+            "8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf",
+        ]);
+    }
+
+    #[test]
+    fn eviction() {
+        let mut decoder = HPACKDecoder { max_size: 80, ..default() };
+        // two entries of 32 + 4 bytes each fit, a third evicts the oldest
+        for name in ["ab", "cd", "ef"] {
+            let mut block = vec![0x40, 2];
+            block.extend(name.as_bytes());
+            block.extend([2, b'v', b'v']);
+            decoder.decode(&block).unwrap();
+        }
+        assert_eq!(decoder.table.len(), 2);
+        assert_eq!(decoder.size, 72);
+        assert_eq!(decoder.decode(&[0xbe]), Some(headers(&[("ef", "vv")])));
+        assert_eq!(decoder.decode(&[0xbf]), Some(headers(&[("cd", "vv")])));
+        assert_eq!(decoder.decode(&[0xc0]), None);
+        // size update to zero empties the table
+        assert_eq!(decoder.decode(&[0x20]), Some(vec![]));
+        assert!(decoder.table.is_empty());
+        assert_eq!(decoder.size, 0);
+    }
+
+    #[test]
+    fn size_update_limit() {
+        let mut decoder = HPACKDecoder { limit: 256, ..default() };
+        // sizes 256 and 257 with a 5-bit prefix
+        assert_eq!(decoder.decode(&[0x3f, 0xe1, 0x01]), Some(vec![]));
+        assert_eq!(decoder.max_size, 256);
+        assert_eq!(decoder.decode(&[0x3f, 0xe2, 0x01]), None);
+        assert_eq!(decoder.max_size, 256);
+        // 4097, past the default
+        assert_eq!(HPACKDecoder::default().decode(&[0x3f, 0xe2, 0x1f]), None);
+    }
+
+    #[test]
+    fn malformed() {
+        let mut decoder = HPACKDecoder::default();
+        // index 0 and past both tables
+        assert_eq!(decoder.decode(&[0x80]), None);
+        assert_eq!(decoder.decode(&[0xbe]), None);
+        // string longer than the block
+        assert_eq!(decoder.decode(&[0x40, 0x05, b'a', b'b']), None);
+        assert_eq!(decoder.decode(&[0x40]), None);
+        // Huffman padding of more than 7 bits, and padding not all ones
+        assert_eq!(decoder.decode(&[0x00, 0x82, 0xff, 0xff, 0x00]), None);
+        assert_eq!(decoder.decode(&[0x00, 0x81, 0x00, 0x00]), None);
+        // EOS inside the string
+        assert_eq!(decoder.decode(&[0x00, 0x84, 0xff, 0xff, 0xff, 0xff, 0x00]), None);
+        assert!(decoder.table.is_empty());
+    }
+
+    #[test]
+    fn truncated_blocks() {
+        let block = unhex("8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff");
+        assert_eq!(HPACKDecoder::default().decode(&block[..3]).map(|h| h.len()), Some(3));
+        for end in 4..block.len() {
+            assert_eq!(HPACKDecoder::default().decode(&block[..end]), None, "{}", end);
+        }
+    }
+}
@@ -1,16 +1,15 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::collections::VecDeque;
+use std::io::Read;
 
 use derivative::Derivative;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
 use lazy_static::lazy_static;
-use nom::bytes::complete::take_until;
-use nom::IResult;
-use nom_locate::{LocatedSpan, position};
+use memmem::{Searcher, TwoWaySearcher};
 use regex::Regex;
 
 use crate::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum HTTPKind {
     Req {
         method: String,
@@ -22,109 +21,314 @@ pub enum HTTPKind {
     },
 }
 
+/// Request a response answers
+#[derive(Debug, Clone)]
+pub struct HTTPRequest {
+    pub method: String,
+    pub uri: String,
+    pub headers: Vec<(String, String)>,
+    /// Body size on the wire
+    pub body_len: usize,
+    /// Timestamp of the segment completing the request
+    pub ts: f64,
+}
+
+/// One complete HTTP/1.x message, a segment may carry several of them
 #[derive(Derivative, Layer)]
 #[derivative(Debug)]
 pub struct HTTP {
+    #[layer(format = "debug")]
     pub kind: HTTPKind,
+    #[layer(format = "debug")]
     pub version: (u8, u8),
-    pub headers: Vec<String>,
+    #[layer(format = "debug")]
+    pub headers: Vec<(String, String)>,
+    /// Body without transfer and content encodings
     #[derivative(Debug = "ignore")]
-    pub payload: HTTPPayload,
+    #[layer(skip)]
+    pub body: Vec<u8>,
+    /// Body size on the wire
+    pub body_len: usize,
+    /// Request of a response, pipelined requests are answered in order
+    #[layer(format = "debug")]
+    pub request: Option<HTTPRequest>,
+    /// `false` when the stream ended before the end of the body
     pub is_finalized: bool,
+    /// The decoded body was cut at the stream cap
+    pub truncated: bool,
 }
 
-type HTTPPayload = Rc<RefCell<Vec<u8>>>;
-
+/// Parsing state of one direction of a TCP stream
 #[derive(Default, Debug)]
 pub struct HTTPContext {
-    pub latest: Vec<u8>,
-    // indexes
-    pub first: Option<usize>,
-    pub last: Option<usize>,
-    pub payload: HTTPPayload,
+    /// Stream bytes not consumed by a complete message yet
+    pub buffer: Vec<u8>,
+    /// Messages parsed on the stream
+    pub messages: usize,
 }
 
+/// Requests waiting for a response, per client to server stream
+#[derive(Default, Debug)]
+pub struct HTTPPending {
+    requests: HashMap<String, (VecDeque<HTTPRequest>, f64)>,
+    last_sweep: f64,
+}
 
+impl HTTPPending {
+    const SWEEP_PERIOD: f64 = 1.0;
+    /// Requests kept per stream, older ones are dropped
+    const MAX_PIPELINED: usize = 64;
 
-lazy_static! {
-    static ref REQ_REGEX: Regex = Regex::new(r"(?P<method>OPTIONS|GET|HEAD|POST|PUT|PATCH|DELETE|TRACE|CONNECT) (?P<uri>.+?) HTTP/(?P<major>\d)\.(?P<minor>\d)").unwrap();
-    static ref RES_REGEX: Regex = Regex::new(r"HTTP/(?P<major>\d)\.(?P<minor>\d) (?P<status>\d\d\d) (?P<reason>.*)$").unwrap();
+    pub fn push(&mut self, key: &str, request: HTTPRequest, ts: f64) {
+        let (requests, last_seen) = self.requests.entry(key.to_string()).or_default();
+        if requests.len() == Self::MAX_PIPELINED {
+            requests.pop_front();
+        }
+        requests.push_back(request);
+        *last_seen = ts;
+    }
+
+    pub fn front(&self, key: &str) -> Option<&HTTPRequest> {
+        self.requests.get(key)?.0.front()
+    }
+
+    pub fn pop(&mut self, key: &str) -> Option<HTTPRequest> {
+        let (requests, _) = self.requests.get_mut(key)?;
+        let request = requests.pop_front();
+        if requests.is_empty() {
+            self.requests.remove(key);
+        }
+        request
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Forget requests unanswered for `timeout` seconds before `ts`
+    pub fn expire(&mut self, ts: f64, timeout: Option<f64>) {
+        let timeout = match timeout {
+            Some(timeout) if ts - self.last_sweep >= Self::SWEEP_PERIOD => timeout,
+            _ => return,
+        };
+        self.last_sweep = ts;
+        self.requests.retain(|_, (_, last_seen)| ts - *last_seen <= timeout);
+    }
 }
 
-type Span<'a> = LocatedSpan<&'a [u8]>;
+lazy_static! {
+    static ref REQ_REGEX: Regex = Regex::new(r"^(?P<method>OPTIONS|GET|HEAD|POST|PUT|PATCH|DELETE|TRACE|CONNECT) (?P<uri>.+?) HTTP/(?P<major>\d)\.(?P<minor>\d)$").unwrap();
+    static ref RES_REGEX: Regex = Regex::new(r"^HTTP/(?P<major>\d)\.(?P<minor>\d) (?P<status>\d\d\d) ?(?P<reason>.*)$").unwrap();
+}
 
 const LINE: &str = "\r\n";
 const LINE2: &str = "\r\n\r\n";
 
-struct Token<'a> {
-    position: Span<'a>,
+fn find(data: &[u8], pattern: &str) -> Option<usize> {
+    TwoWaySearcher::new(pattern.as_bytes()).search_in(data)
 }
 
-fn parse_line(s: Span) -> IResult<Span, Token> {
-    let (s, _) = take_until(LINE.as_bytes())(s)?;
-    let (s, pos) = position(s)?;
-    Ok((s, Token { position: pos }))
+/// How the end of a body is found (RFC 7230 3.3.3)
+enum Framing {
+    Empty,
+    Length(usize),
+    Chunked,
+    Close,
 }
 
-fn parse_line2(s: Span) -> IResult<Span, Token> {
-    let (s, _) = take_until(LINE2.as_bytes())(s)?;
-    let (s, pos) = position(s)?;
-    Ok((s, Token { position: pos }))
+enum Parse {
+    Incomplete,
+    Invalid,
+    /// Message and bytes it takes
+    Done(Box<HTTP>, usize),
 }
 
 impl HTTP {
-    pub fn try_make(ctx: &mut TCPSequence, tcp: &TCP) -> Option<HTTP> {
-        let ctx = &mut ctx.http.get_or_insert(default());
-        ctx.latest.extend(&tcp.data);
-        let data = ctx.latest.as_slice();
-        if data.is_empty() {
-            return None;
-        }
-        let first = if let Some(first) = &ctx.first {
-            *first
-        } else {
-            data.windows(LINE.len())
-                .position(|e| e == LINE.as_bytes())?
+    /// Messages completed by the segment, `None` if the stream is not HTTP
+    pub fn try_make(
+        sequence: &mut TCPSequence,
+        tcp: &TCP,
+        pending: &mut HTTPPending,
+        flow: &Flow,
+    ) -> Option<Vec<HTTP>> {
+        let Flow { key, reverse, ts } = *flow;
+        let cap = sequence.cap;
+        let ctx = sequence.http.get_or_insert_with(default);
+        sequence.truncated |= extend_capped(&mut ctx.buffer, &tcp.stream, cap);
+        let closed = tcp.flags.fin || tcp.flags.rst;
+        let mut messages = vec![];
+        while !ctx.buffer.is_empty() {
+            match Self::parse(&ctx.buffer, pending.front(reverse), closed, cap) {
+                Parse::Incomplete => break,
+                Parse::Invalid if ctx.messages == 0 => {
+                    sequence.http = None;
+                    return None;
+                }
+                Parse::Invalid => {
+                    // lost track of message boundaries, wait for the next one
+                    ctx.buffer.clear();
+                    break;
+                }
+                Parse::Done(mut http, used) => {
+                    ctx.buffer.drain(..used);
+                    ctx.messages += 1;
+                    sequence.truncated |= http.truncated;
+                    match &http.kind {
+                        HTTPKind::Req { method, uri } => pending.push(key, HTTPRequest {
+                            method: method.clone(),
+                            uri: uri.clone(),
+                            headers: http.headers.clone(),
+                            body_len: http.body_len,
+                            ts,
+                        }, ts),
+                        // interim responses precede the final one
+                        HTTPKind::Res { status: 100..=199, .. } => {
+                            http.request = pending.front(reverse).cloned();
+                        }
+                        HTTPKind::Res { .. } => http.request = pending.pop(reverse),
+                    }
+                    messages.push(*http);
+                }
+            }
+        }
+        // the stream is not known to be HTTP before a message parsed
+        (ctx.messages > 0).then_some(messages)
+    }
+
+    fn parse(data: &[u8], request: Option<&HTTPRequest>, closed: bool, cap: Option<usize>) -> Parse {
+        let line_end = match find(data, LINE) {
+            Some(end) => end,
+            None => return Parse::Incomplete,
         };
-        ctx.first = Some(first);
-        let first = data.get(..first).unwrap();
-        let first = String::from_utf8_lossy(first);
+        let first = String::from_utf8_lossy(&data[..line_end]);
         let pairs = rayon::join(
             || Self::_is_request(&first),
             || Self::_is_response(&first),
         );
-
-        let (kind, version) = pairs.0.or_else(|| pairs.1)?;
-
-        let headers_end = if let Some(end) = &ctx.last {
-            *end
-        } else {
-            data
-                .windows(LINE2.len())
-                .position(|e| e == LINE2.as_bytes())?
+        let (kind, version) = match pairs.0.or(pairs.1) {
+            Some(start) => start,
+            None => return Parse::Invalid,
         };
-        let payload_start = headers_end + LINE2.len();
-        let only_payload = ctx.last.is_some();
-        ctx.last = Some(headers_end);
-        if only_payload {
-            ctx.payload.borrow_mut().extend(&tcp.data);
-        } else {
-            let payload = data
-                .get(payload_start..).unwrap();
-            ctx.payload.borrow_mut().extend(payload);
+        // the HTTP/2 connection preface looks like a request
+        if version.0 >= 2 {
+            return Parse::Invalid;
+        }
+        let headers_end = match find(data, LINE2) {
+            Some(end) => end,
+            None => return Parse::Incomplete,
         };
-        let http = HTTP {
+        let headers = data.get(line_end + LINE.len()..headers_end)
+            .map_or(vec![], Self::_parse_headers);
+        let start = headers_end + LINE2.len();
+        let rest = &data[start..];
+        let mut http = HTTP {
             kind,
             version,
-            headers: default(),
-            payload: ctx.payload.clone(),
-            is_finalized: tcp.is_tail_of_sequence(),
+            headers,
+            body: vec![],
+            body_len: 0,
+            request: None,
+            is_finalized: true,
+            truncated: false,
         };
-        Some(http)
+        let (body, used) = match http.framing(request) {
+            Framing::Empty => (vec![], 0),
+            Framing::Length(len) if len <= rest.len() => (rest[..len].to_vec(), len),
+            Framing::Chunked => match dechunk(rest) {
+                Some(Some(chunks)) => chunks,
+                Some(None) if !closed => return Parse::Incomplete,
+                Some(None) => {
+                    http.is_finalized = false;
+                    (rest.to_vec(), rest.len())
+                }
+                None => return Parse::Invalid,
+            },
+            Framing::Close if closed => (rest.to_vec(), rest.len()),
+            Framing::Length(_) if closed => {
+                http.is_finalized = false;
+                (rest.to_vec(), rest.len())
+            }
+            Framing::Length(_) | Framing::Close => return Parse::Incomplete,
+        };
+        http.body_len = used;
+        (http.body, http.truncated) = http.decode(body, cap);
+        Parse::Done(Box::new(http), start + used)
+    }
+
+    fn framing(&self, request: Option<&HTTPRequest>) -> Framing {
+        if let HTTPKind::Res { status, .. } = self.kind {
+            let head = request.is_some_and(|r| r.method == "HEAD");
+            if head || (100..200).contains(&status) || status == 204 || status == 304 {
+                return Framing::Empty;
+            }
+        }
+        let chunked = self.header("Transfer-Encoding")
+            .is_some_and(|te| te.to_ascii_lowercase().contains("chunked"));
+        let length = self.header("Content-Length")
+            .and_then(|len| len.trim().parse().ok());
+        match (&self.kind, chunked, length) {
+            (_, true, _) => Framing::Chunked,
+            (_, false, Some(len)) => Framing::Length(len),
+            (HTTPKind::Req { .. }, false, None) => Framing::Empty,
+            (HTTPKind::Res { .. }, false, None) => Framing::Close,
+        }
+    }
+
+    /// Undo `Content-Encoding`, the body is kept as is if it fails. Decoded
+    /// bodies stop at `cap` bytes, returns whether it cut them
+    fn decode(&self, body: Vec<u8>, cap: Option<usize>) -> (Vec<u8>, bool) {
+        let encoding = match self.header("Content-Encoding") {
+            Some(encoding) => encoding.trim().to_ascii_lowercase(),
+            None => return (body, false),
+        };
+        // one byte past the cap tells a cut body from one that fits
+        let limit = cap.map_or(u64::MAX, |cap| cap as u64 + 1);
+        let mut decoded = vec![];
+        let res = match encoding.as_str() {
+            "gzip" | "x-gzip" => GzDecoder::new(body.as_slice()).take(limit).read_to_end(&mut decoded),
+            // zlib wrapped as specified, raw deflate as sent by some servers
+            "deflate" => ZlibDecoder::new(body.as_slice()).take(limit).read_to_end(&mut decoded)
+                .or_else(|_| {
+                    decoded.clear();
+                    DeflateDecoder::new(body.as_slice()).take(limit).read_to_end(&mut decoded)
+                }),
+            _ => return (body, false),
+        };
+        if res.is_err() {
+            return (body, false);
+        }
+        let truncated = cap.is_some_and(|cap| decoded.len() > cap);
+        if let Some(cap) = cap {
+            decoded.truncate(cap);
+        }
+        (decoded, truncated)
+    }
+
+    /// Value of the first header named `name`, case insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// `101 Switching Protocols` to cleartext HTTP/2
+    pub fn is_h2c_upgrade(&self) -> bool {
+        matches!(self.kind, HTTPKind::Res { status: 101, .. })
+            && self.header("Upgrade").is_some_and(|u| u.eq_ignore_ascii_case("h2c"))
+    }
+
+    fn _parse_headers(data: &[u8]) -> Vec<(String, String)> {
+        String::from_utf8_lossy(data)
+            .split(LINE)
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect()
     }
 
     fn _is_request(s: &str) -> Option<(HTTPKind, (u8, u8))> {
-        REQ_REGEX.captures(&s).map(|cap| {
+        REQ_REGEX.captures(s).map(|cap| {
             let major = cap["major"].parse::<u8>().unwrap();
             let minor = cap["minor"].parse::<u8>().unwrap();
             (
@@ -138,7 +342,7 @@ impl HTTP {
     }
 
     fn _is_response(s: &str) -> Option<(HTTPKind, (u8, u8))> {
-        RES_REGEX.captures(&s).map(|cap| {
+        RES_REGEX.captures(s).map(|cap| {
             let major = cap["major"].parse::<u8>().unwrap();
             let minor = cap["minor"].parse::<u8>().unwrap();
             let status = cap["status"].parse::<u16>().unwrap();
@@ -153,10 +357,7 @@ impl HTTP {
     }
 
     pub fn display(&self) -> String {
-        let content_type = self.headers.iter()
-            .find(|&r| r.starts_with("Content-Type"))
-            .map_or(UNKNOWN_CONTENT_TYPE.to_string(), |t| t.clone());
-        let content_type = content_type.get((content_type.find(":").unwrap() + 2)..).unwrap();
+        let content_type = self.header("Content-Type").unwrap_or(UNKNOWN_CONTENT_TYPE);
         match &self.kind {
             HTTPKind::Req { method, uri } => {
                 format!("{} {} HTTP/{}.{} {}", method, uri, self.version.0, self.version.1, content_type)
@@ -168,4 +369,189 @@ impl HTTP {
     }
 }
 
-const UNKNOWN_CONTENT_TYPE: &'static str = "Content-Type: Unknown";
+impl HTTPRequest {
+    /// Value of the first header named `name`, case insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Body of a chunked message and bytes it takes, `Some(None)` while
+/// incomplete and `None` if malformed
+fn dechunk(data: &[u8]) -> Option<Option<(Vec<u8>, usize)>> {
+    let mut body = vec![];
+    let mut at = 0;
+    loop {
+        let line_end = match find(&data[at..], LINE) {
+            Some(end) => at + end,
+            None => return Some(None),
+        };
+        let size = std::str::from_utf8(&data[at..line_end]).ok()?;
+        // chunk extensions follow a `;`
+        let size = size.split(';').next()?.trim();
+        let size = usize::from_str_radix(size, 16).ok()?;
+        at = line_end + LINE.len();
+        if size == 0 {
+            // optional trailers, then an empty line
+            let rest = &data[at..];
+            return if rest.starts_with(LINE.as_bytes()) {
+                Some(Some((body, at + LINE.len())))
+            } else {
+                Some(find(rest, LINE2).map(|end| (body, at + end + LINE2.len())))
+            };
+        }
+        // sizes past the address space are malformed rather than incomplete
+        let end = at.checked_add(size)?.checked_add(LINE.len())?;
+        match data.get(at..end) {
+            Some(chunk) if chunk.ends_with(LINE.as_bytes()) => body.extend(&chunk[..size]),
+            Some(_) => return None,
+            None => return Some(None),
+        }
+        at = end;
+    }
+}
+
+const UNKNOWN_CONTENT_TYPE: &str = "Unknown";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(data: &[u8], closed: bool) -> Option<(HTTP, usize)> {
+        match HTTP::parse(data, None, closed, None) {
+            Parse::Done(http, used) => Some((*http, used)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn chunks() {
+        let data = b"4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\n\r\nnext";
+        assert_eq!(dechunk(data), Some(Some((b"Wikipedia".to_vec(), data.len() - 4))));
+    }
+
+    #[test]
+    fn chunk_trailers() {
+        let data = b"3\r\nabc\r\n0\r\nExpires: never\r\n\r\n";
+        assert_eq!(dechunk(data), Some(Some((b"abc".to_vec(), data.len()))));
+    }
+
+    #[test]
+    fn incomplete_chunks() {
+        let data = b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n";
+        for end in 0..data.len() {
+            assert_eq!(dechunk(&data[..end]), Some(None), "{}", end);
+        }
+    }
+
+    #[test]
+    fn malformed_chunks() {
+        assert_eq!(dechunk(b"x\r\nabc\r\n"), None);
+        assert_eq!(dechunk(b"\r\n"), None);
+        // chunk data longer than its size
+        assert_eq!(dechunk(b"2\r\nabc\r\n0\r\n\r\n"), None);
+        assert_eq!(dechunk(b"\xff\r\n"), None);
+    }
+
+    #[test]
+    fn oversized_chunk() {
+        assert_eq!(dechunk(b"ffffffffffffffff\r\nabc\r\n"), None);
+        assert_eq!(dechunk(b"fffffffffffffffe\r\nabc\r\n"), None);
+        // too long for usize
+        assert_eq!(dechunk(b"10000000000000000\r\nabc\r\n"), None);
+        // fits the address space, waits for more data
+        assert_eq!(dechunk(b"ffffffff\r\nabc"), Some(None));
+    }
+
+    #[test]
+    fn request() {
+        let data = b"POST /form HTTP/1.1\r\nHost: x\r\nContent-Length: 3\r\n\r\nabcGET";
+        let (http, used) = parsed(data, false).unwrap();
+        assert_eq!(used, data.len() - 3);
+        assert!(matches!(&http.kind, HTTPKind::Req { method, uri } if method == "POST" && uri == "/form"));
+        assert_eq!(http.version, (1, 1));
+        assert_eq!(http.header("host"), Some("x"));
+        assert_eq!(http.body, b"abc");
+    }
+
+    #[test]
+    fn chunked_response() {
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+        let (http, used) = parsed(data, false).unwrap();
+        assert_eq!(used, data.len());
+        assert!(matches!(http.kind, HTTPKind::Res { status: 200, .. }));
+        assert_eq!(http.body, b"abc");
+        assert!(http.is_finalized);
+    }
+
+    #[test]
+    fn truncated_message() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nabc";
+        for end in 0..data.len() {
+            assert!(matches!(HTTP::parse(&data[..end], None, false, None), Parse::Incomplete), "{}", end);
+        }
+        // the connection closed before the announced length
+        let (http, used) = parsed(data, true).unwrap();
+        assert_eq!(used, data.len());
+        assert_eq!(http.body, b"abc");
+        assert!(!http.is_finalized);
+    }
+
+    #[test]
+    fn compressed_body_cap() {
+        use std::io::Write;
+        use flate2::{Compression, write::GzEncoder};
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&[0; 1 << 20]).unwrap();
+        let body = encoder.finish().unwrap();
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            body.len(),
+        );
+        let data = [head.as_bytes(), &body].concat();
+        let caps = [(None, 1 << 20, false), (Some(1 << 20), 1 << 20, false), (Some(1000), 1000, true)];
+        for (cap, len, truncated) in caps {
+            let Parse::Done(http, used) = HTTP::parse(&data, None, false, cap) else { panic!() };
+            assert_eq!(used, data.len());
+            assert_eq!((http.body.len(), http.truncated), (len, truncated));
+            assert_eq!(http.body_len, body.len());
+        }
+    }
+
+    #[test]
+    fn identification() {
+        let mut sequence = TCPSequence::default();
+        let mut pending = HTTPPending::default();
+        let flow = Flow { key: "client", reverse: "server", ts: 0.0 };
+        let mut tcp = TCP::parse(&[0xc3, 0x50, 0, 80, 0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0])
+            .unwrap();
+        let segments: [(&[u8], _); 5] = [
+            (b"", None),
+            (b"GET / HT", None),
+            (b"TP/1.1\r\nHost: x", None),
+            (b"\r\n\r\n", Some(1)),
+            (b"", Some(0)),
+        ];
+        for (data, messages) in segments {
+            tcp.stream = data.to_vec();
+            let http = HTTP::try_make(&mut sequence, &tcp, &mut pending, &flow);
+            assert_eq!(http.map(|messages| messages.len()), messages, "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn invalid_start_line() {
+        assert!(matches!(HTTP::parse(b"\x16\x03\x01\x00\r\n", None, false, None), Parse::Invalid));
+        assert!(matches!(HTTP::parse(b"HELLO\r\n\r\n", None, false, None), Parse::Invalid));
+        assert!(matches!(HTTP::parse(crate::http2::PREFACE, None, false, None), Parse::Invalid));
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        assert!(matches!(HTTP::parse(data, None, false, None), Parse::Invalid));
+    }
+}
@@ -0,0 +1,446 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use derivative::Derivative;
+
+use crate::*;
+use crate::hpack::HPACKDecoder;
+
+/// Client connection preface (RFC 7540 3.5)
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+pub const DATA: u8 = 0;
+pub const HEADERS: u8 = 1;
+pub const PRIORITY: u8 = 2;
+pub const RST_STREAM: u8 = 3;
+pub const SETTINGS: u8 = 4;
+pub const PUSH_PROMISE: u8 = 5;
+pub const PING: u8 = 6;
+pub const GOAWAY: u8 = 7;
+pub const WINDOW_UPDATE: u8 = 8;
+pub const CONTINUATION: u8 = 9;
+
+pub const END_STREAM: u8 = 0x1;
+pub const ACK: u8 = 0x1;
+pub const END_HEADERS: u8 = 0x4;
+pub const PADDED: u8 = 0x8;
+pub const PRIORITY_FLAG: u8 = 0x20;
+
+pub const SETTINGS_HEADER_TABLE_SIZE: u16 = 0x1;
+
+#[derive(Debug, Clone, Default)]
+pub enum HTTP2Payload {
+    Data {
+        len: usize,
+    },
+    /// Decoded header block, carried by the frame that completes it
+    Headers {
+        headers: Vec<(String, String)>,
+    },
+    PushPromise {
+        promised_stream_id: u32,
+        headers: Vec<(String, String)>,
+    },
+    /// Part of a header block continued by later frames
+    Fragment,
+    /// Frame larger than the stream cap, its payload is skipped
+    Skipped {
+        len: usize,
+    },
+    Priority,
+    RstStream {
+        error_code: u32,
+    },
+    Settings {
+        settings: Vec<(u16, u32)>,
+    },
+    Ping {
+        data: [u8; 8],
+    },
+    GoAway {
+        last_stream_id: u32,
+        error_code: u32,
+        debug: String,
+    },
+    WindowUpdate {
+        increment: u32,
+    },
+    /// Unknown type or malformed payload
+    #[default]
+    Unknown,
+}
+
+/// Request and response of a stream, as known after a frame
+#[derive(Debug, Clone, Default)]
+pub struct HTTP2Stream {
+    pub id: u32,
+    pub method: Option<String>,
+    pub scheme: Option<String>,
+    pub authority: Option<String>,
+    pub path: Option<String>,
+    pub status: Option<u16>,
+    /// Timestamp of the request headers
+    pub ts: f64,
+    pub request_bytes: usize,
+    pub response_bytes: usize,
+    pub client_closed: bool,
+    pub server_closed: bool,
+    pub reset: bool,
+}
+
+/// One HTTP/2 frame, a segment may carry several of them
+#[derive(Derivative, Layer, Header)]
+#[derivative(Debug)]
+#[layer(abbrev = "http2")]
+pub struct HTTP2 {
+    #[layer(offset = 0)]
+    #[header(bits = 24)]
+    pub length: u32,
+    #[layer(offset = 3)]
+    pub kind: u8,
+    #[layer(offset = 4, format = "hex")]
+    pub flags: u8,
+    #[layer(offset = 5)]
+    #[header(pad = 1, bits = 31)]
+    pub stream_id: u32,
+    #[layer(format = "debug")]
+    #[header(skip)]
+    pub payload: HTTP2Payload,
+    #[layer(format = "debug")]
+    #[header(skip)]
+    pub stream: Option<HTTP2Stream>,
+}
+
+/// Parsing state of one direction of a connection
+#[derive(Default, Debug)]
+pub struct HTTP2Context {
+    /// Stream bytes not consumed by a complete frame yet
+    pub buffer: Vec<u8>,
+    pub decoder: HPACKDecoder,
+    /// Header block waiting for CONTINUATION frames: frame type, promised
+    /// stream and fragments
+    continued: Option<(u8, u32, Vec<u8>)>,
+    /// The preface or the first SETTINGS frame was seen
+    started: bool,
+    /// Bytes left of a skipped frame
+    skip: usize,
+}
+
+impl HTTP2Context {
+    /// Context of a stream upgraded from HTTP/1.1 with `buffer` left over
+    pub fn upgraded(buffer: Vec<u8>) -> Self {
+        Self { buffer, ..default() }
+    }
+
+    /// Bytes buffered, frames and header block fragments
+    pub fn size(&self) -> usize {
+        self.buffer.len() + self.continued.as_ref().map_or(0, |(_, _, block)| block.len())
+    }
+}
+
+/// Streams of the HTTP/2 connections, keyed by the client to server
+/// TCP stream
+#[derive(Default, Debug)]
+pub struct HTTP2Connections {
+    connections: HashMap<String, Connection>,
+    last_sweep: f64,
+}
+
+#[derive(Debug)]
+struct Connection {
+    streams: HashMap<u32, HTTP2Stream>,
+    last_seen: f64,
+    /// HPACK table bound of the server and of the client direction, the
+    /// `SETTINGS_HEADER_TABLE_SIZE` sent by the opposite peer
+    table_sizes: [usize; 2],
+}
+
+impl HTTP2Connections {
+    const SWEEP_PERIOD: f64 = 1.0;
+
+    pub fn open(&mut self, client: &str, ts: f64) {
+        self.connections.entry(client.to_string()).or_insert_with(|| Connection {
+            streams: default(),
+            last_seen: ts,
+            table_sizes: [HPACKDecoder::DEFAULT_SIZE; 2],
+        });
+    }
+
+    pub fn contains(&self, client: &str) -> bool {
+        self.connections.contains_key(client)
+    }
+
+    pub fn close(&mut self, client: &str) {
+        self.connections.remove(client);
+    }
+
+    pub fn streams(&self, client: &str) -> Option<&HashMap<u32, HTTP2Stream>> {
+        self.connections.get(client).map(|connection| &connection.streams)
+    }
+
+    /// Largest HPACK table the receiver of a direction allows
+    fn table_size(&self, client: &str, from_client: bool) -> usize {
+        self.connections.get(client)
+            .map_or(HPACKDecoder::DEFAULT_SIZE, |connection| connection.table_sizes[from_client as usize])
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// Forget connections idle for `timeout` seconds before `ts`
+    pub fn expire(&mut self, ts: f64, timeout: Option<f64>) {
+        let timeout = match timeout {
+            Some(timeout) if ts - self.last_sweep >= Self::SWEEP_PERIOD => timeout,
+            _ => return,
+        };
+        self.last_sweep = ts;
+        self.connections.retain(|_, connection| ts - connection.last_seen <= timeout);
+    }
+
+    /// Apply a frame to its stream, returns the stream state after it
+    fn apply(&mut self, client: &str, from_client: bool, frame: &HTTP2, ts: f64) -> Option<HTTP2Stream> {
+        let connection = self.connections.get_mut(client)?;
+        connection.last_seen = ts;
+        if let HTTP2Payload::Settings { settings } = &frame.payload {
+            let size = settings.iter().rev().find(|&&(id, _)| id == SETTINGS_HEADER_TABLE_SIZE);
+            if let Some(&(_, size)) = size {
+                // bounds the table of the frames the sender receives
+                connection.table_sizes[!from_client as usize] = size as usize;
+            }
+        }
+        if frame.stream_id == 0 {
+            return None;
+        }
+        let streams = &mut connection.streams;
+        let stream = streams.entry(frame.stream_id)
+            .or_insert_with(|| HTTP2Stream { id: frame.stream_id, ts, ..default() });
+        match &frame.payload {
+            HTTP2Payload::Headers { headers } => {
+                let pseudo = |name: &str| headers.iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, v)| v.clone());
+                if from_client && stream.method.is_none() {
+                    stream.method = pseudo(":method");
+                    stream.scheme = pseudo(":scheme");
+                    stream.authority = pseudo(":authority");
+                    stream.path = pseudo(":path");
+                    stream.ts = ts;
+                } else if !from_client {
+                    // informational 1xx responses are followed by the final one
+                    stream.status = pseudo(":status").and_then(|s| s.parse().ok()).or(stream.status);
+                }
+            }
+            HTTP2Payload::Data { len } | HTTP2Payload::Skipped { len } if frame.kind == DATA => {
+                if from_client { stream.request_bytes += len } else { stream.response_bytes += len }
+            }
+            HTTP2Payload::RstStream { .. } => stream.reset = true,
+            _ => {}
+        }
+        let end_stream = matches!(frame.kind, DATA | HEADERS) && frame.flags & END_STREAM != 0;
+        if end_stream && from_client {
+            stream.client_closed = true;
+        } else if end_stream {
+            stream.server_closed = true;
+        }
+        let state = stream.clone();
+        if state.reset || (state.client_closed && state.server_closed) {
+            streams.remove(&frame.stream_id);
+        }
+        Some(state)
+    }
+}
+
+impl HTTP2 {
+    /// Frames completed by the segment, `None` if the stream is not HTTP/2.
+    /// A client stream starts with the preface, a server stream is accepted
+    /// once the client one was recognized.
+    pub fn try_make(
+        sequence: &mut TCPSequence,
+        tcp: &TCP,
+        connections: &mut HTTP2Connections,
+        flow: &Flow,
+    ) -> Option<Vec<HTTP2>> {
+        let cap = sequence.cap;
+        let ctx = sequence.http2.get_or_insert_with(default);
+        // the buffer is empty while a frame is skipped
+        let skipped = ctx.skip.min(tcp.stream.len());
+        ctx.skip -= skipped;
+        sequence.truncated |= extend_capped(&mut ctx.buffer, &tcp.stream[skipped..], cap);
+        if !ctx.started {
+            let data = ctx.buffer.as_slice();
+            if data.starts_with(PREFACE) {
+                ctx.buffer.drain(..PREFACE.len());
+                connections.open(flow.key, flow.ts);
+            } else if PREFACE.starts_with(data) {
+                // wait for the rest of the preface
+                return None;
+            } else if !connections.contains(flow.reverse) || data.get(3) != Some(&SETTINGS) {
+                sequence.http2 = None;
+                return None;
+            }
+            ctx.started = true;
+        }
+        let (client, from_client) = if connections.contains(flow.key) {
+            (flow.key, true)
+        } else {
+            (flow.reverse, false)
+        };
+        let mut frames = vec![];
+        while ctx.buffer.len() >= HTTP2::SIZE {
+            let mut frame = HTTP2::parse(&ctx.buffer)?;
+            let end = HTTP2::SIZE + frame.length as usize;
+            if cap.is_some_and(|cap| end > cap) {
+                // never fits the buffer, skip its bytes to keep the framing
+                let available = ctx.buffer.len().min(end);
+                ctx.buffer.drain(..available);
+                ctx.skip = end - available;
+                frame.payload = HTTP2Payload::Skipped { len: frame.length as usize };
+                frame.stream = connections.apply(client, from_client, &frame, flow.ts);
+                frames.push(frame);
+                continue;
+            }
+            if ctx.buffer.len() < end {
+                break;
+            }
+            let data = ctx.buffer.drain(..end).collect::<Vec<_>>();
+            ctx.decoder.limit = connections.table_size(client, from_client);
+            frame.payload = frame.decode(&data[HTTP2::SIZE..], ctx, cap);
+            frame.stream = connections.apply(client, from_client, &frame, flow.ts);
+            frames.push(frame);
+        }
+        if tcp.flags.fin && from_client {
+            connections.close(client);
+        }
+        Some(frames)
+    }
+
+    fn decode(&self, payload: &[u8], ctx: &mut HTTP2Context, cap: Option<usize>) -> HTTP2Payload {
+        self.decode_payload(payload, ctx, cap).unwrap_or_default()
+    }
+
+    fn decode_payload(&self, payload: &[u8], ctx: &mut HTTP2Context, cap: Option<usize>) -> Option<HTTP2Payload> {
+        let payload = match self.kind {
+            DATA | HEADERS | PUSH_PROMISE => self.unpad(payload)?,
+            _ => payload,
+        };
+        Some(match self.kind {
+            DATA => HTTP2Payload::Data { len: payload.len() },
+            HEADERS => {
+                let fragment = if self.flags & PRIORITY_FLAG != 0 { payload.get(5..)? } else { payload };
+                self.header_block(HEADERS, 0, fragment, ctx, cap)?
+            }
+            PUSH_PROMISE => {
+                let promised = NetworkEndian::read_u32(payload.get(..4)?) & 0x7fff_ffff;
+                self.header_block(PUSH_PROMISE, promised, payload.get(4..)?, ctx, cap)?
+            }
+            CONTINUATION => {
+                let (kind, promised, mut block) = ctx.continued.take()?;
+                block.extend(payload);
+                self.header_block(kind, promised, &block, ctx, cap)?
+            }
+            PRIORITY => HTTP2Payload::Priority,
+            RST_STREAM => HTTP2Payload::RstStream {
+                error_code: NetworkEndian::read_u32(payload.get(..4)?),
+            },
+            SETTINGS => HTTP2Payload::Settings {
+                settings: payload.chunks_exact(6)
+                    .map(|s| (NetworkEndian::read_u16(&s[..2]), NetworkEndian::read_u32(&s[2..])))
+                    .collect(),
+            },
+            PING => HTTP2Payload::Ping { data: payload.get(..8)?.try_into().unwrap() },
+            GOAWAY => HTTP2Payload::GoAway {
+                last_stream_id: NetworkEndian::read_u32(payload.get(..4)?) & 0x7fff_ffff,
+                error_code: NetworkEndian::read_u32(payload.get(4..8)?),
+                debug: String::from_utf8_lossy(&payload[8..]).into_owned(),
+            },
+            WINDOW_UPDATE => HTTP2Payload::WindowUpdate {
+                increment: NetworkEndian::read_u32(payload.get(..4)?) & 0x7fff_ffff,
+            },
+            _ => HTTP2Payload::Unknown,
+        })
+    }
+
+    /// Payload without the pad length and the padding
+    fn unpad<'a>(&self, payload: &'a [u8]) -> Option<&'a [u8]> {
+        if self.flags & PADDED == 0 {
+            return Some(payload);
+        }
+        let pad = *payload.first()? as usize;
+        payload.get(1..payload.len().checked_sub(pad)?)
+    }
+
+    /// Decode a header block once complete, HPACK state is shared by the
+    /// whole direction so every block must be decoded in order. A block
+    /// larger than `cap` fails
+    fn header_block(
+        &self,
+        kind: u8,
+        promised: u32,
+        block: &[u8],
+        ctx: &mut HTTP2Context,
+        cap: Option<usize>,
+    ) -> Option<HTTP2Payload> {
+        if cap.is_some_and(|cap| block.len() > cap) {
+            return None;
+        }
+        if self.flags & END_HEADERS == 0 {
+            ctx.continued = Some((kind, promised, block.to_vec()));
+            return Some(HTTP2Payload::Fragment);
+        }
+        let headers = ctx.decoder.decode(block)?;
+        Some(match kind {
+            PUSH_PROMISE => HTTP2Payload::PushPromise { promised_stream_id: promised, headers },
+            _ => HTTP2Payload::Headers { headers },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(kind: u8, flags: u8, payload: &[u8]) -> (HTTP2, Vec<u8>) {
+        let len = (payload.len() as u32).to_be_bytes();
+        (HTTP2::parse(&[len[1], len[2], len[3], kind, flags, 0, 0, 0, 1]).unwrap(), payload.to_vec())
+    }
+
+    fn decode(frames: &[(HTTP2, Vec<u8>)], ctx: &mut HTTP2Context, cap: Option<usize>) -> Vec<HTTP2Payload> {
+        frames.iter().map(|(frame, payload)| frame.decode(payload, ctx, cap)).collect()
+    }
+
+    #[test]
+    fn continuation() {
+        // :method GET, indexed
+        let frames = [
+            frame(HEADERS, 0, &[0x82; 6]),
+            frame(CONTINUATION, 0, &[0x82; 6]),
+            frame(CONTINUATION, END_HEADERS, &[0x82]),
+            frame(HEADERS, END_HEADERS, &[0x82]),
+        ];
+        let payloads = decode(&frames, &mut default(), None);
+        assert!(matches!(&payloads[2], HTTP2Payload::Headers { headers } if headers.len() == 13));
+        // the block outgrows the cap, the next one is decoded again
+        let payloads = decode(&frames, &mut default(), Some(10));
+        assert!(matches!(payloads[0], HTTP2Payload::Fragment));
+        assert!(matches!(payloads[1], HTTP2Payload::Unknown));
+        assert!(matches!(payloads[2], HTTP2Payload::Unknown));
+        assert!(matches!(&payloads[3], HTTP2Payload::Headers { headers } if headers.len() == 1));
+    }
+
+    #[test]
+    fn table_size_settings() {
+        let mut connections = HTTP2Connections::default();
+        connections.open("client", 0.0);
+        assert_eq!(connections.table_size("client", true), HPACKDecoder::DEFAULT_SIZE);
+        let (mut settings, _) = frame(SETTINGS, 0, &[]);
+        settings.stream_id = 0;
+        settings.payload = HTTP2Payload::Settings { settings: vec![(SETTINGS_HEADER_TABLE_SIZE, 256)] };
+        // sent by the server, bounds what the client sends
+        connections.apply("client", false, &settings, 0.0);
+        assert_eq!(connections.table_size("client", true), 256);
+        assert_eq!(connections.table_size("client", false), HPACKDecoder::DEFAULT_SIZE);
+    }
+}
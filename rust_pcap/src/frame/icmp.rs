@@ -4,10 +4,16 @@ use crate::*;
 
 #[derive(Debug, Layer)]
 pub struct ICMP {
+    #[layer(offset = 0)]
     pub kind: u8,
+    #[layer(offset = 1)]
     pub code: u8,
+    #[layer(offset = 2, format = "hex")]
     pub checksum: u16,
+    #[layer(offset = 4, format = "debug")]
     pub data: ICMPData,
+    /// [`QuotedIP`] of error messages
+    layers: Layers,
 }
 
 #[derive(Debug)]
@@ -21,31 +27,53 @@ pub enum ICMPData {
     Reserved,
     DstUnreachable {
         kind: DSTUnreachable,
-        not_use: u32,
-        header_ip: u64,
+        /// Next-hop MTU of [`DSTUnreachable::FragRequired`] (RFC 1191), 0
+        /// when unset
+        mtu: u16,
     },
     SrcContainment,
     Forward {
         kind: Forward,
-        addr: u32,
-        header_ip: u64,
+        /// Gateway to use instead
+        addr: [u8; 4],
     },
     AltAddrNode,
-    // TODO: IRDP
-    RouterAdvertisement,
-    // TODO: IRDP
+    RouterAdvertisement {
+        /// Seconds
+        lifetime: u16,
+        addresses: Vec<RouterAddress>,
+    },
     RouterRequest,
     TTLExpire {
         kind: TTLExpire,
-        addr: u32,
-        header_ip: u64,
     },
     InvalidParams(InvalidParams),
     ReqTime(ReqTime),
     ResTime(ReqTime),
+    MaskReq(AddressMask),
+    MaskRes(AddressMask),
+    /// Probe of an interface through a proxy node (RFC 8335)
+    ExtendedEchoRequest {
+        id: u16,
+        num: u8,
+        /// Interface of the proxy node itself
+        local: bool,
+        interface: Option<InterfaceId>,
+    },
+    /// Error in [`ICMP::code`]
+    ExtendedEchoReply {
+        id: u16,
+        num: u8,
+        /// Neighbor reachability state (RFC 8335 3)
+        state: u8,
+        active: bool,
+        ipv4: bool,
+        ipv6: bool,
+    },
     OutDate,
     Photuris(Photuris),
     Experimental,
+    /// Truncated message
     Unknown,
 }
 
@@ -73,6 +101,7 @@ pub enum DSTUnreachable {
     CommunicationAdminForbidden,
     NodeOrderViolation,
     PreferenceOrderPruning,
+    Unknown(u8),
 }
 
 #[derive(Debug)]
@@ -81,6 +110,7 @@ pub enum Forward {
     PktNode,
     ToS,
     PktNodeTos,
+    Unknown(u8),
 }
 
 #[derive(Debug)]
@@ -92,39 +122,104 @@ pub enum TTLExpire {
 
 #[derive(Debug)]
 pub enum InvalidParams {
+    /// Offset of the faulty byte in the quoted header
     PtrError {
         ptr: u8,
-        not_use: u32,
-        header_ip: u64,
-    },
-    MissingOptions {
-        not_use: u32,
-        header_ip: u64,
-    },
-    Length {
-        not_use: u32,
-        header_ip: u64,
     },
+    MissingOptions,
+    Length,
+    Unknown(u8),
+}
+
+/// Router address of an IRDP advertisement (RFC 1256)
+#[derive(Debug)]
+pub struct RouterAddress {
+    pub address: [u8; 4],
+    /// Higher is preferred, `i32::MIN` for an address not to use as default
+    pub preference: i32,
 }
 
 #[derive(Debug)]
 pub struct ReqTime {
-    id: u16,
-    num: u16,
-    begin_time: u32,
-    recv_time: u32,
-    send_time: u32,
+    pub id: u16,
+    pub num: u16,
+    /// Milliseconds since midnight UT
+    pub begin_time: u32,
+    pub recv_time: u32,
+    pub send_time: u32,
 }
 
 impl ReqTime {
-    pub fn from(data: &[u8]) -> ReqTime {
-        ReqTime {
-            id: NetworkEndian::read_u16(data.get(..2).unwrap()),
-            num: NetworkEndian::read_u16(data.get(2..4).unwrap()),
-            begin_time: NetworkEndian::read_u32(data.get(4..8).unwrap()),
-            recv_time: NetworkEndian::read_u32(data.get(8..12).unwrap()),
-            send_time: NetworkEndian::read_u32(data.get(12..16).unwrap()),
+    pub fn parse(data: &[u8]) -> Option<ReqTime> {
+        let data = data.get(..16)?;
+        Some(ReqTime {
+            id: NetworkEndian::read_u16(&data[..2]),
+            num: NetworkEndian::read_u16(&data[2..4]),
+            begin_time: NetworkEndian::read_u32(&data[4..8]),
+            recv_time: NetworkEndian::read_u32(&data[8..12]),
+            send_time: NetworkEndian::read_u32(&data[12..16]),
+        })
+    }
+}
+
+/// Address mask request or reply (RFC 950)
+#[derive(Debug)]
+pub struct AddressMask {
+    pub id: u16,
+    pub num: u16,
+    pub mask: [u8; 4],
+}
+
+impl AddressMask {
+    pub fn parse(data: &[u8]) -> Option<AddressMask> {
+        let data = data.get(..8)?;
+        Some(AddressMask {
+            id: NetworkEndian::read_u16(&data[..2]),
+            num: NetworkEndian::read_u16(&data[2..4]),
+            mask: data[4..8].try_into().unwrap(),
+        })
+    }
+}
+
+/// Interface identification object of an extended echo request (RFC 8335 2.1)
+#[derive(Debug)]
+pub enum InterfaceId {
+    Name(String),
+    Index(u32),
+    Address {
+        /// Address family, 1 for IPv4 and 2 for IPv6
+        afi: u16,
+        address: Vec<u8>,
+    },
+    Other {
+        c_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl InterfaceId {
+    const CLASS: u8 = 3;
+
+    /// Reads the object from an ICMP extension structure (RFC 4884 7)
+    fn parse(data: &[u8]) -> Option<InterfaceId> {
+        let mut objects = data.get(4..)?;
+        while objects.len() >= 4 {
+            let len = NetworkEndian::read_u16(&objects[..2]) as usize;
+            let payload = objects.get(4..len)?;
+            if objects[2] == Self::CLASS {
+                return Some(match objects[3] {
+                    1 => InterfaceId::Name(String::from_utf8_lossy(payload).trim_end_matches('\0').to_string()),
+                    2 => InterfaceId::Index(NetworkEndian::read_u32(payload.get(..4)?)),
+                    3 => InterfaceId::Address {
+                        afi: NetworkEndian::read_u16(payload.get(..2)?),
+                        address: payload.get(4..4 + *payload.get(2)? as usize)?.to_vec(),
+                    },
+                    c_type => InterfaceId::Other { c_type, data: payload.to_vec() },
+                });
+            }
+            objects = &objects[len..];
         }
+        None
     }
 }
 
@@ -136,108 +231,378 @@ pub enum Photuris {
     DecodeError,
     NeedValid,
     NeedAuth,
+    Unknown(u8),
+}
+
+/// IPv4 header and first transport bytes of the datagram that caused an
+/// ICMP error (RFC 792)
+#[derive(Debug, Layer)]
+#[layer(abbrev = "quoted_ip")]
+pub struct QuotedIP {
+    #[layer(offset = 2)]
+    pub size: u16,
+    #[layer(offset = 4, format = "hex")]
+    pub id: u16,
+    #[layer(offset = 8)]
+    pub ttl: u8,
+    #[layer(offset = 9)]
+    pub protocol: u8,
+    #[layer(offset = 12)]
+    pub src: [u8; 4],
+    #[layer(offset = 16)]
+    pub dst: [u8; 4],
+    /// TCP and UDP only
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+    /// Up to 8 bytes past the IP header
+    pub transport: Vec<u8>,
+}
+
+impl QuotedIP {
+    pub fn try_make(data: &[u8]) -> Option<QuotedIP> {
+        let ip = IPv4::parse(data)?;
+        if ip.version != 4 { return None; }
+        let transport = data.get((ip.ihl as usize * 4).max(IPv4::SIZE)..).unwrap_or_default();
+        let transport = &transport[..transport.len().min(8)];
+        let port = |at: usize| {
+            [6, 17].contains(&ip.protocol)
+                .then(|| transport.get(at..at + 2).map(NetworkEndian::read_u16))
+                .flatten()
+        };
+        Some(QuotedIP {
+            size: ip.size,
+            id: ip.id,
+            ttl: ip.ttl,
+            protocol: ip.protocol,
+            src: ip.src,
+            dst: ip.dst,
+            src_port: port(0),
+            dst_port: port(2),
+            transport: transport.to_vec(),
+        })
+    }
 }
 
 impl ICMP {
-    pub fn new(data: &[u8]) -> ICMP {
-        let kind = data.get(0).unwrap().clone();
-        let code = data.get(1).unwrap().clone();
-        let checksum = NetworkEndian::read_u16(data.get(2..4).unwrap());
-        let data = match kind {
-            0 | 8 => ICMPData::Echo {
-                id: NetworkEndian::read_u16(data.get(4..6).unwrap()),
-                kind: if kind == 0 { Echo::Response } else { Echo::Request },
-                num: NetworkEndian::read_u16(data.get(6..8).unwrap()),
-                data: data.get(8..).unwrap().to_vec(),
-            },
-            1 | 2 | 7 | 19 | 20..=29 | 42..=252 | 255 => ICMPData::Reserved,
-            3 => {
-                let not_use = NetworkEndian::read_u32(data.get(4..8).unwrap());
-                let header_ip = NetworkEndian::read_u64(data.get(8..16).unwrap());
-                match code {
-                    0 => ICMPData::DstUnreachable { kind: DSTUnreachable::NetUnreachable, not_use, header_ip },
-                    1 => ICMPData::DstUnreachable { kind: DSTUnreachable::NodeUnreachable, not_use, header_ip },
-                    2 => ICMPData::DstUnreachable { kind: DSTUnreachable::ProtocolUnreachable, not_use, header_ip },
-                    3 => ICMPData::DstUnreachable { kind: DSTUnreachable::PortUnreachable, not_use, header_ip },
-                    4 => ICMPData::DstUnreachable { kind: DSTUnreachable::FragRequired, not_use, header_ip },
-                    5 => ICMPData::DstUnreachable { kind: DSTUnreachable::WrongRoad, not_use, header_ip },
-                    6 => ICMPData::DstUnreachable { kind: DSTUnreachable::NetUnknown, not_use, header_ip },
-                    7 => ICMPData::DstUnreachable { kind: DSTUnreachable::NodeUnknown, not_use, header_ip },
-                    8 => ICMPData::DstUnreachable { kind: DSTUnreachable::SRCNodeIsolated, not_use, header_ip },
-                    9 => ICMPData::DstUnreachable { kind: DSTUnreachable::NetAdminForbidden, not_use, header_ip },
-                    10 => ICMPData::DstUnreachable { kind: DSTUnreachable::NodeAdminForbidden, not_use, header_ip },
-                    11 => ICMPData::DstUnreachable { kind: DSTUnreachable::NetUnavailableToS, not_use, header_ip },
-                    12 => ICMPData::DstUnreachable { kind: DSTUnreachable::NodeUnavailableToS, not_use, header_ip },
-                    13 => ICMPData::DstUnreachable { kind: DSTUnreachable::CommunicationAdminForbidden, not_use, header_ip },
-                    14 => ICMPData::DstUnreachable { kind: DSTUnreachable::NodeOrderViolation, not_use, header_ip },
-                    15 => ICMPData::DstUnreachable { kind: DSTUnreachable::PreferenceOrderPruning, not_use, header_ip },
-                    _ => unreachable!()
-                }
-            }
-            4 | 6 | 15 | 16 | 17 | 18 | 30..=39 => ICMPData::OutDate,
-            5 => {
-                let addr = NetworkEndian::read_u32(data.get(4..8).unwrap());
-                let header_ip = NetworkEndian::read_u64(data.get(8..16).unwrap());
-                match code {
-                    0 => ICMPData::Forward { kind: Forward::PktNet, addr, header_ip },
-                    1 => ICMPData::Forward { kind: Forward::PktNode, addr, header_ip },
-                    2 => ICMPData::Forward { kind: Forward::ToS, addr, header_ip },
-                    3 => ICMPData::Forward { kind: Forward::PktNodeTos, addr, header_ip },
-                    _ => unreachable!()
-                }
+    pub const ECHO_REPLY: u8 = 0;
+    pub const DST_UNREACHABLE: u8 = 3;
+    pub const SOURCE_QUENCH: u8 = 4;
+    pub const REDIRECT: u8 = 5;
+    pub const ECHO_REQUEST: u8 = 8;
+    pub const ROUTER_ADVERTISEMENT: u8 = 9;
+    pub const ROUTER_SOLICITATION: u8 = 10;
+    pub const TIME_EXCEEDED: u8 = 11;
+    pub const PARAMETER_PROBLEM: u8 = 12;
+    pub const TIMESTAMP: u8 = 13;
+    pub const TIMESTAMP_REPLY: u8 = 14;
+    pub const MASK_REQUEST: u8 = 17;
+    pub const MASK_REPLY: u8 = 18;
+    pub const PHOTURIS: u8 = 40;
+    pub const EXTENDED_ECHO_REQUEST: u8 = 42;
+    pub const EXTENDED_ECHO_REPLY: u8 = 43;
+
+    pub fn try_make(data: &[u8]) -> Option<ICMP> {
+        let kind = *data.first()?;
+        let code = *data.get(1)?;
+        let checksum = NetworkEndian::read_u16(data.get(2..4)?);
+        let mut layers = Layers::default();
+        if Self::is_error_kind(kind) {
+            if let Some(quoted) = data.get(8..).and_then(QuotedIP::try_make) {
+                layers.insert(quoted);
             }
-            9 => ICMPData::RouterAdvertisement,
-            10 => ICMPData::RouterRequest,
-            11 => {
-                let addr = NetworkEndian::read_u32(data.get(4..8).unwrap());
-                let header_ip = NetworkEndian::read_u64(data.get(8..16).unwrap());
-                match code {
-                    0 => ICMPData::TTLExpire { kind: TTLExpire::Transportation, addr, header_ip },
-                    1 => ICMPData::TTLExpire { kind: TTLExpire::BuildFrag, addr, header_ip },
-                    _ => ICMPData::TTLExpire { kind: TTLExpire::Unknown(code), addr, header_ip },
-                }
+        }
+        Some(ICMP {
+            kind,
+            code,
+            checksum,
+            data: Self::decode(kind, code, data).unwrap_or(ICMPData::Unknown),
+            layers,
+        })
+    }
+
+    fn decode(kind: u8, code: u8, data: &[u8]) -> Option<ICMPData> {
+        let u16_at = |at: usize| data.get(at..at + 2).map(NetworkEndian::read_u16);
+        Some(match kind {
+            Self::ECHO_REPLY | Self::ECHO_REQUEST => ICMPData::Echo {
+                id: u16_at(4)?,
+                kind: if kind == Self::ECHO_REPLY { Echo::Response } else { Echo::Request },
+                num: u16_at(6)?,
+                data: data[8..].to_vec(),
+            },
+            1 | 2 | 7 | 19 | 20..=29 | 44..=252 | 255 => ICMPData::Reserved,
+            Self::DST_UNREACHABLE => ICMPData::DstUnreachable {
+                kind: match code {
+                    0 => DSTUnreachable::NetUnreachable,
+                    1 => DSTUnreachable::NodeUnreachable,
+                    2 => DSTUnreachable::ProtocolUnreachable,
+                    3 => DSTUnreachable::PortUnreachable,
+                    4 => DSTUnreachable::FragRequired,
+                    5 => DSTUnreachable::WrongRoad,
+                    6 => DSTUnreachable::NetUnknown,
+                    7 => DSTUnreachable::NodeUnknown,
+                    8 => DSTUnreachable::SRCNodeIsolated,
+                    9 => DSTUnreachable::NetAdminForbidden,
+                    10 => DSTUnreachable::NodeAdminForbidden,
+                    11 => DSTUnreachable::NetUnavailableToS,
+                    12 => DSTUnreachable::NodeUnavailableToS,
+                    13 => DSTUnreachable::CommunicationAdminForbidden,
+                    14 => DSTUnreachable::NodeOrderViolation,
+                    15 => DSTUnreachable::PreferenceOrderPruning,
+                    _ => DSTUnreachable::Unknown(code),
+                },
+                mtu: if code == 4 { u16_at(6)? } else { 0 },
+            },
+            Self::SOURCE_QUENCH => ICMPData::SrcContainment,
+            6 | 15 | 16 | 30..=39 => ICMPData::OutDate,
+            Self::REDIRECT => ICMPData::Forward {
+                kind: match code {
+                    0 => Forward::PktNet,
+                    1 => Forward::PktNode,
+                    2 => Forward::ToS,
+                    3 => Forward::PktNodeTos,
+                    _ => Forward::Unknown(code),
+                },
+                addr: data.get(4..8)?.try_into().unwrap(),
+            },
+            Self::ROUTER_ADVERTISEMENT => {
+                let count = *data.get(4)? as usize;
+                // in 32-bit words, at least the address and preference
+                let entry_size = (*data.get(5)? as usize).max(2) * 4;
+                let addresses = data.get(8..)?.chunks_exact(entry_size)
+                    .take(count)
+                    .map(|entry| RouterAddress {
+                        address: entry[..4].try_into().unwrap(),
+                        preference: NetworkEndian::read_i32(&entry[4..8]),
+                    })
+                    .collect();
+                ICMPData::RouterAdvertisement { lifetime: u16_at(6)?, addresses }
             }
-            12 => {
-                let not_use = NetworkEndian::read_u32(data.get(4..8).unwrap());
-                let header_ip = NetworkEndian::read_u64(data.get(8..16).unwrap());
-                match code {
-                    0 => ICMPData::InvalidParams(InvalidParams::PtrError {
-                        ptr: data.get(4).unwrap().clone(),
-                        not_use,
-                        header_ip,
-                    }),
-                    1 => ICMPData::InvalidParams(InvalidParams::MissingOptions {
-                        not_use,
-                        header_ip,
-                    }),
-                    2 => ICMPData::InvalidParams(InvalidParams::Length {
-                        not_use,
-                        header_ip,
-                    }),
-                    _ => unreachable!()
+            Self::ROUTER_SOLICITATION => ICMPData::RouterRequest,
+            Self::TIME_EXCEEDED => ICMPData::TTLExpire {
+                kind: match code {
+                    0 => TTLExpire::Transportation,
+                    1 => TTLExpire::BuildFrag,
+                    _ => TTLExpire::Unknown(code),
+                },
+            },
+            Self::PARAMETER_PROBLEM => ICMPData::InvalidParams(match code {
+                0 => InvalidParams::PtrError { ptr: *data.get(4)? },
+                1 => InvalidParams::MissingOptions,
+                2 => InvalidParams::Length,
+                _ => InvalidParams::Unknown(code),
+            }),
+            Self::TIMESTAMP => ICMPData::ReqTime(ReqTime::parse(&data[4..])?),
+            Self::TIMESTAMP_REPLY => ICMPData::ResTime(ReqTime::parse(&data[4..])?),
+            Self::MASK_REQUEST => ICMPData::MaskReq(AddressMask::parse(&data[4..])?),
+            Self::MASK_REPLY => ICMPData::MaskRes(AddressMask::parse(&data[4..])?),
+            Self::PHOTURIS => ICMPData::Photuris(match code {
+                0 => Photuris::Reserved,
+                1 => Photuris::UnknownIndex,
+                2 => Photuris::AuthError,
+                3 => Photuris::DecodeError,
+                4 => Photuris::NeedValid,
+                5 => Photuris::NeedAuth,
+                _ => Photuris::Unknown(code),
+            }),
+            Self::EXTENDED_ECHO_REQUEST => ICMPData::ExtendedEchoRequest {
+                id: u16_at(4)?,
+                num: *data.get(6)?,
+                local: data.get(7)? & 1 != 0,
+                interface: InterfaceId::parse(&data[8..]),
+            },
+            Self::EXTENDED_ECHO_REPLY => {
+                let flags = *data.get(7)?;
+                ICMPData::ExtendedEchoReply {
+                    id: u16_at(4)?,
+                    num: *data.get(6)?,
+                    state: flags >> 5,
+                    active: flags & 0b100 != 0,
+                    ipv4: flags & 0b10 != 0,
+                    ipv6: flags & 1 != 0,
                 }
             }
-            13 => ICMPData::ReqTime(ReqTime::from(data.get(4..).unwrap())),
-            14 => ICMPData::ResTime(ReqTime::from(data.get(4..).unwrap())),
-            40 => {
-                ICMPData::Photuris(match code {
-                    0 => Photuris::Reserved,
-                    1 => Photuris::UnknownIndex,
-                    2 => Photuris::AuthError,
-                    3 => Photuris::DecodeError,
-                    4 => Photuris::NeedValid,
-                    5 => Photuris::NeedAuth,
-                    _ => unreachable!()
-                })
-            }
             41 | 253 | 254 => ICMPData::Experimental,
-            // _ => ICMPData::Unknown
-        };
-        ICMP {
+        })
+    }
+
+    /// Error messages quote the datagram that caused them
+    fn is_error_kind(kind: u8) -> bool {
+        matches!(
             kind,
-            code,
-            checksum,
-            data,
+            Self::DST_UNREACHABLE | Self::SOURCE_QUENCH | Self::REDIRECT
+                | Self::TIME_EXCEEDED | Self::PARAMETER_PROBLEM
+        )
+    }
+
+    pub fn is_error(&self) -> bool {
+        Self::is_error_kind(self.kind)
+    }
+
+    pub fn quoted(&self) -> Option<&QuotedIP> {
+        self.layers.get::<QuotedIP>()
+    }
+}
+
+impl HasLayers for ICMP {
+    fn layers(&self) -> &Layers {
+        &self.layers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote() -> Vec<u8> {
+        let mut data = vec![0x45, 0, 0, 40, 0x12, 0x34, 0, 0, 1, 17, 0, 0, 10, 0, 0, 1, 8, 8, 8, 8];
+        data.extend([0x82, 0x9b, 0, 53, 0, 20, 0, 0, 0xff]);
+        data
+    }
+
+    fn extended_echo_request(c_type: u8, payload: &[u8]) -> Vec<u8> {
+        let len = 4 + payload.len() as u16;
+        [
+            &[ICMP::EXTENDED_ECHO_REQUEST, 0, 0, 0, 0, 1, 7, 1][..],
+            &[0x20, 0, 0, 0],
+            &len.to_be_bytes(),
+            &[InterfaceId::CLASS, c_type],
+            payload,
+        ].concat()
+    }
+
+    #[test]
+    fn echo() {
+        let icmp = ICMP::try_make(&[ICMP::ECHO_REQUEST, 0, 0xf7, 0xfd, 0, 1, 0, 2, b'h', b'i']).unwrap();
+        assert_eq!(icmp.checksum, 0xf7fd);
+        assert!(!icmp.is_error());
+        assert!(matches!(
+            icmp.data,
+            ICMPData::Echo { id: 1, kind: Echo::Request, num: 2, ref data } if data == b"hi"
+        ));
+        let icmp = ICMP::try_make(&[ICMP::ECHO_REPLY, 0, 0, 0, 0, 1, 0, 2]).unwrap();
+        assert!(matches!(icmp.data, ICMPData::Echo { kind: Echo::Response, ref data, .. } if data.is_empty()));
+    }
+
+    #[test]
+    fn errors() {
+        let data = [&[ICMP::DST_UNREACHABLE, 4, 0, 0, 0, 0, 0x05, 0xdc][..], &quote()].concat();
+        let icmp = ICMP::try_make(&data).unwrap();
+        assert!(icmp.is_error());
+        assert!(matches!(icmp.data, ICMPData::DstUnreachable { kind: DSTUnreachable::FragRequired, mtu: 1500 }));
+        let quoted = icmp.quoted().unwrap();
+        assert_eq!((quoted.src, quoted.dst, quoted.protocol), ([10, 0, 0, 1], [8, 8, 8, 8], 17));
+        assert_eq!((quoted.src_port, quoted.dst_port), (Some(33435), Some(53)));
+        assert_eq!(quoted.transport.len(), 8);
+
+        let data = [&[ICMP::REDIRECT, 1, 0, 0, 10, 0, 0, 254][..], &quote()].concat();
+        let icmp = ICMP::try_make(&data).unwrap();
+        assert!(matches!(icmp.data, ICMPData::Forward { kind: Forward::PktNode, addr: [10, 0, 0, 254] }));
+
+        let icmp = ICMP::try_make(&[ICMP::PARAMETER_PROBLEM, 0, 0, 0, 9, 0, 0, 0]).unwrap();
+        assert!(matches!(icmp.data, ICMPData::InvalidParams(InvalidParams::PtrError { ptr: 9 })));
+        assert!(icmp.quoted().is_none());
+
+        // only error messages quote a datagram
+        let data = [&[ICMP::ECHO_REPLY, 0, 0, 0, 0, 1, 0, 2][..], &quote()].concat();
+        assert!(ICMP::try_make(&data).unwrap().quoted().is_none());
+    }
+
+    #[test]
+    fn queries() {
+        let data = [
+            &[ICMP::ROUTER_ADVERTISEMENT, 0, 0, 0, 2, 2, 0x07, 0x08][..],
+            &[10, 0, 0, 1, 0, 0, 0, 1],
+            &[10, 0, 0, 2, 0x80, 0, 0, 0],
+        ].concat();
+        let ICMPData::RouterAdvertisement { lifetime, addresses } = ICMP::try_make(&data).unwrap().data else {
+            panic!()
+        };
+        assert_eq!(lifetime, 1800);
+        assert_eq!(addresses.len(), 2);
+        assert_eq!((addresses[0].address, addresses[0].preference), ([10, 0, 0, 1], 1));
+        assert_eq!(addresses[1].preference, i32::MIN);
+
+        let data = [ICMP::TIMESTAMP_REPLY, 0, 0, 0, 0, 1, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0, 5];
+        let ICMPData::ResTime(time) = ICMP::try_make(&data).unwrap().data else { panic!() };
+        assert_eq!((time.id, time.num, time.begin_time, time.recv_time, time.send_time), (1, 2, 3, 4, 5));
+
+        let data = [ICMP::MASK_REPLY, 0, 0, 0, 0, 1, 0, 2, 255, 255, 255, 0];
+        let ICMPData::MaskRes(mask) = ICMP::try_make(&data).unwrap().data else { panic!() };
+        assert_eq!(mask.mask, [255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn extended_echo() {
+        let icmp = ICMP::try_make(&extended_echo_request(1, b"eth0\0\0\0\0")).unwrap();
+        assert!(matches!(
+            icmp.data,
+            ICMPData::ExtendedEchoRequest { id: 1, num: 7, local: true, interface: Some(InterfaceId::Name(ref name)) }
+                if name == "eth0"
+        ));
+        let icmp = ICMP::try_make(&extended_echo_request(2, &[0, 0, 0, 3])).unwrap();
+        assert!(matches!(icmp.data, ICMPData::ExtendedEchoRequest { interface: Some(InterfaceId::Index(3)), .. }));
+        let icmp = ICMP::try_make(&extended_echo_request(3, &[0, 1, 4, 0, 10, 0, 0, 1])).unwrap();
+        assert!(matches!(
+            icmp.data,
+            ICMPData::ExtendedEchoRequest { interface: Some(InterfaceId::Address { afi: 1, ref address }), .. }
+                if address == &[10, 0, 0, 1]
+        ));
+
+        let icmp = ICMP::try_make(&[ICMP::EXTENDED_ECHO_REPLY, 0, 0, 0, 0, 1, 7, 0b0100_0110]).unwrap();
+        assert!(matches!(
+            icmp.data,
+            ICMPData::ExtendedEchoReply { id: 1, num: 7, state: 2, active: true, ipv4: true, ipv6: false }
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        let messages = [
+            vec![ICMP::ECHO_REQUEST, 0, 0, 0, 0, 1, 0, 2],
+            vec![ICMP::DST_UNREACHABLE, 4, 0, 0, 0, 0, 0x05, 0xdc],
+            vec![ICMP::REDIRECT, 0, 0, 0, 10, 0, 0, 254],
+            vec![ICMP::TIMESTAMP, 0, 0, 0, 0, 1, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0, 5],
+            vec![ICMP::MASK_REQUEST, 0, 0, 0, 0, 1, 0, 2, 255, 255, 255, 0],
+            vec![ICMP::EXTENDED_ECHO_REPLY, 0, 0, 0, 0, 1, 7, 0],
+        ];
+        for data in &messages {
+            assert!(!matches!(ICMP::try_make(data).unwrap().data, ICMPData::Unknown));
+            for end in 0..data.len() {
+                match ICMP::try_make(&data[..end]) {
+                    Some(icmp) => assert!(end >= 4 && matches!(icmp.data, ICMPData::Unknown), "{:?}", &data[..end]),
+                    None => assert!(end < 4),
+                }
+            }
         }
+        // quote shorter than an IPv4 header
+        let data = [&[ICMP::TIME_EXCEEDED, 0, 0, 0, 0, 0, 0, 0][..], &quote()[..19]].concat();
+        let icmp = ICMP::try_make(&data).unwrap();
+        assert!(matches!(icmp.data, ICMPData::TTLExpire { kind: TTLExpire::Transportation }));
+        assert!(icmp.quoted().is_none());
+    }
+
+    #[test]
+    fn malformed() {
+        // object length shorter than its header, past the message, and a
+        // non-interface class
+        assert!(InterfaceId::parse(&[0x20, 0, 0, 0, 0, 2, 3, 1]).is_none());
+        assert!(InterfaceId::parse(&[0x20, 0, 0, 0, 0, 9, 3, 1, 0]).is_none());
+        assert!(InterfaceId::parse(&[0x20, 0, 0, 0, 0, 4, 1, 1]).is_none());
+        // index and address objects too short for their fields
+        assert!(InterfaceId::parse(&[0x20, 0, 0, 0, 0, 6, 3, 2, 0, 1]).is_none());
+        assert!(InterfaceId::parse(&[0x20, 0, 0, 0, 0, 8, 3, 3, 0, 1, 4, 0]).is_none());
+        assert!(matches!(
+            InterfaceId::parse(&[0x20, 0, 0, 0, 0, 4, 1, 1, 0, 5, 3, 9, 1]),
+            Some(InterfaceId::Other { c_type: 9, ref data }) if data == &[1]
+        ));
+        // an advertisement with more entries announced than present
+        let data = [ICMP::ROUTER_ADVERTISEMENT, 0, 0, 0, 3, 0, 0, 30, 10, 0, 0, 1, 0, 0, 0, 0, 10];
+        assert!(matches!(
+            ICMP::try_make(&data).unwrap().data,
+            ICMPData::RouterAdvertisement { ref addresses, .. } if addresses.len() == 1
+        ));
+        assert!(matches!(ICMP::try_make(&[3, 99, 0, 0, 0, 0, 0, 0]).unwrap().data,
+            ICMPData::DstUnreachable { kind: DSTUnreachable::Unknown(99), mtu: 0 }));
+        assert!(matches!(ICMP::try_make(&[200, 0, 0, 0]).unwrap().data, ICMPData::Reserved));
     }
 }
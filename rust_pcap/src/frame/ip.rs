@@ -1,5 +1,3 @@
-use byteorder::{ByteOrder, NetworkEndian};
-
 use crate::*;
 use crate::frame::icmp::ICMP;
 
@@ -8,94 +6,87 @@ pub trait IP {
     fn dst(&self) -> &[u8];
 }
 
-#[derive(Layer, Debug)]
+#[derive(Layer, Header, Debug)]
 pub struct IPFlags {
     pub null: bool,
     pub df: bool,
     pub mf: bool,
 }
 
-#[derive(Layer, Debug)]
+#[derive(Layer, Header, Debug)]
+#[layer(abbrev = "ip")]
 pub struct IPv4 {
     // offset: 0
+    #[layer(offset = 0)]
+    #[header(bits = 4)]
+    pub version: u8,
+    #[layer(offset = 0)]
+    #[header(bits = 4)]
     pub ihl: u8,
+    #[layer(offset = 1)]
+    #[header(bits = 6)]
     pub dscp: u8,
+    #[layer(offset = 1)]
+    #[header(bits = 2)]
     pub ecn: u8,
+    #[layer(offset = 2)]
     pub size: u16,
     // offset: 4
+    #[layer(offset = 4, format = "hex")]
     pub id: u16,
+    #[layer(offset = 6, format = "debug")]
     pub flags: IPFlags,
+    #[layer(offset = 6)]
+    #[header(bits = 13)]
     pub fragments_offset: u16,
     // offset: 8
+    #[layer(offset = 8)]
     pub ttl: u8,
+    #[layer(offset = 9)]
     pub protocol: u8,
+    #[layer(offset = 10, format = "hex")]
     pub checksum: u16,
     // offset: 12
+    #[layer(offset = 12)]
     pub src: [u8; 4],
     // offset: 16
+    #[layer(offset = 16)]
     pub dst: [u8; 4],
     // optional offset: 20
+    #[layer(offset = 20, format = "bytes")]
+    #[header(skip)]
     pub opt: Option<[u8; 4]>,
+    #[header(skip)]
     layers: Layers,
 }
 
 impl IPv4 {
-    pub fn new(data: &[u8], ctx: &mut DissectionContext) -> IPv4 {
-        let ihl = data.get(0).unwrap().clone();
-        let ihl = ihl & 0x0F;
-        let dscp = data.get(1).unwrap().clone();
-        let ecn = dscp & 0b000000_11;
-        let dscp = dscp >> 2;
-        let size = NetworkEndian::read_u16(data.get(2..4).unwrap());
-        let id = NetworkEndian::read_u16(data.get(4..6).unwrap());
-        let fragments_offset = NetworkEndian::read_u16(data.get(6..8).unwrap());
-        let flags = IPFlags {
-            null: fragments_offset & 0b100_00000 > 0,
-            df: fragments_offset & 0b010_00000 > 0,
-            mf: fragments_offset & 0b001_00000 > 0,
-        };
-        let fragments_offset = fragments_offset & 0b000_11111;
-        let ttl = data.get(8).unwrap().clone();
-        let protocol = data.get(9).unwrap().clone();
-        let checksum = NetworkEndian::read_u16(data.get(10..12).unwrap());
-        let (_, data) = split(data, 12);
-        let (src, data) = split(data, 4);
-        let (dst, data) = split(data, 4);
-        let (opt, data) = if ihl > 5 {
-            let (o, d) = split(data, 4);
-            (Some(o.try_into().unwrap()), d)
-        } else {
-            (None, data)
-        };
-        let mut ipv4 = IPv4 {
-            ihl,
-            dscp,
-            ecn,
-            size,
-            id,
-            flags,
-            fragments_offset,
-            ttl,
-            protocol,
-            checksum,
-            src: src.try_into().unwrap(),
-            dst: dst.try_into().unwrap(),
-            opt,
-            layers: default(),
-        };
-        match protocol {
+    pub fn try_make(data: &[u8], ctx: &mut DissectionContext) -> Option<IPv4> {
+        let mut ipv4 = IPv4::parse(data)?;
+        let header_len = (ipv4.ihl as usize * 4).max(IPv4::SIZE);
+        let (header, data) = split(data, header_len);
+        // drop Ethernet padding past the total length
+        let data = data.get(..(ipv4.size as usize).saturating_sub(header_len)).unwrap_or(data);
+        ipv4.opt = header.get(IPv4::SIZE..IPv4::SIZE + 4).map(|o| o.try_into().unwrap());
+        match ipv4.protocol {
             1 => {
-                ipv4.layers.insert(ICMP::new(data));
+                if let Some(icmp) = ICMP::try_make(data) {
+                    ipv4.layers.insert(icmp);
+                }
             }
             6 => {
-                ipv4.layers.insert(TCP::new(data, &ipv4, ctx));
+                if let Some(tcp) = TCP::try_make(data, &ipv4, ctx) {
+                    ipv4.layers.insert(tcp);
+                }
             }
             17 => {
-                ipv4.layers.insert(UDP::new(data));
+                if let Some(udp) = UDP::try_make(data, &ipv4, ctx) {
+                    ipv4.layers.insert(udp);
+                }
             }
             _ => {}
         }
-        ipv4
+        Some(ipv4)
     }
 }
 
@@ -113,47 +104,85 @@ impl HasLayers for IPv4 {
     fn layers(&self) -> &Layers {
         &self.layers
     }
-    fn get_layer_descendants<T>(&self) -> Option<&T> where T: Layer {
-        get_layer_descendants!(self, T, UDP, TCP)
-    }
 }
 
 
-#[derive(Layer)]
+#[derive(Layer, Header, Debug)]
 pub struct IPv6 {
     // offset 0
-    qos: u8,
-    label: u32,
+    #[layer(offset = 0)]
+    #[header(bits = 4)]
+    pub version: u8,
+    /// Traffic class
+    #[layer(offset = 0)]
+    pub qos: u8,
+    #[layer(offset = 1, format = "hex")]
+    #[header(bits = 20)]
+    pub label: u32,
     // offset 4
-    len: u16,
-    header: u8,
-    hops: u8,
+    /// Payload length, extension headers included
+    #[layer(offset = 4)]
+    pub len: u16,
+    /// Next header
+    #[layer(offset = 6)]
+    pub header: u8,
+    #[layer(offset = 7)]
+    pub hops: u8,
     // offset 8
-    src: [u8; 16],
+    #[layer(offset = 8)]
+    pub src: [u8; 16],
     // offset 24
-    dst: [u8; 16],
+    #[layer(offset = 24)]
+    pub dst: [u8; 16],
+    #[header(skip)]
+    layers: Layers,
 }
 
 impl IPv6 {
-    pub fn new(data: &[u8], _ctx: &mut DissectionContext) -> IPv6 {
-        let qos = NetworkEndian::read_u16(data.get(0..2).unwrap());
-        let qos = (qos << 4) as u8;
-        let label = NetworkEndian::read_u32(data.get(..4).unwrap());
-        let label = label << 12;
-        let len = NetworkEndian::read_u16(data.get(4..6).unwrap());
-        let header = data.get(6).unwrap().clone();
-        let hops = data.get(7).unwrap().clone();
-        let src = get_array!(data, 8..24);
-        let dst = get_array!(data, 24..40);
-        IPv6 {
-            qos,
-            label,
-            len,
-            header,
-            hops,
-            src,
-            dst,
+    const HOP_BY_HOP: u8 = 0;
+    const ROUTING: u8 = 43;
+    const FRAGMENT: u8 = 44;
+    const DESTINATION: u8 = 60;
+
+    pub fn try_make(data: &[u8], ctx: &mut DissectionContext) -> Option<IPv6> {
+        let mut ipv6 = IPv6::parse(data)?;
+        let (_, data) = split(data, IPv6::SIZE);
+        // drop Ethernet padding past the payload length
+        let mut data = data.get(..ipv6.len as usize).unwrap_or(data);
+        let mut next = ipv6.header;
+        while matches!(next, Self::HOP_BY_HOP | Self::ROUTING | Self::DESTINATION) {
+            let len = match data.get(1) {
+                Some(&len) => (len as usize + 1) * 8,
+                None => return Some(ipv6),
+            };
+            next = data[0];
+            data = match data.get(len..) {
+                Some(rest) => rest,
+                None => return Some(ipv6),
+            };
         }
+        match next {
+            6 => {
+                if let Some(tcp) = TCP::try_make(data, &ipv6, ctx) {
+                    ipv6.layers.insert(tcp);
+                }
+            }
+            17 => {
+                if let Some(udp) = UDP::try_make(data, &ipv6, ctx) {
+                    ipv6.layers.insert(udp);
+                }
+            }
+            // fragments are not reassembled
+            Self::FRAGMENT => {}
+            _ => {}
+        }
+        Some(ipv6)
+    }
+}
+
+impl HasLayers for IPv6 {
+    fn layers(&self) -> &Layers {
+        &self.layers
     }
 }
 
@@ -38,15 +38,19 @@ impl TryFrom<&[u8]> for MessageType {
 
 #[derive(Layer, Debug)]
 pub struct OpcUa {
+    #[layer(offset = 0, format = "debug")]
     pub msg_type: MessageType,
+    #[layer(offset = 3)]
     pub chunk_type: char,
+    #[layer(offset = 4)]
     pub message_size: u32,
+    #[layer(offset = 8)]
     pub data: Vec<u8>,
 }
 
 impl OpcUa {
     pub fn try_make(tcp: &TCP) -> Option<Self> {
-        let data = tcp.data.as_slice();
+        let data = tcp.stream.as_slice();
         let kind = MessageType::try_from(data).ok()?;
         let chunk_type = *data.get(3)? as char;
         let message_size = LittleEndian::read_u32(data.get(4..8)?);
@@ -3,11 +3,17 @@ use byteorder::{ByteOrder, NetworkEndian};
 
 
 #[derive(Layer)]
+#[layer(abbrev = "sv")]
 pub struct SampledValue {
+    #[layer(offset = 0, format = "hex")]
     app_id: u16,  //  0x4000 ～ 0x7fff
+    #[layer(offset = 2)]
     pub len: u16,
+    #[layer(offset = 4)]
     pub reserved1: u16,
+    #[layer(offset = 6)]
     pub reserved2: u16,
+    #[layer(offset = 8)]
     pub apdu: Vec<u8>,
 }
 
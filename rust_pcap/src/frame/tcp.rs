@@ -1,11 +1,16 @@
+use std::collections::BTreeMap;
+
 use byteorder::{ByteOrder, NetworkEndian};
 use derivative::Derivative;
 
 use crate::*;
+use crate::dns::{DNS, DNSContext};
+use crate::http2::HTTP2;
+use crate::tls::{TLS, TLSContext};
 use crate::opc_ua::OpcUa;
 use crate::tpkt::TPKT;
 
-#[derive(Debug)]
+#[derive(Debug, Header)]
 pub struct TCPFlags {
     pub ns: bool,
     pub cwr: bool,
@@ -18,104 +23,501 @@ pub struct TCPFlags {
     pub fin: bool,
 }
 
-#[derive(Derivative, Layer)]
+#[derive(Derivative, Layer, Header)]
 #[derivative(Debug)]
 pub struct TCP {
+    #[layer(offset = 0)]
     pub src: u16,
+    #[layer(offset = 2)]
     pub dst: u16,
+    #[layer(offset = 4)]
     pub sn: u32,
+    #[layer(offset = 8)]
     pub ack_sn: u32,
+    #[layer(offset = 12)]
+    #[header(bits = 4)]
     pub header_len: u8,
+    #[layer(offset = 12, format = "debug")]
+    #[header(pad = 3)]
     pub flags: TCPFlags,
+    #[layer(offset = 14)]
     pub window_size: u16,
+    #[layer(offset = 16, format = "hex")]
     pub checksum: u16,
+    #[layer(offset = 18)]
     pub urgent_point: u16,
+    #[layer(offset = 20)]
+    #[header(skip)]
     pub options: Vec<u8>,
+    /// Not a field, payloads would be copied on every filter evaluation
+    #[layer(skip)]
     #[derivative(Debug = "ignore")]
+    #[header(skip)]
     pub data: Vec<u8>,
+    #[layer(skip)]
     #[derivative(Debug = "ignore")]
+    #[header(skip)]
     pub whole_data: Vec<u8>,
+    /// Bytes the segment adds to its stream in order: the payload without
+    /// retransmitted bytes, plus queued segments it makes contiguous
+    #[layer(skip)]
+    #[derivative(Debug = "ignore")]
+    #[header(skip)]
+    pub stream: Vec<u8>,
+    #[header(skip)]
     layers: Layers,
 }
 
 impl TCP {
-    pub fn new(data: &[u8], ip: &impl IP, ctx: &mut DissectionContext) -> TCP {
-        let src = NetworkEndian::read_u16(data.get(..2).unwrap());
-        let dst = NetworkEndian::read_u16(data.get(2..4).unwrap());
-        let sn = NetworkEndian::read_u32(data.get(4..8).unwrap());
-        let ack_sn = NetworkEndian::read_u32(data.get(8..12).unwrap());
-        let header_len = (data.get(12).unwrap() & 0b11110000) >> 4;
-        let flags = NetworkEndian::read_u16(data.get(12..14).unwrap());
-        let flags = TCPFlags {
-            ns: flags & 0b0000000100000000 > 0,
-            cwr: flags & 0b0000000010000000 > 0,
-            ece: flags & 0b0000000001000000 > 0,
-            urg: flags & 0b0000000000100000 > 0,
-            ack: flags & 0b0000000000010000 > 0,
-            psh: flags & 0b0000000000001000 > 0,
-            rst: flags & 0b0000000000000100 > 0,
-            syn: flags & 0b0000000000000010 > 0,
-            fin: flags & 0b0000000000000001 > 0,
-        };
-        let window_size = NetworkEndian::read_u16(data.get(14..16).unwrap());
-        let checksum = NetworkEndian::read_u16(data.get(16..18).unwrap());
-        let urgent_point = NetworkEndian::read_u16(data.get(18..20).unwrap());
-        let options = data.get(20..(header_len.clone() as usize * 4)).unwrap().to_vec();
-        let data = data.get((header_len.clone() as usize * 4)..).unwrap().to_vec();
-        let mut tcp = TCP {
-            src,
-            dst,
-            sn,
-            ack_sn,
-            header_len: header_len.clone(),
-            flags,
-            window_size,
-            checksum,
-            urgent_point,
-            options,
-            data,
-            whole_data: default(),
-            layers: default(),
-        };
-        let ctx = &mut ctx.tcp;
+    pub fn try_make(data: &[u8], ip: &impl IP, ctx: &mut DissectionContext) -> Option<TCP> {
+        let mut tcp = TCP::parse(data)?;
+        let header_len = (tcp.header_len as usize * 4).max(TCP::SIZE);
+        tcp.options = data.get(TCP::SIZE..header_len)?.to_vec();
+        tcp.data = data.get(header_len..)?.to_vec();
         let key = Self::_key(ip.src(), tcp.src, ip.dst(), tcp.dst);
-        let mut sequence = ctx.entry(key.clone()).or_insert(default());
-        sequence.data.extend(&tcp.data);
-        if let Some(http) = HTTP::try_make(&mut sequence, &tcp) {
-            tcp.layers.insert(http);
-        } else if let Some(opc_ua) = OpcUa::try_make(&tcp) {
-            tcp.layers.insert(opc_ua);
-        } else if let Some(tpkt) = TPKT::try_make(&sequence) {
-            tcp.layers.insert(tpkt);
+        let reverse = Self::_key(ip.dst(), tcp.dst, ip.src(), tcp.src);
+        let flow = format!("tcp {}", key);
+        let sequence = ctx.tcp.touch(&key, ctx.ts, ctx.limits.stream_bytes);
+        let truncated = sequence.truncated;
+        tcp.stream = sequence.reassemble(&tcp);
+        let candidates = match ctx.apps.get(&flow, ctx.ts) {
+            Some(app) => vec![app],
+            // handshakes, bare ACKs and retransmissions tell nothing of the
+            // application
+            None if tcp.stream.is_empty() => vec![],
+            None => ctx.registry.candidates(Transport::TCP, tcp.src, tcp.dst),
+        };
+        sequence.truncated |= extend_capped(&mut sequence.data, &tcp.stream, sequence.cap);
+        let segment = Flow { key: &key, reverse: &reverse, ts: ctx.ts };
+        for app in candidates {
+            if tcp.try_app(app, sequence, &segment, &mut ctx.http, &mut ctx.http2) {
+                ctx.apps.insert(&flow, app, ctx.ts);
+                break;
+            }
         }
-        if tcp.is_tail_of_sequence() {
-            if let Some(seq) = ctx.remove(&key) {
-                tcp.whole_data = seq.data;
+        if tcp.layers.get_all::<HTTP>().any(|http| http.is_h2c_upgrade()) {
+            // both directions continue as HTTP/2, the client one is the reverse
+            ctx.http2.open(&reverse, ctx.ts);
+            ctx.apps.insert(&flow, App::HTTP2, ctx.ts);
+            ctx.apps.insert(&format!("tcp {}", reverse), App::HTTP2, ctx.ts);
+            let buffer = sequence.http.take().map(|http| http.buffer).unwrap_or_default();
+            sequence.http2 = Some(HTTP2Context::upgraded(buffer));
+        }
+        if !truncated && sequence.truncated {
+            ctx.stats.truncated += 1;
+        }
+        if tcp.flags.psh {
+            // application state outlives pushes, only the segment data is handed over
+            tcp.whole_data = std::mem::take(&mut sequence.data);
+        }
+        if tcp.flags.fin {
+            if let Some(seq) = ctx.tcp.remove(&key) {
+                tcp.whole_data.extend(seq.data);
             }
+        } else {
+            ctx.tcp.account(&key);
+            ctx.tcp.enforce_budget(ctx.limits.memory_budget, &mut ctx.stats);
         }
-        tcp
+        Some(tcp)
+    }
+
+    /// Whether `app` identified the stream, i.e. dissected at least one
+    /// message; buffering a possible start does not count
+    fn try_app(
+        &mut self,
+        app: App,
+        sequence: &mut TCPSequence,
+        flow: &Flow,
+        http: &mut HTTPPending,
+        http2: &mut HTTP2Connections,
+    ) -> bool {
+        match app {
+            App::HTTP => HTTP::try_make(sequence, self, http, flow)
+                .is_some_and(|messages| self.insert_all(messages)),
+            App::HTTP2 => HTTP2::try_make(sequence, self, http2, flow)
+                .is_some_and(|frames| self.insert_all(frames)),
+            App::TLS => TLS::try_make(sequence, self)
+                .is_some_and(|records| self.insert_all(records)),
+            App::OpcUa => OpcUa::try_make(self)
+                .is_some_and(|opc_ua| self.insert_all(vec![opc_ua])),
+            App::TPKT => TPKT::try_make(sequence)
+                .is_some_and(|tpkt| self.insert_all(vec![tpkt])),
+            App::DNS => DNS::try_make_tcp(sequence, self)
+                .is_some_and(|messages| self.insert_all(messages)),
+            App::DHCP | App::DHCPv6 => false,
+        }
+    }
+
+    /// Returns whether there was any layer
+    fn insert_all<T: Layer>(&mut self, layers: Vec<T>) -> bool {
+        let any = !layers.is_empty();
+        layers.into_iter().for_each(|layer| self.layers.insert(layer));
+        any
     }
 
     pub fn is_tail_of_sequence(&self) -> bool {
         self.flags.psh | self.flags.fin
     }
 
+    /// Sequence space taken by the segment: payload plus SYN and FIN
+    pub fn seq_len(&self) -> u32 {
+        self.data.len() as u32 + self.flags.syn as u32 + self.flags.fin as u32
+    }
+
+    /// `(TSval, TSecr)` of the timestamp option (RFC 7323)
+    pub fn timestamp(&self) -> Option<(u32, u32)> {
+        const END: u8 = 0;
+        const NOP: u8 = 1;
+        const TIMESTAMP: u8 = 8;
+        let mut options = self.options.as_slice();
+        loop {
+            match *options.first()? {
+                END => return None,
+                NOP => options = options.get(1..)?,
+                kind => {
+                    let len = *options.get(1)? as usize;
+                    if len < 2 { return None; }
+                    if kind == TIMESTAMP && len == 10 {
+                        return Some((
+                            NetworkEndian::read_u32(options.get(2..6)?),
+                            NetworkEndian::read_u32(options.get(6..10)?),
+                        ));
+                    }
+                    options = options.get(len..)?;
+                }
+            }
+        }
+    }
+
     fn _key(src_ip: &[u8], src: u16, dst_ip: &[u8], dst: u16) -> String {
         format!("{:?} {} -> {:?} {}", src_ip, src, dst_ip, dst)
     }
 }
 
+/// Both directions of the connection a segment belongs to
+#[derive(Debug, Clone, Copy)]
+pub struct Flow<'a> {
+    /// Stream of the segment
+    pub key: &'a str,
+    /// Opposite stream
+    pub reverse: &'a str,
+    pub ts: f64,
+}
+
 impl HasLayers for TCP {
     fn layers(&self) -> &Layers {
         &self.layers
     }
 }
 
-pub type TCPContext = HashMap<String, TCPSequence>;
+/// Per stream reassembly state, bounded by [`DissectionLimits`]
+#[derive(Debug, Default)]
+pub struct TCPContext {
+    streams: HashMap<String, TCPSequence>,
+    /// Bytes buffered over all streams
+    pub buffered: usize,
+    /// Streams by last use, least recent first
+    recency: BTreeMap<u64, String>,
+    /// Use counter ordering `recency`
+    tick: u64,
+    last_sweep: f64,
+}
+
+impl TCPContext {
+    /// Sweep idle streams at most once per this many seconds of capture time
+    const SWEEP_PERIOD: f64 = 1.0;
 
+    /// Stream for `key`, created if needed and marked as used at `ts`
+    pub fn touch(&mut self, key: &str, ts: f64, cap: Option<usize>) -> &mut TCPSequence {
+        let sequence = self.streams.entry(key.to_string())
+            .or_insert_with(|| TCPSequence { cap, ..default() });
+        sequence.last_seen = ts;
+        if let Some(tick) = sequence.tick {
+            self.recency.remove(&tick);
+        }
+        self.tick += 1;
+        sequence.tick = Some(self.tick);
+        self.recency.insert(self.tick, key.to_string());
+        sequence
+    }
+
+    pub fn get(&self, key: &str) -> Option<&TCPSequence> {
+        self.streams.get(key)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<TCPSequence> {
+        let sequence = self.streams.remove(key)?;
+        if let Some(tick) = sequence.tick {
+            self.recency.remove(&tick);
+        }
+        self.buffered -= sequence.accounted;
+        Some(sequence)
+    }
+
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Refresh `buffered` after the stream buffers changed
+    pub fn account(&mut self, key: &str) {
+        if let Some(sequence) = self.streams.get_mut(key) {
+            let size = sequence.size();
+            self.buffered = self.buffered - sequence.accounted + size;
+            sequence.accounted = size;
+        }
+    }
+
+    /// Evict streams without segments for `timeout` seconds before `ts`
+    pub fn expire(&mut self, ts: f64, timeout: Option<f64>, stats: &mut DissectionStats) {
+        let timeout = match timeout {
+            Some(timeout) if ts - self.last_sweep >= Self::SWEEP_PERIOD => timeout,
+            _ => return,
+        };
+        self.last_sweep = ts;
+        let idle = self.streams.iter()
+            .filter(|(_, s)| ts - s.last_seen > timeout)
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+        for key in idle {
+            self.remove(&key);
+            stats.evicted_idle += 1;
+        }
+    }
+
+    /// Evict least recently used streams until `buffered` fits `budget`
+    pub fn enforce_budget(&mut self, budget: Option<usize>, stats: &mut DissectionStats) {
+        let budget = match budget {
+            Some(budget) => budget,
+            None => return,
+        };
+        while self.buffered > budget {
+            match self.recency.first_key_value().map(|(_, key)| key.clone()) {
+                Some(key) => {
+                    self.remove(&key);
+                    stats.evicted_budget += 1;
+                }
+                None => break,
+            }
+        }
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct TCPSequence {
     pub data: Vec<u8>,
+    /// Sequence number of the next stream byte, unknown before the first
+    /// segment
+    next_sn: Option<u32>,
+    /// Segments past a gap by sequence number, waiting for it to fill
+    out_of_order: Vec<(u32, Vec<u8>)>,
     pub http: Option<HTTPContext>,
+    pub http2: Option<HTTP2Context>,
+    pub tls: Option<TLSContext>,
+    pub dns: Option<DNSContext>,
+    /// Bytes kept per buffer, the rest of the stream is dropped
+    pub cap: Option<usize>,
+    /// Some of the stream data was dropped because of `cap`
+    pub truncated: bool,
+    /// Timestamp of the latest segment
+    pub last_seen: f64,
+    /// Size as counted in [`TCPContext::buffered`]
+    accounted: usize,
+    /// Position in [`TCPContext`] recency
+    tick: Option<u64>,
+}
+
+impl TCPSequence {
+    /// Segments queued past a gap before it is given up as lost
+    const MAX_OUT_OF_ORDER: usize = 64;
+
+    /// Stream bytes `tcp` delivers in order. Retransmitted bytes are dropped
+    /// and segments past a gap are queued; a gap is skipped, truncating the
+    /// stream, when the queue is full or the stream ends
+    pub fn reassemble(&mut self, tcp: &TCP) -> Vec<u8> {
+        let start = tcp.sn.wrapping_add(tcp.flags.syn as u32);
+        if tcp.flags.syn {
+            self.next_sn = Some(start);
+            self.out_of_order.clear();
+        }
+        let mut next = *self.next_sn.get_or_insert(start);
+        if !tcp.data.is_empty() {
+            self.out_of_order.push((start, tcp.data.clone()));
+        }
+        let mut stream = vec![];
+        loop {
+            // offsets from `next`, negative ones overlap delivered bytes
+            let offset = |sn: u32| sn.wrapping_sub(next) as i32;
+            match self.out_of_order.iter().position(|&(sn, _)| offset(sn) <= 0) {
+                Some(i) => {
+                    let (sn, data) = self.out_of_order.swap_remove(i);
+                    let seen = offset(sn).unsigned_abs() as usize;
+                    if let Some(new) = data.get(seen..) {
+                        stream.extend(new);
+                        next = next.wrapping_add(new.len() as u32);
+                    }
+                }
+                None => {
+                    let queued = self.out_of_order.iter().map(|(_, data)| data.len()).sum::<usize>();
+                    let full = self.out_of_order.len() > Self::MAX_OUT_OF_ORDER
+                        || self.cap.is_some_and(|cap| queued > cap);
+                    let ended = tcp.flags.fin || tcp.flags.rst;
+                    match self.out_of_order.iter().map(|&(sn, _)| sn).min_by_key(|&sn| offset(sn)) {
+                        Some(sn) if full || ended => {
+                            self.truncated = true;
+                            next = sn;
+                        }
+                        _ => break,
+                    }
+                }
+            }
+        }
+        self.next_sn = Some(next);
+        stream
+    }
+
+    /// Bytes buffered by the stream
+    pub fn size(&self) -> usize {
+        self.data.len() + self.out_of_order.iter().map(|(_, data)| data.len()).sum::<usize>()
+            + self.http.as_ref().map_or(0, |http| {
+            http.buffer.len()
+        }) + self.http2.as_ref().map_or(0, |http2| http2.size())
+            + self.tls.as_ref().map_or(0, |tls| tls.size())
+            + self.dns.as_ref().map_or(0, |dns| dns.buffer.len())
+    }
+}
+
+/// Extends `buf` with `data` up to `cap` bytes, returns whether `data` was cut
+pub fn extend_capped(buf: &mut Vec<u8>, data: &[u8], cap: Option<usize>) -> bool {
+    let room = cap.map_or(data.len(), |cap| cap.saturating_sub(buf.len()));
+    buf.extend(&data[..room.min(data.len())]);
+    room < data.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use pcap_parser::Linktype;
+
+    use super::*;
+
+    const SYN: u8 = 0x02;
+    const ACK: u8 = 0x10;
+    const PSH: u8 = 0x08;
+    const FIN: u8 = 0x01;
+
+    fn segment(src: u16, dst: u16, sn: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let (client, server) = ([10, 0, 0, 1], [10, 0, 0, 2]);
+        let (src_ip, dst_ip) = if src > dst { (client, server) } else { (server, client) };
+        let len = (40 + payload.len()) as u16;
+        [
+            &[0x45, 0][..], &len.to_be_bytes(), &[0, 1, 0x40, 0, 64, 6, 0, 0], &src_ip, &dst_ip,
+            &src.to_be_bytes(), &dst.to_be_bytes(), &sn.to_be_bytes(), &[0, 0, 0, 0],
+            &[0x50, flags, 0xff, 0xff, 0, 0, 0, 0],
+            payload,
+        ].concat()
+    }
+
+    /// Protocol path of each segment of a connection, the client port is
+    /// the higher one
+    fn paths(segments: &[Vec<u8>]) -> Vec<String> {
+        let mut ctx = DissectionContext::default();
+        segments.iter().enumerate()
+            .map(|(i, data)| {
+                let len = data.len() as u32;
+                Frame::new(data, i as f64, len, len, Linktype::IPV4, &mut ctx).protocol_path()
+            })
+            .collect()
+    }
+
+    fn with_handshake(port: u16, payload: &[u8]) -> Vec<Vec<u8>> {
+        vec![
+            segment(50000, port, 100, SYN, b""),
+            segment(port, 50000, 900, SYN | ACK, b""),
+            segment(50000, port, 101, ACK, b""),
+            segment(50000, port, 101, PSH | ACK, payload),
+        ]
+    }
+
+    #[test]
+    fn handshake_does_not_pick_the_app() {
+        let hello = [b"HELF".as_slice(), &28u32.to_le_bytes(), &[0; 20]].concat();
+        assert_eq!(paths(&with_handshake(4840, &hello))[3], "ip:tcp:opcua");
+
+        let client_hello = [
+            &[0x16, 3, 1, 0, 45, 1, 0, 0, 41, 3, 3][..], &[0; 32], &[0, 0, 2, 0x13, 1, 1, 0],
+        ].concat();
+        assert_eq!(paths(&with_handshake(443, &client_hello))[3], "ip:tcp:tls");
+
+        let request = b"GET / HTTP/1.1\r\nHost: a\r\n\r\n";
+        assert_eq!(paths(&with_handshake(8080, request))[3], "ip:tcp:http");
+    }
+
+    fn reassembled(segments: &[(u32, u8, &[u8])], cap: Option<usize>) -> (Vec<Vec<u8>>, bool) {
+        let mut sequence = TCPSequence { cap, ..default() };
+        let streams = segments.iter()
+            .map(|&(sn, flags, payload)| {
+                let mut tcp = TCP::parse(&segment(50000, 80, sn, flags, payload)[20..]).unwrap();
+                tcp.data = payload.to_vec();
+                sequence.reassemble(&tcp)
+            })
+            .collect();
+        (streams, sequence.truncated)
+    }
+
+    #[test]
+    fn reassembly() {
+        // retransmitted, overlapping and reordered segments
+        let (streams, truncated) = reassembled(&[
+            (100, SYN, &b""[..]),
+            (101, ACK, b"abc"),
+            (101, ACK, b"abc"),
+            (107, ACK, b"ghi"),
+            (102, ACK, b"bcdef"),
+            (110, ACK, b"j"),
+        ], None);
+        assert_eq!(streams, [&b""[..], b"abc", b"", b"", b"defghi", b"j"]);
+        assert!(!truncated);
+        // sequence numbers wrap around
+        let (streams, _) = reassembled(&[(u32::MAX - 1, ACK, &b"ab"[..]), (0, ACK, b"cd")], None);
+        assert_eq!(streams.concat(), b"abcd");
+    }
+
+    #[test]
+    fn gaps() {
+        // skipped once the queue outgrows the cap
+        let (streams, truncated) = reassembled(&[
+            (1, ACK, &b"ab"[..]),
+            (5, ACK, b"ef"),
+            (7, ACK, b"gh"),
+            (3, ACK, b"cd"),
+        ], Some(3));
+        assert_eq!(streams, [&b"ab"[..], b"", b"efgh", b""]);
+        assert!(truncated);
+        // or when the stream ends
+        let (streams, truncated) = reassembled(&[(1, ACK, &b"ab"[..]), (5, ACK, b"ef"), (7, FIN | ACK, b"")], None);
+        assert_eq!(streams, [&b"ab"[..], b"", b"ef"]);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn reordered_http() {
+        let mut request = with_handshake(8080, b"POST / HTTP/1.1\r\nContent-");
+        request.extend([
+            segment(50000, 8080, 146, PSH | ACK, b"ghij"),
+            segment(50000, 8080, 126, PSH | ACK, b"Length: 10\r\n\r\nabcdef"),
+            segment(50000, 8080, 126, PSH | ACK, b"Length: 10\r\n\r\nabcdef"),
+        ]);
+        let mut ctx = DissectionContext::default();
+        let frames = request.iter()
+            .map(|data| Frame::new(data, 0.0, data.len() as u32, data.len() as u32, Linktype::IPV4, &mut ctx))
+            .collect::<Vec<_>>();
+        let http = frames.iter().flat_map(|frame| frame.get_layer::<HTTP>()).collect::<Vec<_>>();
+        assert_eq!(http.len(), 1);
+        assert_eq!(http[0].body, b"abcdefghij");
+        assert_eq!(frames[5].protocol_path(), "ip:tcp:http");
+        assert_eq!(frames[6].protocol_path(), "ip:tcp");
+    }
 }
@@ -0,0 +1,531 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use derivative::Derivative;
+
+use crate::*;
+use crate::hash::{md5, sha256, to_hex};
+use crate::x509::Certificate;
+
+pub const CHANGE_CIPHER_SPEC: u8 = 20;
+pub const ALERT: u8 = 21;
+pub const HANDSHAKE: u8 = 22;
+pub const APPLICATION_DATA: u8 = 23;
+pub const HEARTBEAT: u8 = 24;
+
+pub const CLIENT_HELLO: u8 = 1;
+pub const SERVER_HELLO: u8 = 2;
+pub const CERTIFICATE: u8 = 11;
+
+pub const SERVER_NAME: u16 = 0;
+pub const SUPPORTED_GROUPS: u16 = 10;
+pub const EC_POINT_FORMATS: u16 = 11;
+pub const SIGNATURE_ALGORITHMS: u16 = 13;
+pub const ALPN: u16 = 16;
+pub const SUPPORTED_VERSIONS: u16 = 43;
+
+pub const TLS1_3: u16 = 0x0304;
+
+#[derive(Debug, Clone)]
+pub struct TLSExtension {
+    pub kind: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientHello {
+    pub version: u16,
+    pub random: [u8; 32],
+    pub session_id: Vec<u8>,
+    pub cipher_suites: Vec<u16>,
+    pub compression_methods: Vec<u8>,
+    /// In the order sent
+    pub extensions: Vec<TLSExtension>,
+    pub sni: Option<String>,
+    pub alpn: Vec<String>,
+    pub supported_versions: Vec<u16>,
+    pub supported_groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+    pub signature_algorithms: Vec<u16>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerHello {
+    pub version: u16,
+    pub random: [u8; 32],
+    pub session_id: Vec<u8>,
+    pub cipher_suite: u16,
+    pub compression_method: u8,
+    pub extensions: Vec<TLSExtension>,
+    pub alpn: Option<String>,
+    /// `supported_versions` of TLS 1.3, the legacy `version` says TLS 1.2
+    pub supported_version: Option<u16>,
+}
+
+impl ServerHello {
+    pub fn negotiated_version(&self) -> u16 {
+        self.supported_version.unwrap_or(self.version)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TLSHandshake {
+    ClientHello(ClientHello),
+    ServerHello(ServerHello),
+    /// Chain sent in clear up to TLS 1.2, certificates that fail to parse
+    /// are left out
+    Certificate(Vec<Certificate>),
+    /// Unparsed message, or one larger than the stream cap
+    Other {
+        kind: u8,
+        len: usize,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum TLSMessage {
+    ChangeCipherSpec,
+    Alert {
+        level: u8,
+        description: u8,
+    },
+    Handshake(Vec<TLSHandshake>),
+    ApplicationData,
+    Heartbeat,
+    /// Handshake or alert sent after the keys changed
+    Encrypted,
+    #[default]
+    Unknown,
+}
+
+/// One TLS record, a segment may carry several of them
+#[derive(Derivative, Layer, Header)]
+#[derivative(Debug)]
+#[layer(abbrev = "tls")]
+pub struct TLS {
+    #[layer(offset = 0)]
+    pub content_type: u8,
+    #[layer(offset = 1, format = "hex")]
+    pub version: u16,
+    #[layer(offset = 3)]
+    pub length: u16,
+    #[layer(format = "debug")]
+    #[header(skip)]
+    pub message: TLSMessage,
+    /// JA3 hash of the client, or JA3S of the server, that opened the
+    /// direction; empty before its hello
+    #[header(skip)]
+    pub ja3: String,
+    /// JA4 of the client, empty in the server direction
+    #[header(skip)]
+    pub ja4: String,
+}
+
+/// Record parsing state of one direction of a connection
+#[derive(Default, Debug)]
+pub struct TLSContext {
+    /// Stream bytes not consumed by a complete record yet
+    pub buffer: Vec<u8>,
+    /// Handshake messages fragmented over several records
+    handshake: Vec<u8>,
+    /// Bytes left of a handshake message too large to buffer
+    handshake_skip: usize,
+    /// Application data still to skip, it is not buffered
+    skip: usize,
+    /// The sender changed keys, later handshake records are opaque
+    encrypted: bool,
+    started: bool,
+    /// Fingerprints of the hello, copied to every later record
+    ja3: String,
+    ja4: String,
+}
+
+impl TLSContext {
+    /// Bytes buffered for records and handshake messages
+    pub fn size(&self) -> usize {
+        self.buffer.len() + self.handshake.len()
+    }
+}
+
+impl TLS {
+    /// Largest record: 2^14 bytes of plaintext plus expansion
+    const MAX_LENGTH: usize = (1 << 14) + 2048;
+
+    /// Records completed by the segment, `None` if the stream is not TLS
+    pub fn try_make(sequence: &mut TCPSequence, tcp: &TCP) -> Option<Vec<TLS>> {
+        let cap = sequence.cap;
+        let ctx = sequence.tls.get_or_insert_with(default);
+        let skip = ctx.skip.min(tcp.stream.len());
+        ctx.skip -= skip;
+        sequence.truncated |= extend_capped(&mut ctx.buffer, &tcp.stream[skip..], cap);
+        if !ctx.started {
+            match Self::is_hello(&ctx.buffer) {
+                Some(true) => ctx.started = true,
+                Some(false) => {
+                    sequence.tls = None;
+                    return None;
+                }
+                None => return None,
+            }
+        }
+        let mut records = vec![];
+        while ctx.buffer.len() >= TLS::SIZE {
+            let mut record = TLS::parse(&ctx.buffer)?;
+            let length = record.length as usize;
+            if !(CHANGE_CIPHER_SPEC..=HEARTBEAT).contains(&record.content_type)
+                || record.version >> 8 != 3 || length > Self::MAX_LENGTH {
+                // lost the record boundaries
+                ctx.buffer.clear();
+                break;
+            }
+            let end = TLS::SIZE + length;
+            if record.content_type == APPLICATION_DATA && ctx.buffer.len() < end {
+                ctx.skip = end - ctx.buffer.len();
+                ctx.buffer.clear();
+                record.message = TLSMessage::ApplicationData;
+                record.ja3 = ctx.ja3.clone();
+                record.ja4 = ctx.ja4.clone();
+                records.push(record);
+                break;
+            }
+            if ctx.buffer.len() < end {
+                break;
+            }
+            let data = ctx.buffer.drain(..end).collect::<Vec<_>>();
+            record.message = record.decode(&data[TLS::SIZE..], ctx, cap);
+            record.ja3 = ctx.ja3.clone();
+            record.ja4 = ctx.ja4.clone();
+            records.push(record);
+        }
+        Some(records)
+    }
+
+    /// Whether the stream starts with a hello record, `None` until known
+    fn is_hello(data: &[u8]) -> Option<bool> {
+        let header = data.get(..6)?;
+        Some(header[0] == HANDSHAKE
+            && header[1] == 3 && header[2] <= 4
+            && matches!(header[5], CLIENT_HELLO | SERVER_HELLO))
+    }
+
+    fn decode(&self, payload: &[u8], ctx: &mut TLSContext, cap: Option<usize>) -> TLSMessage {
+        match self.content_type {
+            CHANGE_CIPHER_SPEC => {
+                ctx.encrypted = true;
+                TLSMessage::ChangeCipherSpec
+            }
+            ALERT | HANDSHAKE if ctx.encrypted => TLSMessage::Encrypted,
+            ALERT => match payload {
+                &[level, description] => TLSMessage::Alert { level, description },
+                _ => TLSMessage::Unknown,
+            },
+            HANDSHAKE => {
+                let skip = ctx.handshake_skip.min(payload.len());
+                ctx.handshake_skip -= skip;
+                ctx.handshake.extend(&payload[skip..]);
+                let mut messages = vec![];
+                while let Some(len) = ctx.handshake.get(1..4).map(|l| NetworkEndian::read_u24(l) as usize) {
+                    if cap.is_some_and(|cap| 4 + len > cap) {
+                        // never fits the buffer, skip the rest of it
+                        let available = ctx.handshake.len().min(4 + len);
+                        messages.push(TLSHandshake::Other { kind: ctx.handshake[0], len });
+                        ctx.handshake.drain(..available);
+                        ctx.handshake_skip = 4 + len - available;
+                        continue;
+                    }
+                    let message = match ctx.handshake.get(..4 + len) {
+                        Some(message) => Self::handshake(message),
+                        None => break,
+                    };
+                    ctx.handshake.drain(..4 + len);
+                    match &message {
+                        TLSHandshake::ClientHello(hello) => {
+                            ctx.ja3 = hello.ja3();
+                            ctx.ja4 = hello.ja4();
+                        }
+                        TLSHandshake::ServerHello(hello) => {
+                            ctx.ja3 = hello.ja3s();
+                            // everything after the TLS 1.3 ServerHello is encrypted
+                            ctx.encrypted |= hello.negotiated_version() == TLS1_3;
+                        }
+                        _ => {}
+                    }
+                    messages.push(message);
+                }
+                TLSMessage::Handshake(messages)
+            }
+            APPLICATION_DATA => TLSMessage::ApplicationData,
+            HEARTBEAT => TLSMessage::Heartbeat,
+            _ => TLSMessage::Unknown,
+        }
+    }
+
+    fn handshake(message: &[u8]) -> TLSHandshake {
+        let kind = message[0];
+        let body = &message[4..];
+        let parsed = match kind {
+            CLIENT_HELLO => ClientHello::parse(body).map(TLSHandshake::ClientHello),
+            SERVER_HELLO => ServerHello::parse(body).map(TLSHandshake::ServerHello),
+            CERTIFICATE => Self::certificates(body).map(TLSHandshake::Certificate),
+            _ => None,
+        };
+        parsed.unwrap_or(TLSHandshake::Other { kind, len: body.len() })
+    }
+
+    fn certificates(body: &[u8]) -> Option<Vec<Certificate>> {
+        let mut list = Cursor(Cursor(body).vec24()?);
+        let mut certificates = vec![];
+        while !list.0.is_empty() {
+            if let Some(certificate) = Certificate::parse(list.vec24()?) {
+                certificates.push(certificate);
+            }
+        }
+        Some(certificates)
+    }
+
+    /// Handshake messages of the record
+    pub fn handshakes(&self) -> &[TLSHandshake] {
+        match &self.message {
+            TLSMessage::Handshake(messages) => messages,
+            _ => &[],
+        }
+    }
+}
+
+impl ClientHello {
+    fn parse(body: &[u8]) -> Option<ClientHello> {
+        let mut body = Cursor(body);
+        let version = body.u16()?;
+        let random = body.bytes(32)?.try_into().unwrap();
+        let session_id = body.vec8()?.to_vec();
+        let cipher_suites = Cursor(body.vec16()?).u16s();
+        let compression_methods = body.vec8()?.to_vec();
+        let extensions = extensions(&mut body)?;
+        let mut hello = ClientHello {
+            version,
+            random,
+            session_id,
+            cipher_suites,
+            compression_methods,
+            extensions,
+            sni: None,
+            alpn: vec![],
+            supported_versions: vec![],
+            supported_groups: vec![],
+            ec_point_formats: vec![],
+            signature_algorithms: vec![],
+        };
+        for extension in &hello.extensions {
+            let mut data = Cursor(&extension.data);
+            match extension.kind {
+                SERVER_NAME => {
+                    let mut names = Cursor(data.vec16()?);
+                    while !names.0.is_empty() {
+                        let kind = names.u8()?;
+                        let name = names.vec16()?;
+                        // host_name
+                        if kind == 0 {
+                            hello.sni = Some(String::from_utf8_lossy(name).into_owned());
+                        }
+                    }
+                }
+                ALPN => hello.alpn = alpn(&mut data)?,
+                SUPPORTED_VERSIONS => hello.supported_versions = Cursor(data.vec8()?).u16s(),
+                SUPPORTED_GROUPS => hello.supported_groups = Cursor(data.vec16()?).u16s(),
+                EC_POINT_FORMATS => hello.ec_point_formats = data.vec8()?.to_vec(),
+                SIGNATURE_ALGORITHMS => hello.signature_algorithms = Cursor(data.vec16()?).u16s(),
+                _ => {}
+            }
+        }
+        Some(hello)
+    }
+
+    /// JA3 string: version, ciphers, extensions, groups and point formats,
+    /// GREASE values left out
+    pub fn ja3_string(&self) -> String {
+        let extensions = self.extensions.iter().map(|e| e.kind).collect::<Vec<_>>();
+        format!("{},{},{},{},{}",
+                self.version,
+                ja3_list(&self.cipher_suites),
+                ja3_list(&extensions),
+                ja3_list(&self.supported_groups),
+                fmt_iter!(self.ec_point_formats, "-"))
+    }
+
+    /// MD5 of [`ClientHello::ja3_string`]
+    pub fn ja3(&self) -> String {
+        to_hex(&md5(self.ja3_string().as_bytes()))
+    }
+
+    /// JA4 over TCP, e.g. `t13d1516h2_8daaf6152771_e5627efa2ab1`
+    pub fn ja4(&self) -> String {
+        let version = self.supported_versions.iter().copied()
+            .filter(|&v| !is_grease(v))
+            .max()
+            .unwrap_or(self.version);
+        let version = match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            0x0002 => "s2",
+            _ => "00",
+        };
+        let ciphers = self.cipher_suites.iter().copied()
+            .filter(|&c| !is_grease(c))
+            .collect::<Vec<_>>();
+        let extensions = self.extensions.iter().map(|e| e.kind)
+            .filter(|&e| !is_grease(e))
+            .collect::<Vec<_>>();
+        let alpn = match self.alpn.first().map(|a| a.as_bytes()) {
+            Some(alpn) if !alpn.is_empty() => {
+                let (first, last) = (alpn[0], alpn[alpn.len() - 1]);
+                if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                    format!("{}{}", first as char, last as char)
+                } else {
+                    let first = format!("{:02x}", first);
+                    let last = format!("{:02x}", last);
+                    format!("{}{}", &first[..1], &last[1..])
+                }
+            }
+            _ => "00".to_string(),
+        };
+        let a = format!("t{}{}{:02}{:02}{}",
+                        version,
+                        if self.sni.is_some() { 'd' } else { 'i' },
+                        ciphers.len().min(99),
+                        extensions.len().min(99),
+                        alpn);
+        let mut ciphers = ciphers;
+        ciphers.sort_unstable();
+        let mut sorted = extensions.into_iter()
+            .filter(|&e| e != SERVER_NAME && e != ALPN)
+            .collect::<Vec<_>>();
+        sorted.sort_unstable();
+        let mut c = fmt_iter!(sorted, ",", "{:04x}");
+        if !self.signature_algorithms.is_empty() {
+            c = format!("{}_{}", c, fmt_iter!(self.signature_algorithms, ",", "{:04x}"));
+        }
+        format!("{}_{}_{}", a, ja4_hash(&fmt_iter!(ciphers, ",", "{:04x}")), ja4_hash(&c))
+    }
+}
+
+impl ServerHello {
+    fn parse(body: &[u8]) -> Option<ServerHello> {
+        let mut body = Cursor(body);
+        let mut hello = ServerHello {
+            version: body.u16()?,
+            random: body.bytes(32)?.try_into().unwrap(),
+            session_id: body.vec8()?.to_vec(),
+            cipher_suite: body.u16()?,
+            compression_method: body.u8()?,
+            extensions: extensions(&mut body)?,
+            alpn: None,
+            supported_version: None,
+        };
+        for extension in &hello.extensions {
+            let mut data = Cursor(&extension.data);
+            match extension.kind {
+                ALPN => hello.alpn = alpn(&mut data)?.into_iter().next(),
+                SUPPORTED_VERSIONS => hello.supported_version = Some(data.u16()?),
+                _ => {}
+            }
+        }
+        Some(hello)
+    }
+
+    /// JA3S string: version, cipher and extensions
+    pub fn ja3s_string(&self) -> String {
+        let extensions = self.extensions.iter().map(|e| e.kind).collect::<Vec<_>>();
+        format!("{},{},{}", self.version, self.cipher_suite, ja3_list(&extensions))
+    }
+
+    /// MD5 of [`ServerHello::ja3s_string`]
+    pub fn ja3s(&self) -> String {
+        to_hex(&md5(self.ja3s_string().as_bytes()))
+    }
+}
+
+/// Reserved values of the form `0x?a?a` (RFC 8701) sent to keep peers tolerant
+pub fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 12 == (value >> 4) & 0xf
+}
+
+fn ja3_list(values: &[u16]) -> String {
+    let values = values.iter().filter(|&&v| !is_grease(v)).collect::<Vec<_>>();
+    fmt_iter!(values, "-")
+}
+
+/// First 12 hexadecimal digits of the SHA-256, zeros for an empty list
+fn ja4_hash(list: &str) -> String {
+    if list.is_empty() {
+        return "0".repeat(12);
+    }
+    to_hex(&sha256(list.as_bytes()))[..12].to_string()
+}
+
+/// Extension block, absent in old hellos
+fn extensions(body: &mut Cursor) -> Option<Vec<TLSExtension>> {
+    if body.0.is_empty() {
+        return Some(vec![]);
+    }
+    let mut block = Cursor(body.vec16()?);
+    let mut extensions = vec![];
+    while !block.0.is_empty() {
+        extensions.push(TLSExtension {
+            kind: block.u16()?,
+            data: block.vec16()?.to_vec(),
+        });
+    }
+    Some(extensions)
+}
+
+fn alpn(data: &mut Cursor) -> Option<Vec<String>> {
+    let mut list = Cursor(data.vec16()?);
+    let mut protocols = vec![];
+    while !list.0.is_empty() {
+        protocols.push(String::from_utf8_lossy(list.vec8()?).into_owned());
+    }
+    Some(protocols)
+}
+
+/// Reads big endian fields and length prefixed vectors
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.0.get(..len)?;
+        self.0 = &self.0[len..];
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(NetworkEndian::read_u16(self.bytes(2)?))
+    }
+
+    fn vec8(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()? as usize;
+        self.bytes(len)
+    }
+
+    fn vec16(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()? as usize;
+        self.bytes(len)
+    }
+
+    fn vec24(&mut self) -> Option<&'a [u8]> {
+        let len = NetworkEndian::read_u24(self.bytes(3)?) as usize;
+        self.bytes(len)
+    }
+
+    fn u16s(&mut self) -> Vec<u16> {
+        let mut values = vec![];
+        while let Some(value) = self.u16() {
+            values.push(value);
+        }
+        values
+    }
+}
@@ -4,16 +4,23 @@ use byteorder::{ByteOrder, NetworkEndian};
 
 #[derive(Layer)]
 pub struct TPKT {
+    #[layer(offset = 0)]
     version: u8,
+    #[layer(offset = 1)]
     reserved: u8,
+    #[layer(offset = 2)]
     len: u16,
 }
 
 impl TPKT {
+    /// RFC 1006 version
+    const VERSION: u8 = 3;
+
     pub fn try_make(ctx: &TCPSequence) -> Option<TPKT> {
         let data = &ctx.data;
         let version = *data.get(0)?;
         let reserved = *data.get(1)?;
+        if version != Self::VERSION || reserved != 0 { return None; }
         let len = NetworkEndian::read_u16(data.get(2..4)?);
         let tpkt = TPKT {
             version,
@@ -1,15 +1,23 @@
-use byteorder::{ByteOrder, NetworkEndian};
-
 use crate::*;
+use crate::dhcpv6::DHCPv6;
+use crate::dns::DNS;
 
-#[derive(Debug, Layer)]
+#[derive(Debug, Layer, Header)]
 pub struct UDP {
-    src: u16,
-    dst: u16,
+    #[layer(offset = 0)]
+    pub src: u16,
+    #[layer(offset = 2)]
+    pub dst: u16,
+    #[layer(offset = 4)]
     len: u16,
+    #[layer(offset = 6, format = "hex")]
     checksum: u16,
+    /// Not a field, payloads would be copied on every filter evaluation
+    #[layer(skip)]
+    #[header(skip)]
     pub payload: Vec<u8>,
 
+    #[header(skip)]
     layers: Layers,
 }
 
@@ -20,19 +28,32 @@ impl HasLayers for UDP {
 }
 
 impl UDP {
-    pub fn new(data: &[u8]) -> UDP {
-        let mut layers = Layers::default();
-        if let Some(dhcp) = DHCP::try_make(data.get(8..).unwrap()) {
-            layers.insert(dhcp);
+    pub fn try_make(data: &[u8], ip: &impl IP, ctx: &mut DissectionContext) -> Option<UDP> {
+        let mut udp = UDP::parse(data)?;
+        udp.payload = data.get(UDP::SIZE..)?.to_vec();
+        let flow = format!("udp {:?} {} -> {:?} {}", ip.src(), udp.src, ip.dst(), udp.dst);
+        let candidates = match ctx.apps.get(&flow, ctx.ts) {
+            Some(app) => vec![app],
+            None => ctx.registry.candidates(Transport::UDP, udp.src, udp.dst),
+        };
+        for app in candidates {
+            if udp.try_app(app) {
+                ctx.apps.insert(&flow, app, ctx.ts);
+                break;
+            }
         }
-        UDP {
-            src: NetworkEndian::read_u16(data.get(..2).unwrap()),
-            dst: NetworkEndian::read_u16(data.get(2..4).unwrap()),
-            len: NetworkEndian::read_u16(data.get(4..6).unwrap()),
-            checksum: NetworkEndian::read_u16(data.get(6..8).unwrap()),
-            payload: data.get(8..).unwrap().to_vec(),
-            layers,
+        Some(udp)
+    }
+
+    fn try_app(&mut self, app: App) -> bool {
+        match app {
+            App::DHCP => DHCP::try_make(&self.payload)
+                .map(|dhcp| self.layers.insert(dhcp)).is_some(),
+            App::DHCPv6 => DHCPv6::try_make(&self.payload)
+                .map(|dhcpv6| self.layers.insert(dhcpv6)).is_some(),
+            App::DNS => DNS::try_make(&self.payload)
+                .map(|dns| self.layers.insert(dns)).is_some(),
+            App::HTTP | App::HTTP2 | App::TLS | App::OpcUa | App::TPKT => false,
         }
     }
 }
-
@@ -0,0 +1,135 @@
+/// Fields of an X.509 certificate (RFC 5280) shown by the TLS dissector
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Certificate {
+    /// Hexadecimal serial number
+    pub serial: String,
+    /// Distinguished names, e.g. `CN=example.com, O=Example`
+    pub subject: String,
+    pub issuer: String,
+    /// Validity as written, `YYMMDDhhmmssZ` (UTCTime) or `YYYYMMDDhhmmssZ`
+    pub not_before: String,
+    pub not_after: String,
+}
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const INTEGER: u8 = 0x02;
+const OID: u8 = 0x06;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+/// `[0] EXPLICIT` version of the TBS certificate
+const VERSION: u8 = 0xa0;
+
+/// DER element: tag, content and the bytes after it
+fn element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)?;
+    let (len, header) = if first & 0x80 == 0 {
+        (first as usize, 2)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = data.get(2..2 + count)?.iter()
+            .fold(0usize, |len, &b| len << 8 | b as usize);
+        (len, 2 + count)
+    };
+    let content = data.get(header..header + len)?;
+    Some((tag, content, &data[header + len..]))
+}
+
+/// Content of an element with the expected tag
+fn expect(data: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    match element(data)? {
+        (t, content, rest) if t == tag => Some((content, rest)),
+        _ => None,
+    }
+}
+
+fn attribute_name(oid: &[u8]) -> String {
+    match oid {
+        [0x55, 0x04, 0x03] => "CN".to_string(),
+        [0x55, 0x04, 0x05] => "serialNumber".to_string(),
+        [0x55, 0x04, 0x06] => "C".to_string(),
+        [0x55, 0x04, 0x07] => "L".to_string(),
+        [0x55, 0x04, 0x08] => "ST".to_string(),
+        [0x55, 0x04, 0x0a] => "O".to_string(),
+        [0x55, 0x04, 0x0b] => "OU".to_string(),
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01] => "emailAddress".to_string(),
+        _ => fmt_oid(oid),
+    }
+}
+
+/// Dotted notation of an encoded object identifier
+fn fmt_oid(oid: &[u8]) -> String {
+    let mut arcs = vec![];
+    let mut arc = 0u64;
+    for &b in oid {
+        arc = arc << 7 | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (arc / 40).min(2);
+                arcs.push(first);
+                arcs.push(arc - first * 40);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        }
+    }
+    arcs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(".")
+}
+
+/// `Name` as comma separated `attribute=value` pairs
+fn name(mut data: &[u8]) -> Option<String> {
+    let mut parts = vec![];
+    while !data.is_empty() {
+        let (set, rest) = expect(data, SET)?;
+        data = rest;
+        let mut set = set;
+        while !set.is_empty() {
+            let (pair, rest) = expect(set, SEQUENCE)?;
+            set = rest;
+            let (oid, value) = expect(pair, OID)?;
+            let (_, value, _) = element(value)?;
+            parts.push(format!("{}={}", attribute_name(oid), String::from_utf8_lossy(value)));
+        }
+    }
+    Some(parts.join(", "))
+}
+
+fn time(data: &[u8]) -> Option<(String, &[u8])> {
+    match element(data)? {
+        (UTC_TIME | GENERALIZED_TIME, content, rest) => {
+            Some((String::from_utf8_lossy(content).into_owned(), rest))
+        }
+        _ => None,
+    }
+}
+
+impl Certificate {
+    /// Parse a DER encoded certificate, `None` if malformed
+    pub fn parse(der: &[u8]) -> Option<Certificate> {
+        let (certificate, _) = expect(der, SEQUENCE)?;
+        let (tbs, _) = expect(certificate, SEQUENCE)?;
+        let tbs = match expect(tbs, VERSION) {
+            Some((_, rest)) => rest,
+            None => tbs,
+        };
+        let (serial, tbs) = expect(tbs, INTEGER)?;
+        let (_signature, tbs) = expect(tbs, SEQUENCE)?;
+        let (issuer, tbs) = expect(tbs, SEQUENCE)?;
+        let (validity, tbs) = expect(tbs, SEQUENCE)?;
+        let (subject, _) = expect(tbs, SEQUENCE)?;
+        let (not_before, validity) = time(validity)?;
+        let (not_after, _) = time(validity)?;
+        Some(Certificate {
+            serial: serial.iter().map(|b| format!("{:02x}", b)).collect(),
+            subject: name(subject)?,
+            issuer: name(issuer)?,
+            not_before,
+            not_after,
+        })
+    }
+}
@@ -0,0 +1,21 @@
+//! Digests used to identify extracted objects and fingerprints
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::fmt_iter;
+
+/// SHA-256 (FIPS 180-4)
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// Lowercase hexadecimal digest
+pub fn to_hex(digest: &[u8]) -> String {
+    fmt_iter!(digest, "", "{:02x}")
+}
+
+/// MD5 (RFC 1321), only for fingerprints that are defined with it
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    Md5::digest(data).into()
+}
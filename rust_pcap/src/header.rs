@@ -0,0 +1,51 @@
+/// Fixed-layout protocol header, usually implemented by `#[derive(Header)]`
+pub trait Header: Sized {
+    /// Size of the header in bits
+    const BITS: usize;
+    /// Size of the header in whole bytes
+    const SIZE: usize = Self::BITS.div_ceil(8);
+
+    /// Reads the header starting `offset` bits into `data`, which must be
+    /// long enough
+    fn read(data: &[u8], offset: usize) -> Self;
+    /// Writes the header starting `offset` bits into `out`, which must be
+    /// long enough
+    fn write_bits(&self, out: &mut [u8], offset: usize);
+
+    /// Reads the header from the start of `data`, `None` if it is too short
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < Self::SIZE { return None; }
+        Some(Self::read(data, 0))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![0; Self::SIZE];
+        self.write_bits(&mut out, 0);
+        out
+    }
+}
+
+/// Big endian (MSB first) read of `bits <= 64` bits at bit `offset`
+pub fn read_bits(data: &[u8], offset: usize, bits: usize) -> u64 {
+    if offset.is_multiple_of(8) && bits.is_multiple_of(8) {
+        let start = offset / 8;
+        return data[start..start + bits / 8].iter()
+            .fold(0, |acc, &b| acc << 8 | b as u64);
+    }
+    (offset..offset + bits).fold(0, |acc, i| {
+        acc << 1 | ((data[i / 8] >> (7 - i % 8)) & 1) as u64
+    })
+}
+
+/// Big endian (MSB first) write of the low `bits` of `value` at bit `offset`
+pub fn write_bits(out: &mut [u8], offset: usize, bits: usize, value: u64) {
+    for (n, i) in (offset..offset + bits).enumerate() {
+        let bit = (value >> (bits - 1 - n)) & 1;
+        let mask = 1 << (7 - i % 8);
+        if bit == 1 {
+            out[i / 8] |= mask;
+        } else {
+            out[i / 8] &= !mask;
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use serde::Serialize;
+
+use crate::*;
+use crate::rtt::Connection;
+
+/// One line of a Zeek-like `http.log`, written when the response completes
+#[derive(Debug, Clone, Serialize)]
+pub struct HTTPLogEntry {
+    /// Timestamp of the request
+    pub ts: f64,
+    pub client: String,
+    pub client_port: u16,
+    pub server: String,
+    pub server_port: u16,
+    pub method: String,
+    pub host: Option<String>,
+    pub uri: String,
+    pub status: u16,
+    pub user_agent: Option<String>,
+    /// Content type of the response
+    pub content_type: Option<String>,
+    pub request_body_len: usize,
+    pub response_body_len: usize,
+    /// Seconds between the request and the response
+    pub latency: f64,
+}
+
+impl HTTPLogEntry {
+    /// Entries for the responses of `frame` paired with their request,
+    /// requests without a response are not logged
+    pub fn from_frame(frame: &Frame) -> Vec<HTTPLogEntry> {
+        let (ip, tcp) = match (frame.get_layer::<IPv4>(), frame.get_layer::<TCP>()) {
+            (Some(ip), Some(tcp)) => (ip, tcp),
+            _ => return vec![],
+        };
+        // responses flow from the server to the client
+        let connection = Connection {
+            client: ip.dst,
+            client_port: tcp.dst,
+            server: ip.src,
+            server_port: tcp.src,
+        };
+        tcp.layers().get_all::<HTTP>()
+            .filter_map(|http| match (&http.kind, &http.request) {
+                (HTTPKind::Res { status, .. }, Some(request)) => {
+                    Some(Self::new(frame.ts, &connection, request, *status, http))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn new(ts: f64, connection: &Connection, request: &HTTPRequest, status: u16, response: &HTTP) -> Self {
+        HTTPLogEntry {
+            ts: request.ts,
+            client: fmt_iter!(connection.client, "."),
+            client_port: connection.client_port,
+            server: fmt_iter!(connection.server, "."),
+            server_port: connection.server_port,
+            method: request.method.clone(),
+            host: request.header("Host").map(to_string),
+            uri: request.uri.clone(),
+            status,
+            user_agent: request.header("User-Agent").map(to_string),
+            content_type: response.header("Content-Type").map(to_string),
+            request_body_len: request.body_len,
+            response_body_len: response.body_len,
+            latency: ts - request.ts,
+        }
+    }
+
+    /// The entry as a JSON object on a single line, non-finite numbers
+    /// are `null`
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
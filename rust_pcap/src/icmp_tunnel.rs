@@ -0,0 +1,307 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::*;
+
+#[derive(Debug, Clone)]
+pub struct ICMPTunnelConfig {
+    /// Echo messages of a session before its payloads are judged
+    pub min_packets: usize,
+    /// Shannon entropy in bits per byte of the session payloads, OS pings
+    /// stay below 6
+    pub entropy: f64,
+    /// Distinct payload sizes in a session, OS pings use one
+    pub distinct_sizes: usize,
+    /// Replies whose payload differs from the request
+    pub mismatches: usize,
+    /// Payload bytes per second, in both directions
+    pub volume_rate: f64,
+    /// Seconds the rate must be sustained, the rate is measured over the
+    /// latest ones
+    pub volume_duration: f64,
+}
+
+impl Default for ICMPTunnelConfig {
+    fn default() -> Self {
+        Self {
+            min_packets: 10,
+            entropy: 7.0,
+            distinct_sizes: 5,
+            mismatches: 3,
+            volume_rate: 5000.0,
+            volume_duration: 10.0,
+        }
+    }
+}
+
+/// Echo requests from `client` to `server` with one identifier, and their
+/// replies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EchoSession {
+    pub client: [u8; 4],
+    pub server: [u8; 4],
+    pub id: u16,
+}
+
+impl std::fmt::Display for EchoSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> {} id {}", fmt_iter!(self.client, "."), fmt_iter!(self.server, "."), self.id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TunnelIndicator {
+    /// Random looking payloads, e.g. encrypted or compressed data
+    HighEntropy,
+    /// Payload sizes vary with the carried data
+    SizeVariance,
+    /// Payloads unlike the ones of Windows, Linux or BSD ping
+    NonStandardPayload,
+    /// Replies that do not echo the request data
+    PayloadMismatch,
+    /// Sustained high payload rate
+    HighVolume,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ICMPTunnelAlert {
+    pub ts: f64,
+    pub session: EchoSession,
+    pub indicator: TunnelIndicator,
+    /// Entropy, distinct sizes, non-standard payloads, mismatches or bytes
+    /// per second
+    pub value: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionStats {
+    pub first_seen: f64,
+    pub last_seen: f64,
+    pub requests: usize,
+    pub replies: usize,
+    /// Payload bytes in both directions
+    pub bytes: usize,
+    pub sizes: HashSet<usize>,
+    pub non_standard: usize,
+    pub mismatches: usize,
+    /// Indicators raised, in order
+    pub indicators: Vec<TunnelIndicator>,
+    histogram: [usize; 256],
+    /// Timestamp and payload size of the messages within the volume window
+    recent: VecDeque<(f64, usize)>,
+    recent_bytes: usize,
+}
+
+impl SessionStats {
+    fn new(ts: f64) -> Self {
+        SessionStats {
+            first_seen: ts,
+            last_seen: ts,
+            requests: 0,
+            replies: 0,
+            bytes: 0,
+            sizes: HashSet::new(),
+            non_standard: 0,
+            mismatches: 0,
+            indicators: vec![],
+            histogram: [0; 256],
+            recent: VecDeque::new(),
+            recent_bytes: 0,
+        }
+    }
+
+    /// Shannon entropy of the payload bytes, in bits per byte
+    pub fn entropy(&self) -> f64 {
+        let total = self.bytes as f64;
+        self.histogram.iter()
+            .filter(|&&n| n > 0)
+            .map(|&n| {
+                let p = n as f64 / total;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    /// Payload bytes per second over the session
+    pub fn rate(&self) -> f64 {
+        let duration = self.last_seen - self.first_seen;
+        if duration > 0.0 { self.bytes as f64 / duration } else { 0.0 }
+    }
+
+    /// Payload bytes per second over the latest `window` seconds
+    pub fn recent_rate(&self, window: f64) -> f64 {
+        if window > 0.0 { self.recent_bytes as f64 / window } else { 0.0 }
+    }
+
+    fn slide(&mut self, ts: f64, len: usize, window: f64) {
+        self.recent.push_back((ts, len));
+        self.recent_bytes += len;
+        while let Some(&(t, len)) = self.recent.front() {
+            if ts - t <= window {
+                break;
+            }
+            self.recent.pop_front();
+            self.recent_bytes -= len;
+        }
+    }
+
+    pub fn is_suspect(&self) -> bool {
+        !self.indicators.is_empty()
+    }
+}
+
+/// Detects data carried over ICMP echo: high entropy or variable size
+/// payloads, payloads unlike OS defaults, replies not echoing requests and
+/// sustained high volume
+#[derive(Debug, Default)]
+pub struct ICMPTunnelDetector {
+    pub config: ICMPTunnelConfig,
+    sessions: HashMap<EchoSession, SessionStats>,
+    /// Request time and payload hash of unanswered requests by sequence
+    pending: HashMap<(EchoSession, u16), (f64, u64)>,
+    last_sweep: f64,
+}
+
+impl ICMPTunnelDetector {
+    /// Entropy is not judged on fewer bytes, short samples look ordered
+    const ENTROPY_MIN_BYTES: usize = 512;
+    /// Seconds a request waits for its reply
+    const TIMEOUT: f64 = 10.0;
+    /// Upper bound of requests waiting for a reply
+    const MAX_PENDING: usize = 65536;
+    /// Seconds of silence after which a session is forgotten
+    const SESSION_TIMEOUT: f64 = 300.0;
+    /// Upper bound of tracked sessions, the least recently seen one makes
+    /// room for a new one
+    const MAX_SESSIONS: usize = 4096;
+    const SWEEP_PERIOD: f64 = 1.0;
+
+    pub fn new(config: ICMPTunnelConfig) -> Self {
+        Self { config, ..default() }
+    }
+
+    pub fn sessions(&self) -> impl Iterator<Item=(&EchoSession, &SessionStats)> {
+        self.sessions.iter()
+    }
+
+    /// Sessions with at least one indicator raised
+    pub fn suspects(&self) -> impl Iterator<Item=(&EchoSession, &SessionStats)> {
+        self.sessions().filter(|(_, stats)| stats.is_suspect())
+    }
+
+    pub fn apply(&mut self, frame: &Frame) -> Vec<ICMPTunnelAlert> {
+        self.sweep(frame.ts);
+        let (ip, icmp) = match (frame.get_layer::<IPv4>(), frame.get_layer::<ICMP>()) {
+            (Some(ip), Some(icmp)) => (ip, icmp),
+            _ => return vec![],
+        };
+        let (id, seq, kind, payload) = match &icmp.data {
+            ICMPData::Echo { id, num, kind, data } => (*id, *num, kind, data),
+            _ => return vec![],
+        };
+        let request = matches!(kind, Echo::Request);
+        let session = if request {
+            EchoSession { client: ip.src, server: ip.dst, id }
+        } else {
+            EchoSession { client: ip.dst, server: ip.src, id }
+        };
+        let mut hasher = DefaultHasher::new();
+        payload.hash(&mut hasher);
+        let digest = hasher.finish();
+        let mut mismatch = false;
+        if request {
+            if self.pending.len() < Self::MAX_PENDING {
+                self.pending.insert((session, seq), (frame.ts, digest));
+            }
+        } else if let Some((_, expected)) = self.pending.remove(&(session, seq)) {
+            mismatch = expected != digest;
+        }
+
+        if !self.sessions.contains_key(&session) && self.sessions.len() >= Self::MAX_SESSIONS {
+            self.evict();
+        }
+        let stats = self.sessions.entry(session).or_insert_with(|| SessionStats::new(frame.ts));
+        stats.last_seen = frame.ts;
+        if request { stats.requests += 1 } else { stats.replies += 1 }
+        stats.bytes += payload.len();
+        stats.slide(frame.ts, payload.len(), self.config.volume_duration);
+        stats.sizes.insert(payload.len());
+        payload.iter().for_each(|&b| stats.histogram[b as usize] += 1);
+        if !is_os_default(payload) { stats.non_standard += 1 }
+        if mismatch { stats.mismatches += 1 }
+
+        let config = &self.config;
+        let judged = stats.requests + stats.replies >= config.min_packets;
+        let mut raised = vec![];
+        if judged && stats.bytes >= Self::ENTROPY_MIN_BYTES && stats.entropy() >= config.entropy {
+            raised.push((TunnelIndicator::HighEntropy, stats.entropy()));
+        }
+        if judged && stats.sizes.len() >= config.distinct_sizes {
+            raised.push((TunnelIndicator::SizeVariance, stats.sizes.len() as f64));
+        }
+        if judged && stats.non_standard >= config.min_packets {
+            raised.push((TunnelIndicator::NonStandardPayload, stats.non_standard as f64));
+        }
+        if stats.mismatches >= config.mismatches {
+            raised.push((TunnelIndicator::PayloadMismatch, stats.mismatches as f64));
+        }
+        let rate = stats.recent_rate(config.volume_duration);
+        if stats.last_seen - stats.first_seen >= config.volume_duration && rate >= config.volume_rate {
+            raised.push((TunnelIndicator::HighVolume, rate));
+        }
+        raised.retain(|(indicator, _)| !stats.indicators.contains(indicator));
+        stats.indicators.extend(raised.iter().map(|&(indicator, _)| indicator));
+        raised.into_iter()
+            .map(|(indicator, value)| ICMPTunnelAlert { ts: frame.ts, session, indicator, value })
+            .collect()
+    }
+
+    /// Forgets requests whose reply never came and idle sessions
+    fn sweep(&mut self, ts: f64) {
+        if ts - self.last_sweep < Self::SWEEP_PERIOD {
+            return;
+        }
+        self.last_sweep = ts;
+        self.pending.retain(|_, &mut (sent, _)| ts - sent <= Self::TIMEOUT);
+        self.sessions.retain(|_, stats| ts - stats.last_seen <= Self::SESSION_TIMEOUT);
+    }
+
+    fn evict(&mut self) {
+        let oldest = self.sessions.iter()
+            .min_by(|(_, a), (_, b)| a.last_seen.total_cmp(&b.last_seen))
+            .map(|(&session, _)| session);
+        if let Some(session) = oldest {
+            self.sessions.remove(&session);
+        }
+    }
+}
+
+/// Payload as filled by the system ping of Windows (the alphabet up to `w`,
+/// repeated), Linux and BSD (a timestamp then each byte set to its offset),
+/// or a single repeated byte (`ping -p`, zero filled tools)
+pub fn is_os_default(payload: &[u8]) -> bool {
+    let windows = payload.iter().enumerate().all(|(i, &b)| b == b'a' + (i % 23) as u8);
+    let fill = |from: usize| payload.iter().enumerate().skip(from).all(|(i, &b)| b == i as u8);
+    // a 32-bit timeval of 8 bytes, or a 64-bit one of 16 whose second half
+    // holds microseconds, little endian on common hosts
+    let usec = payload.get(8..16).map(LittleEndian::read_u64);
+    let unix = payload.len() >= 8 && (fill(8) || usec.is_some_and(|usec| usec < 1_000_000) && fill(16));
+    let constant = payload.windows(2).all(|w| w[0] == w[1]);
+    windows || unix || constant
+}
+
+impl std::fmt::Display for ICMPTunnelAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self.indicator {
+            TunnelIndicator::HighEntropy => format!("payload entropy {:.2} bits/byte", self.value),
+            TunnelIndicator::SizeVariance => format!("{} distinct payload sizes", self.value),
+            TunnelIndicator::NonStandardPayload => format!("{} non-standard payloads", self.value),
+            TunnelIndicator::PayloadMismatch => format!("{} replies not echoing the request", self.value),
+            TunnelIndicator::HighVolume => format!("payload rate {:.0} bytes/s", self.value),
+        };
+        write!(f, "{} possible ICMP tunnel {}: {}", self.ts, self.session, value)
+    }
+}
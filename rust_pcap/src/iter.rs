@@ -5,11 +5,13 @@ use pcap::{Packet, PacketCodec, PacketHeader};
 use pcap_parser::{Block, LegacyPcapReader, Linktype, PcapBlockOwned, PcapError, PcapNGReader};
 use pcap_parser::traits::{PcapNGPacketBlock, PcapReaderIterator};
 
-use crate::{default, DissectionContext, Frame};
+use crate::{default, DissectionContext, DissectionLimits, Frame, Registry};
+use crate::bpf::BpfFilter;
 
 pub struct Pcap {
     reader: LegacyPcapReader<File>,
     link_type: Linktype,
+    bpf: Option<BpfFilter>,
     ctx: DissectionContext,
 }
 
@@ -20,11 +22,38 @@ impl Pcap {
         Self {
             reader,
             link_type: Linktype::NULL,
+            bpf: None,
             ctx: default(),
         }
     }
 }
 
+impl Pcap {
+    pub fn with_limits(mut self, limits: DissectionLimits) -> Self {
+        self.ctx.limits = limits;
+        self
+    }
+
+    pub fn with_registry(mut self, registry: Registry) -> Self {
+        self.ctx.registry = registry;
+        self
+    }
+
+    /// Skip frames rejected by `bpf` before dissecting them
+    pub fn with_bpf(mut self, bpf: BpfFilter) -> Self {
+        self.bpf = Some(bpf);
+        self
+    }
+
+    pub fn bpf(&self) -> Option<&BpfFilter> {
+        self.bpf.as_ref()
+    }
+
+    pub fn context(&self) -> &DissectionContext {
+        &self.ctx
+    }
+}
+
 impl Iterator for Pcap {
     type Item = Frame;
 
@@ -35,12 +64,14 @@ impl Iterator for Pcap {
                 Ok((offset, block)) => {
                     match block {
                         PcapBlockOwned::LegacyHeader(hdr) => {
-                            println!("{:?}", hdr);
-                            println!("Pcap root is {}", hdr.network);
+                            eprintln!("{:?}", hdr);
+                            eprintln!("Pcap root is {}", hdr.network);
                             self.link_type = hdr.network;
                         }
                         PcapBlockOwned::Legacy(b) => {
-                            item = Some(Frame::from_legacy(&b, self.link_type, &mut self.ctx));
+                            if accept(&mut self.bpf, b.data, self.link_type) {
+                                item = Some(Frame::from_legacy(&b, self.link_type, &mut self.ctx));
+                            }
                         }
                         PcapBlockOwned::NG(_) => unreachable!(),
                     }
@@ -65,6 +96,7 @@ pub struct PcapNG {
     if_tsresol: u8,
     if_tsoffset: u64,
 
+    bpf: Option<BpfFilter>,
     ctx: DissectionContext,
 }
 
@@ -77,11 +109,38 @@ impl PcapNG {
             if_linktypes: vec![],
             if_tsresol: 0,
             if_tsoffset: 0,
+            bpf: None,
             ctx: default(),
         }
     }
 }
 
+impl PcapNG {
+    pub fn with_limits(mut self, limits: DissectionLimits) -> Self {
+        self.ctx.limits = limits;
+        self
+    }
+
+    pub fn with_registry(mut self, registry: Registry) -> Self {
+        self.ctx.registry = registry;
+        self
+    }
+
+    /// Skip frames rejected by `bpf` before dissecting them
+    pub fn with_bpf(mut self, bpf: BpfFilter) -> Self {
+        self.bpf = Some(bpf);
+        self
+    }
+
+    pub fn bpf(&self) -> Option<&BpfFilter> {
+        self.bpf.as_ref()
+    }
+
+    pub fn context(&self) -> &DissectionContext {
+        &self.ctx
+    }
+}
+
 impl Iterator for PcapNG {
     type Item = Frame;
 
@@ -105,11 +164,13 @@ impl Iterator for PcapNG {
                             let linktype = self.if_linktypes[epb.if_id as usize];
                             // let res = pcap_parser::data::get_packetdata(epb.data, linktype, epb.caplen as usize);
 
-                            item = Some(Frame::from_enhanced(
-                                epb, linktype,
-                                self.if_tsoffset, self.if_tsresol,
-                                &mut self.ctx,
-                            ));
+                            if accept(&mut self.bpf, epb.packet_data(), linktype) {
+                                item = Some(Frame::from_enhanced(
+                                    epb, linktype,
+                                    self.if_tsoffset, self.if_tsresol,
+                                    &mut self.ctx,
+                                ));
+                            }
                         }
                         PcapBlockOwned::NG(Block::SimplePacket(ref spb)) => {
                             assert!(self.if_linktypes.len() > 0);
@@ -117,14 +178,16 @@ impl Iterator for PcapNG {
                             // let blen = (spb.block_len1 - 16) as usize;
                             // let res = pcap_parser::data::get_packetdata(spb.data, linktype, blen);
 
-                            item = Some(Frame::new(
-                                spb.packet_data(),
-                                0.0,
-                                0,
-                                spb.orig_len(),
-                                linktype,
-                                &mut self.ctx,
-                            ));
+                            if accept(&mut self.bpf, spb.packet_data(), linktype) {
+                                item = Some(Frame::new(
+                                    spb.packet_data(),
+                                    0.0,
+                                    0,
+                                    spb.orig_len(),
+                                    linktype,
+                                    &mut self.ctx,
+                                ));
+                            }
                         }
                         PcapBlockOwned::NG(_) => {
                             // can be statistics (ISB), name resolution (NRB), etc.
@@ -152,12 +215,39 @@ impl Iterator for PcapNG {
 pub struct PcapIterator {
     packets: IntoIter<PacketOwned>,
 
+    bpf: Option<BpfFilter>,
     ctx: DissectionContext,
 }
 
 impl PcapIterator{
     pub fn new(packets: Vec<PacketOwned>) -> Self {
-        return Self { packets: packets.into_iter(), ctx: default() };
+        return Self { packets: packets.into_iter(), bpf: None, ctx: default() };
+    }
+}
+
+impl PcapIterator {
+    pub fn with_limits(mut self, limits: DissectionLimits) -> Self {
+        self.ctx.limits = limits;
+        self
+    }
+
+    pub fn with_registry(mut self, registry: Registry) -> Self {
+        self.ctx.registry = registry;
+        self
+    }
+
+    /// Skip frames rejected by `bpf` before dissecting them
+    pub fn with_bpf(mut self, bpf: BpfFilter) -> Self {
+        self.bpf = Some(bpf);
+        self
+    }
+
+    pub fn bpf(&self) -> Option<&BpfFilter> {
+        self.bpf.as_ref()
+    }
+
+    pub fn context(&self) -> &DissectionContext {
+        &self.ctx
     }
 }
 
@@ -165,10 +255,16 @@ impl Iterator for PcapIterator {
     type Item = Frame;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.packets.next().and_then(|pkt| Some(Frame::from_packed(pkt, &mut self.ctx)))
+        let pkt = self.packets
+            .find(|pkt| accept(&mut self.bpf, &pkt.data, Linktype::ETHERNET))?;
+        Some(Frame::from_packed(pkt, &mut self.ctx))
     }
 }
 
+fn accept(bpf: &mut Option<BpfFilter>, data: &[u8], link_type: Linktype) -> bool {
+    bpf.as_mut().is_none_or(|bpf| bpf.matches(data, link_type))
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PacketOwned {
     pub header: PacketHeader,
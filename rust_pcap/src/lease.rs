@@ -0,0 +1,111 @@
+use crate::*;
+
+/// Address assigned to a client by a DHCPACK
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lease {
+    /// Client hardware address
+    pub mac: [u8; 6],
+    pub ip: [u8; 4],
+    /// Server identifier, or the source of the ACK without one
+    pub server: [u8; 4],
+    pub hostname: Option<String>,
+    /// Timestamp of the first ACK
+    pub start: f64,
+    /// Timestamp of the latest ACK, renewals move it forward
+    pub renewed: f64,
+    /// End of the lease as granted by the latest ACK, or the release time
+    pub end: Option<f64>,
+    pub renewals: usize,
+    pub released: bool,
+}
+
+impl Lease {
+    pub fn is_active(&self, ts: f64) -> bool {
+        ts >= self.start && self.end.is_none_or(|end| ts < end)
+    }
+}
+
+/// Addresses assigned to each client MAC over time, from DHCPACK and
+/// DHCPRELEASE messages
+#[derive(Debug, Default)]
+pub struct LeaseTable {
+    leases: HashMap<[u8; 6], Vec<Lease>>,
+}
+
+impl LeaseTable {
+    /// Update the table with the DHCP message of the frame, returns the
+    /// lease it created or changed
+    pub fn apply(&mut self, frame: &Frame) -> Option<&Lease> {
+        let dhcp = frame.get_layer::<DHCP>()?;
+        let mac = dhcp.mac()?;
+        match dhcp.message_type? {
+            DHCP::ACK if dhcp.yiaddr != [0; 4] => {
+                let server = dhcp.server_id()
+                    .or_else(|| frame.get_layer::<IPv4>().map(|ip| ip.src))
+                    .unwrap_or_default();
+                let end = dhcp.lease_time()
+                    .filter(|&time| time != u32::MAX)
+                    .map(|time| frame.ts + time as f64);
+                let history = self.leases.entry(mac).or_default();
+                match history.last_mut() {
+                    Some(lease) if lease.ip == dhcp.yiaddr && !lease.released => {
+                        lease.renewed = frame.ts;
+                        lease.end = end;
+                        lease.renewals += 1;
+                        lease.hostname = dhcp.hostname().map(to_string).or(lease.hostname.take());
+                    }
+                    _ => history.push(Lease {
+                        mac,
+                        ip: dhcp.yiaddr,
+                        server,
+                        hostname: dhcp.hostname().map(to_string),
+                        start: frame.ts,
+                        renewed: frame.ts,
+                        end,
+                        renewals: 0,
+                        released: false,
+                    }),
+                }
+                history.last()
+            }
+            DHCP::RELEASE => {
+                let lease = self.leases.get_mut(&mac)?.iter_mut().rev()
+                    .find(|lease| lease.ip == dhcp.ciaddr && !lease.released)?;
+                lease.released = true;
+                lease.end = Some(frame.ts);
+                Some(lease)
+            }
+            _ => None,
+        }
+    }
+
+    /// Leases of `mac`, oldest first
+    pub fn history(&self, mac: &[u8; 6]) -> &[Lease] {
+        self.leases.get(mac).map_or(&[], |leases| leases.as_slice())
+    }
+
+    /// Lease held by `mac` at `ts`
+    pub fn current(&self, mac: &[u8; 6], ts: f64) -> Option<&Lease> {
+        self.history(mac).iter().rev().find(|lease| lease.is_active(ts))
+    }
+
+    /// Client holding `ip` at `ts`
+    pub fn holder(&self, ip: [u8; 4], ts: f64) -> Option<[u8; 6]> {
+        self.leases.iter()
+            .find(|(_, leases)| leases.iter().any(|l| l.ip == ip && l.is_active(ts)))
+            .map(|(&mac, _)| mac)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(&[u8; 6], &Lease)> {
+        self.leases.iter().flat_map(|(mac, leases)| leases.iter().map(move |l| (mac, l)))
+    }
+
+    /// Clients seen
+    pub fn len(&self) -> usize {
+        self.leases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leases.is_empty()
+    }
+}
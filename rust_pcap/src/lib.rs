@@ -1,31 +1,50 @@
 #![allow(dead_code)]
 
 
+use std::any::Any;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Formatter;
 use std::ops::{Range, RangeFrom, RangeTo};
 use std::path::Path;
-use std::ptr::NonNull;
 use std::slice::SliceIndex;
 
 use csv::ReaderBuilder;
 
 pub use analyze_derive::*;
+pub use field::*;
 pub use frame::*;
 pub use frame::arp::*;
 pub use frame::dhcp::*;
+pub use frame::dispatch::*;
 pub use frame::ethernet::*;
 pub use frame::http::*;
+pub use frame::http2::{HTTP2Connections, HTTP2Context};
 pub use frame::icmp::*;
 pub use frame::ip::*;
 pub use frame::tcp::*;
 pub use frame::udp::*;
+pub use header::*;
 pub use iter::*;
 
+mod field;
 mod frame;
+mod header;
 mod iter;
+pub mod arp_monitor;
+pub mod bpf;
 pub mod counter;
+pub mod dhcp_monitor;
+pub mod dns_log;
+pub mod echo;
+pub mod extract;
+pub mod filter;
+pub mod fingerprint;
+pub mod hash;
+pub mod http_log;
+pub mod icmp_tunnel;
+pub mod lease;
+pub mod rtt;
 pub mod tf;
 mod combo;
 
@@ -48,16 +67,6 @@ macro_rules! get_array {
     }
 }
 
-#[macro_export]
-macro_rules! get_layer_descendants {
-    ($self:expr, $target:ident, $($type:ident),+ $(,)?) => {
-        None
-        $(
-        .or_else(|| get_layer::<_, $type>($self).and_then(|l| { l.get_layer::<$target>() }))
-        )+
-    }
-}
-
 #[macro_export]
 macro_rules! fmt_iter {
     ($iter:expr, $separator:expr, $fmt:tt) => {
@@ -71,40 +80,97 @@ macro_rules! fmt_iter {
     }
 }
 
-pub trait Layer {
+pub trait Layer: Any {
     fn name() -> &'static str where Self: Sized;
-    fn as_ptr(&self) -> NonNull<usize> {
-        NonNull::from(self).cast::<usize>()
+    /// [`Layer::name`] of the concrete type behind a `dyn Layer`
+    fn layer_name(&self) -> &'static str;
+    /// Short protocol name as used in protocol paths, e.g. `eth`
+    fn abbrev(&self) -> &'static str;
+    fn as_any(&self) -> &dyn Any;
+    fn children(&self) -> Option<&Layers> { None }
+    fn field_info(&self) -> &'static [FieldInfo];
+    fn fields(&self) -> Vec<Field>;
+    fn field(&self, name: &str) -> Option<Field> {
+        self.fields().into_iter().find(|f| f.name() == name)
     }
 }
 
-#[derive(Default, derive_more::Deref, derive_more::DerefMut)]
-pub struct Layers(HashMap<String, Box<dyn Layer>>);
+/// Child layers in protocol-stack order, a type may appear several times
+/// (stacked VLAN tags, tunnels)
+#[derive(Default, derive_more::Deref)]
+pub struct Layers(Vec<Box<dyn Layer>>);
 
 impl Layers {
-    pub fn insert<T: Layer + 'static>(&mut self, layer: T) {
-        self.0.insert(T::name().to_string(), Box::new(layer));
+    pub fn insert<T: Layer>(&mut self, layer: T) {
+        self.0.push(Box::new(layer));
+    }
+
+    /// First (outermost) layer of type `T`
+    pub fn get<T: Layer>(&self) -> Option<&T> {
+        self.get_all::<T>().next()
+    }
+
+    /// Every layer of type `T` in stack order
+    pub fn get_all<T: Layer>(&self) -> impl Iterator<Item=&T> {
+        self.0.iter().filter_map(|l| l.as_any().downcast_ref::<T>())
     }
 }
 
 impl std::fmt::Debug for Layers {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self.0.iter().map(|(e, _)| e.clone()).collect::<Vec<String>>())
+        write!(f, "{:?}", self.0.iter().map(|l| l.layer_name()).collect::<Vec<_>>())
     }
 }
 
 pub trait HasLayers {
     fn layers(&self) -> &Layers;
-    fn get_layer_descendants<T>(&self) -> Option<&T> where T: Layer { None }
 }
 
 pub trait GetLayers {
+    /// First layer of type `T` in depth-first order
     fn get_layer<T>(&self) -> Option<&T> where T: Layer;
+    /// Every nested layer in depth-first (protocol-stack) order
+    fn layers_iter(&self) -> LayersIter<'_>;
+    /// Abbreviations of the nested layers, e.g. `eth:vlan:ip:tcp:http`
+    fn protocol_path(&self) -> String;
 }
 
 impl<L: Layer + HasLayers> GetLayers for L {
     fn get_layer<T>(&self) -> Option<&T> where T: Layer {
-        get_layer(self).or_else(|| self.get_layer_descendants::<T>())
+        self.layers_iter().find_map(|l| l.as_any().downcast_ref::<T>())
+    }
+
+    fn layers_iter(&self) -> LayersIter<'_> {
+        LayersIter::new(self.layers())
+    }
+
+    fn protocol_path(&self) -> String {
+        self.layers_iter()
+            .map(|l| l.abbrev())
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+}
+
+pub struct LayersIter<'a> {
+    stack: Vec<&'a dyn Layer>,
+}
+
+impl<'a> LayersIter<'a> {
+    pub fn new(layers: &'a Layers) -> Self {
+        Self { stack: layers.iter().rev().map(|l| l.as_ref()).collect() }
+    }
+}
+
+impl<'a> Iterator for LayersIter<'a> {
+    type Item = &'a dyn Layer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let layer = self.stack.pop()?;
+        if let Some(children) = layer.children() {
+            self.stack.extend(children.iter().rev().map(|l| l.as_ref()));
+        }
+        Some(layer)
     }
 }
 
@@ -113,12 +179,7 @@ pub fn get_layer<O, T>(origin: &O) -> Option<&T>
         O: Layer + HasLayers,
         T: Layer
 {
-    let key = T::name();
-    if let Some(layer) = origin.layers().get(key) {
-        let ptr = layer.as_ptr().cast::<T>();
-        return Some(unsafe { ptr.as_ref() });
-    }
-    None
+    origin.layers().get::<T>()
 }
 
 pub struct MultipartSlice<'t> {
@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::*;
+
+/// TCP connection oriented from the client (SYN sender or first seen side)
+/// to the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Connection {
+    pub client: [u8; 4],
+    pub client_port: u16,
+    pub server: [u8; 4],
+    pub server_port: u16,
+}
+
+impl std::fmt::Display for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f, "{}:{} -> {}:{}",
+            fmt_iter!(self.client, "."), self.client_port,
+            fmt_iter!(self.server, "."), self.server_port,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RTTKind {
+    /// SYN -> SYN/ACK
+    Handshake,
+    /// Data segment -> covering ACK
+    Data,
+    /// TSval -> matching TSecr
+    Timestamp,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RTTSample {
+    pub connection: Connection,
+    pub kind: RTTKind,
+    pub rtt: f64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct RTTStats {
+    pub samples: usize,
+    pub min: f32,
+    pub avg: f32,
+    pub max: f32,
+    pub p95: f32,
+}
+
+impl RTTStats {
+    pub fn compute(samples: impl IntoIterator<Item=f64>) -> Self {
+        let mut samples = samples.into_iter().collect::<Vec<_>>();
+        if samples.is_empty() {
+            return default();
+        }
+        samples.sort_by(|a, b| a.total_cmp(b));
+        let count = samples.len();
+        // nearest-rank percentile
+        let p95 = ((count as f64 * 0.95).ceil() as usize).max(1) - 1;
+        RTTStats {
+            samples: count,
+            min: samples[0] as f32,
+            avg: (samples.iter().sum::<f64>() / count as f64) as f32,
+            max: samples[count - 1] as f32,
+            p95: samples[p95] as f32,
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct RTTCount {
+    pub samples: Vec<RTTSample>,
+
+    pub handshake: RTTStats,
+    pub data: RTTStats,
+    pub timestamp: RTTStats,
+    /// All kinds of samples per connection
+    pub connections: HashMap<Connection, RTTStats>,
+}
+
+impl RTTCount {
+    pub fn flush(&mut self) {
+        let by_kind = |kind: RTTKind| {
+            RTTStats::compute(
+                self.samples.iter()
+                    .filter(|s| s.kind == kind)
+                    .map(|s| s.rtt)
+            )
+        };
+        self.handshake = by_kind(RTTKind::Handshake);
+        self.data = by_kind(RTTKind::Data);
+        self.timestamp = by_kind(RTTKind::Timestamp);
+
+        let mut connections = HashMap::<Connection, Vec<f64>>::new();
+        for sample in &self.samples {
+            connections.entry(sample.connection).or_default().push(sample.rtt);
+        }
+        self.connections = connections.into_iter()
+            .map(|(c, s)| (c, RTTStats::compute(s)))
+            .collect();
+    }
+}
+
+/// Sent but not yet acknowledged state of one direction
+#[derive(Default, Debug)]
+struct Direction {
+    /// Segment end sequence -> (sent at, retransmitted)
+    unacked: BTreeMap<u32, (f64, bool)>,
+    /// TSval -> first seen at
+    tsvals: BTreeMap<u32, f64>,
+    /// Sequence number acknowledging the FIN
+    fin: Option<u32>,
+    /// The FIN was acknowledged
+    closed: bool,
+}
+
+#[derive(Default, Debug)]
+struct ConnectionState {
+    /// Time and sequence of the client SYN, `None` once answered or retransmitted
+    syn: Option<(f64, u32)>,
+    syn_retransmitted: bool,
+    client: Direction,
+    server: Direction,
+    /// Timestamp of the latest segment
+    last_seen: f64,
+}
+
+/// Produces RTT samples from the TCP segments of consecutive frames
+#[derive(Default, Debug)]
+pub struct RTTTracker {
+    connections: HashMap<Connection, ConnectionState>,
+    last_sweep: f64,
+}
+
+impl RTTTracker {
+    /// Upper bound of tracked segments or TSvals per direction
+    const MAX_OUTSTANDING: usize = 1024;
+    /// Seconds without segments after which a connection is forgotten, as
+    /// the default [`DissectionLimits::idle_timeout`]
+    pub const IDLE_TIMEOUT: f64 = 300.0;
+    const SWEEP_PERIOD: f64 = 1.0;
+
+    pub fn apply(&mut self, frame: &Frame) -> Vec<RTTSample> {
+        let mut samples = vec![];
+        self.expire(frame.ts);
+        let (ip, tcp) = match (frame.get_layer::<IPv4>(), frame.get_layer::<TCP>()) {
+            (Some(ip), Some(tcp)) => (ip, tcp),
+            _ => return samples,
+        };
+        let ts = frame.ts;
+        let forward = Connection {
+            client: ip.src,
+            client_port: tcp.src,
+            server: ip.dst,
+            server_port: tcp.dst,
+        };
+        let backward = Connection {
+            client: ip.dst,
+            client_port: tcp.dst,
+            server: ip.src,
+            server_port: tcp.src,
+        };
+        let (connection, from_client) = if self.connections.contains_key(&forward) {
+            (forward, true)
+        } else if self.connections.contains_key(&backward) {
+            (backward, false)
+        } else if tcp.flags.syn && tcp.flags.ack {
+            // handshake started before the capture
+            (backward, false)
+        } else {
+            (forward, true)
+        };
+        if tcp.flags.rst {
+            self.connections.remove(&connection);
+            return samples;
+        }
+        let state = self.connections.entry(connection).or_default();
+        state.last_seen = ts;
+        let mut sample = |kind, rtt| samples.push(RTTSample { connection, kind, rtt });
+
+        if tcp.flags.syn && !tcp.flags.ack && from_client {
+            if state.syn.is_some() || state.syn_retransmitted {
+                // Karn's algorithm: ambiguous
+                state.syn = None;
+                state.syn_retransmitted = true;
+            } else {
+                state.syn = Some((ts, tcp.sn));
+            }
+        } else if tcp.flags.syn && tcp.flags.ack && !from_client {
+            if let Some((syn_ts, syn_sn)) = state.syn.take() {
+                if tcp.ack_sn == syn_sn.wrapping_add(1) {
+                    sample(RTTKind::Handshake, ts - syn_ts);
+                }
+            }
+        }
+
+        let (sender, receiver) = if from_client {
+            (&mut state.client, &mut state.server)
+        } else {
+            (&mut state.server, &mut state.client)
+        };
+
+        if tcp.flags.ack {
+            if let Some(rtt) = receiver.acknowledge(tcp.ack_sn, ts) {
+                sample(RTTKind::Data, rtt);
+            }
+        }
+        if let Some((tsval, tsecr)) = tcp.timestamp() {
+            if tcp.flags.ack && tsecr != 0 {
+                if let Some(rtt) = receiver.echo(tsecr, ts) {
+                    sample(RTTKind::Timestamp, rtt);
+                }
+            }
+            if sender.tsvals.len() < Self::MAX_OUTSTANDING {
+                sender.tsvals.entry(tsval).or_insert(ts);
+            }
+        }
+        let len = tcp.seq_len();
+        if len > 0 && !tcp.flags.syn {
+            let end = tcp.sn.wrapping_add(len);
+            if let Some(sent) = sender.unacked.get_mut(&end) {
+                sent.1 = true;
+            } else if sender.unacked.len() < Self::MAX_OUTSTANDING {
+                sender.unacked.insert(end, (ts, false));
+            }
+        }
+        if tcp.flags.fin {
+            sender.fin = Some(tcp.sn.wrapping_add(len));
+        }
+        if tcp.flags.ack && receiver.fin.is_some_and(|fin| seq_le(fin, tcp.ack_sn)) {
+            receiver.closed = true;
+        }
+        if state.client.closed && state.server.closed {
+            self.connections.remove(&connection);
+        }
+        samples
+    }
+
+    /// Forgets connections idle for [`Self::IDLE_TIMEOUT`]
+    fn expire(&mut self, ts: f64) {
+        if ts - self.last_sweep < Self::SWEEP_PERIOD {
+            return;
+        }
+        self.last_sweep = ts;
+        self.connections.retain(|_, state| ts - state.last_seen <= Self::IDLE_TIMEOUT);
+    }
+
+    /// Connections tracked
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+}
+
+impl Direction {
+    /// Drops every segment covered by `ack` and measures the newest of them
+    fn acknowledge(&mut self, ack: u32, ts: f64) -> Option<f64> {
+        let acked = self.unacked.keys()
+            .copied()
+            .filter(|&end| seq_le(end, ack))
+            .collect::<Vec<_>>();
+        let mut rtt = None;
+        let mut newest = None;
+        for end in acked {
+            let (sent, retransmitted) = self.unacked.remove(&end).unwrap();
+            if newest.is_none_or(|n| seq_le(n, end)) {
+                newest = Some(end);
+                rtt = if retransmitted { None } else { Some(ts - sent) };
+            }
+        }
+        rtt
+    }
+
+    /// Drops every TSval up to `tsecr` and measures the echoed one
+    fn echo(&mut self, tsecr: u32, ts: f64) -> Option<f64> {
+        let sent = self.tsvals.remove(&tsecr);
+        self.tsvals.retain(|&tsval, _| !seq_le(tsval, tsecr));
+        sent.map(|sent| ts - sent)
+    }
+}
+
+/// `a <= b` in 32 bit sequence space
+fn seq_le(a: u32, b: u32) -> bool {
+    b.wrapping_sub(a) < 1 << 31
+}